@@ -0,0 +1,99 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use raypump_copytrading_bot::{
+    engine::backtest::{fetch_fixture, replay, BacktestConfig, Fixture},
+    utils::logger::Logger,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+/// Replays a target wallet's pump.fun trades and reports how copying them
+/// would have done. Runs offline once the fixture is cached.
+#[derive(Parser)]
+struct Args {
+    /// Wallet to backtest
+    target: String,
+    /// Cached transactions; fetched from RPC_HTTPS when missing
+    #[arg(long, default_value = "backtest.json")]
+    fixture: String,
+    /// Fetch again even if the fixture exists
+    #[arg(long)]
+    refresh: bool,
+    /// Most transactions to fetch
+    #[arg(long, default_value_t = 1000)]
+    limit: usize,
+    #[arg(long, default_value_t = 10.0)]
+    starting_sol: f64,
+    /// Percent of the target's size to copy
+    #[arg(long, default_value_t = 1.0)]
+    token_percent: f64,
+    /// Slippage tolerance in percent
+    #[arg(long, default_value_t = 10)]
+    slippage: u64,
+    /// Slots between the target's trade and the copy landing
+    #[arg(long, default_value_t = 1)]
+    latency_slots: u64,
+    /// Extra adverse price movement in bps
+    #[arg(long, default_value_t = 0)]
+    price_impact_bps: u64,
+    /// pump.fun curve fee in bps
+    #[arg(long, default_value_t = 95)]
+    fee_bps: u64,
+    /// Creator fee in bps, charged by curves with a creator
+    #[arg(long, default_value_t = 5)]
+    creator_fee_bps: u64,
+    /// Jito tip per copy in SOL
+    #[arg(long, default_value_t = 0.004)]
+    tip_sol: f64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    let args = Args::parse();
+    let logger = Logger::new("[BACKTEST] => ".to_string());
+    let target = Pubkey::from_str(&args.target)
+        .map_err(|e| anyhow!("invalid target {}: {}", args.target, e))?;
+
+    let fixture = if args.refresh || !Path::new(&args.fixture).exists() {
+        let rpc_https = std::env::var("RPC_HTTPS")
+            .map_err(|_| anyhow!("RPC_HTTPS is needed to fetch {}", args.fixture))?;
+        let client = RpcClient::new_with_commitment(rpc_https, CommitmentConfig::confirmed());
+        let fixture = fetch_fixture(&client, &target, args.limit, &logger).await?;
+        fixture.save(&args.fixture)?;
+        logger.log(format!(
+            "cached {} transactions to {}",
+            fixture.transactions.len(),
+            args.fixture
+        ));
+        fixture
+    } else {
+        Fixture::load(&args.fixture)?
+    };
+    if fixture.target != target.to_string() {
+        return Err(anyhow!(
+            "{} holds transactions of {}, not {}",
+            args.fixture,
+            fixture.target,
+            target
+        ));
+    }
+
+    let report = replay(
+        &fixture,
+        &BacktestConfig {
+            starting_sol: args.starting_sol,
+            token_percent: args.token_percent,
+            slippage: args.slippage,
+            latency_slots: args.latency_slots,
+            price_impact_bps: args.price_impact_bps,
+            fee_basis_points: args.fee_bps,
+            creator_fee_basis_points: args.creator_fee_bps,
+            tip_sol: args.tip_sol,
+        },
+    );
+    logger.log(format!("\n{}", report.summary()));
+    Ok(())
+}
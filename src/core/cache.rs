@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::pubsub_client::{PubsubClient, PubsubClientError},
+    rpc_config::RpcAccountInfoConfig,
+};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, hash::Hash, pubkey::Pubkey,
+};
+use tokio::{
+    sync::{Mutex, OnceCell},
+    time::Instant,
+};
+
+use crate::{services::rpc_pool::RpcPool, utils::logger::Logger};
+
+pub const BLOCKHASH_REFRESH_INTERVAL: Duration = Duration::from_millis(400);
+// a cached blockhash older than this is not trusted on the hot path
+pub const BLOCKHASH_MAX_AGE: Duration = Duration::from_secs(5);
+/// How long a bonding curve touched by a target stays subscribed.
+pub const TARGET_WATCH_TTL: Duration = Duration::from_secs(10 * 60);
+/// How long a bonding curve of a token we bought stays subscribed.
+pub const HELD_WATCH_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const WATCH_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+static BLOCKHASH_REFRESHER: OnceCell<()> = OnceCell::const_new();
+
+struct WatchedAccount {
+    data: Option<Vec<u8>>,
+    expires_at: Instant,
+}
+
+lazy_static! {
+    static ref LATEST_BLOCKHASH: RwLock<Option<(Hash, Instant)>> = RwLock::new(None);
+    static ref ACCOUNTS: RwLock<HashMap<Pubkey, WatchedAccount>> = RwLock::new(HashMap::new());
+    // shared by every account subscription, replaced once its connection drops
+    static ref PUBSUB_CLIENT: Mutex<Option<Arc<PubsubClient>>> = Mutex::new(None);
+}
+
+/// Starts the background blockhash refresher once per process.
+pub async fn start_blockhash_refresher(rpc_pool: Arc<RpcPool>) {
+    BLOCKHASH_REFRESHER
+        .get_or_init(|| async {
+            tokio::spawn(async move {
+                let logger = Logger::new("[BLOCKHASH-CACHE] => ".to_string());
+                let mut interval = tokio::time::interval(BLOCKHASH_REFRESH_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    match rpc_pool
+                        .read(|client| async move {
+                            client
+                                .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+                                .await
+                        })
+                        .await
+                    {
+                        Ok((blockhash, _)) => {
+                            *LATEST_BLOCKHASH.write().unwrap() = Some((blockhash, Instant::now()));
+                        }
+                        Err(e) => {
+                            logger.debug(format!("failed to refresh blockhash: {}", e));
+                        }
+                    }
+                }
+            });
+        })
+        .await;
+}
+
+/// The most recent blockhash if it was refreshed within `BLOCKHASH_MAX_AGE`.
+pub fn latest_blockhash() -> Option<Hash> {
+    LATEST_BLOCKHASH
+        .read()
+        .unwrap()
+        .filter(|(_, fetched_at)| fetched_at.elapsed() < BLOCKHASH_MAX_AGE)
+        .map(|(blockhash, _)| blockhash)
+}
+
+/// Latest account data received from the subscription, if the account is watched.
+pub fn account_data(pubkey: &Pubkey) -> Option<Vec<u8>> {
+    ACCOUNTS
+        .read()
+        .unwrap()
+        .get(pubkey)
+        .and_then(|account| account.data.clone())
+}
+
+async fn pubsub_client(rpc_wss: &str) -> Result<Arc<PubsubClient>, PubsubClientError> {
+    let mut shared = PUBSUB_CLIENT.lock().await;
+    if let Some(client) = shared.as_ref() {
+        return Ok(client.clone());
+    }
+    let client = Arc::new(PubsubClient::new(rpc_wss).await?);
+    *shared = Some(client.clone());
+    Ok(client)
+}
+
+/// Drops the shared client after its connection failed, unless another
+/// subscription already replaced it.
+async fn reset_pubsub_client(failed: &Arc<PubsubClient>) {
+    let mut shared = PUBSUB_CLIENT.lock().await;
+    if shared
+        .as_ref()
+        .is_some_and(|client| Arc::ptr_eq(client, failed))
+    {
+        *shared = None;
+    }
+}
+
+fn is_expired(pubkey: &Pubkey) -> bool {
+    ACCOUNTS
+        .read()
+        .unwrap()
+        .get(pubkey)
+        .map(|account| account.expires_at <= Instant::now())
+        .unwrap_or(true)
+}
+
+/// Subscribes to `pubkey` for at least `ttl` in the background; it returns at
+/// once so the copy path never waits on the connection. Watching an account
+/// that is already subscribed only extends its expiry.
+pub fn watch_account(rpc_wss: &str, pubkey: Pubkey, ttl: Duration) {
+    {
+        let mut accounts = ACCOUNTS.write().unwrap();
+        if let Some(account) = accounts.get_mut(&pubkey) {
+            account.expires_at = account.expires_at.max(Instant::now() + ttl);
+            return;
+        }
+        accounts.insert(
+            pubkey,
+            WatchedAccount {
+                data: None,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    let rpc_wss = rpc_wss.to_string();
+    tokio::spawn(async move {
+        // reconnects and resubscribes whenever the connection drops
+        while !is_expired(&pubkey) {
+            let result = match pubsub_client(&rpc_wss).await {
+                Ok(client) => {
+                    let result = follow_account(&client, &pubkey).await;
+                    if result.is_err() {
+                        reset_pubsub_client(&client).await;
+                    }
+                    result
+                }
+                Err(e) => Err(format!("failed to connect account subscriptions: {}", e)),
+            };
+            let Err(e) = result else {
+                break;
+            };
+            println!("Account subscription {} dropped: {}", pubkey, e);
+            // stale data is worse than a fetch
+            if let Some(watched) = ACCOUNTS.write().unwrap().get_mut(&pubkey) {
+                watched.data = None;
+            }
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+        ACCOUNTS.write().unwrap().remove(&pubkey);
+    });
+}
+
+/// Keeps `pubkey`'s cached data current until it expires. Fails when the
+/// subscription can't be made or its stream ends.
+async fn follow_account(client: &PubsubClient, pubkey: &Pubkey) -> Result<(), String> {
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::processed()),
+        ..RpcAccountInfoConfig::default()
+    };
+    let (mut stream, unsubscribe) = client
+        .account_subscribe(pubkey, Some(config))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut sweep = tokio::time::interval(WATCH_SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            update = stream.next() => match update {
+                Some(response) => {
+                    if let Some(account) = response.value.decode::<Account>() {
+                        if let Some(watched) = ACCOUNTS.write().unwrap().get_mut(pubkey) {
+                            watched.data = Some(account.data);
+                        }
+                    }
+                }
+                None => return Err("stream ended".to_string()),
+            },
+            _ = sweep.tick() => {
+                if is_expired(pubkey) {
+                    break;
+                }
+            }
+        }
+    }
+
+    drop(stream);
+    unsubscribe().await;
+    Ok(())
+}
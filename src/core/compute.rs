@@ -0,0 +1,123 @@
+use std::{collections::HashMap, env, str::FromStr, sync::Mutex};
+
+use lazy_static::lazy_static;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget, instruction::Instruction, pubkey::Pubkey,
+    transaction::Transaction,
+};
+
+use crate::utils::logger::Logger;
+
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+lazy_static! {
+    // instruction shape -> simulated compute unit limit (margin included)
+    static ref UNIT_LIMIT_CACHE: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
+}
+
+pub fn get_unit_limit() -> u32 {
+    env::var("UNIT_LIMIT")
+        .ok()
+        .and_then(|v| u32::from_str(&v).ok())
+        .unwrap_or(300_000)
+}
+
+fn simulate_unit_limit_enabled() -> bool {
+    env::var("SIMULATE_UNIT_LIMIT")
+        .map(|v| v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+fn unit_limit_margin_pct() -> u32 {
+    env::var("UNIT_LIMIT_MARGIN")
+        .ok()
+        .and_then(|v| u32::from_str(&v).ok())
+        .unwrap_or(20)
+}
+
+/// Identifies the instruction layout independently of the accounts and amounts,
+/// e.g. `[create ATA, pump buy]` or `[create wsol, init wsol, raydium swap, close wsol]`.
+/// The first data byte is enough to tell pump.fun, Raydium, system and token
+/// instructions apart.
+pub fn instruction_shape(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .filter(|ix| ix.program_id != compute_budget::id())
+        .map(|ix| format!("{}:{:?}", ix.program_id, ix.data.first()))
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+/// Returns the compute unit limit for `instructions`. With `SIMULATE_UNIT_LIMIT`
+/// enabled the first transaction of each instruction shape is simulated and
+/// `units_consumed` plus `UNIT_LIMIT_MARGIN` percent is cached for later sends.
+pub async fn unit_limit_for(
+    client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    logger: &Logger,
+) -> u32 {
+    if !simulate_unit_limit_enabled() {
+        return get_unit_limit();
+    }
+
+    let shape = instruction_shape(instructions);
+    if let Some(units) = UNIT_LIMIT_CACHE.lock().unwrap().get(&shape) {
+        return *units;
+    }
+
+    match simulate_units_consumed(client, payer, instructions).await {
+        Ok(units_consumed) => {
+            let units = (units_consumed * (100 + unit_limit_margin_pct() as u64) / 100)
+                .min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32;
+            logger.log(format!(
+                "simulated {} CU, caching limit {} for shape {}",
+                units_consumed, units, shape
+            ));
+            UNIT_LIMIT_CACHE.lock().unwrap().insert(shape, units);
+            units
+        }
+        Err(e) => {
+            logger.error(format!("compute unit simulation failed: {}", e));
+            get_unit_limit()
+        }
+    }
+}
+
+async fn simulate_units_consumed(
+    client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+) -> anyhow::Result<u64> {
+    let mut simulated = vec![
+        compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT),
+    ];
+    simulated.extend(
+        instructions
+            .iter()
+            .filter(|ix| ix.program_id != compute_budget::id())
+            .cloned(),
+    );
+    let transaction = Transaction::new_with_payer(&simulated, Some(payer));
+    let result = client
+        .simulate_transaction_with_config(
+            &transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::processed()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .await?
+        .value;
+
+    if let Some(err) = result.err {
+        return Err(anyhow::anyhow!("{}", err));
+    }
+    result
+        .units_consumed
+        .filter(|units| *units > 0)
+        .ok_or_else(|| anyhow::anyhow!("simulation returned no units consumed"))
+}
@@ -0,0 +1,100 @@
+use std::{env, str::FromStr};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::{engine::swap::SwapDirection, utils::logger::Logger};
+
+// getRecentPrioritizationFees accepts at most 128 addresses
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct PriorityFeeConfig {
+    /// Percentile (0-100) of recent fees paid for the touched accounts
+    pub percentile: u8,
+    /// Floor in micro-lamports, also used when the estimate is unavailable
+    pub min_unit_price: u64,
+    pub max_buy_unit_price: u64,
+    pub max_sell_unit_price: u64,
+}
+
+impl PriorityFeeConfig {
+    pub fn from_env() -> Self {
+        Self {
+            percentile: env_or("PRIORITY_FEE_PERCENTILE", 75_u8).min(100),
+            min_unit_price: env_or("UNIT_PRICE", 1),
+            max_buy_unit_price: env_or("PRIORITY_FEE_MAX_BUY", 1_000_000),
+            max_sell_unit_price: env_or("PRIORITY_FEE_MAX_SELL", 2_000_000),
+        }
+    }
+
+    pub fn cap(&self, swap_direction: &SwapDirection) -> u64 {
+        match swap_direction {
+            SwapDirection::Buy => self.max_buy_unit_price,
+            SwapDirection::Sell => self.max_sell_unit_price,
+        }
+    }
+}
+
+pub(crate) fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| T::from_str(&v).ok())
+        .unwrap_or(default)
+}
+
+/// Accounts whose write locks the transaction competes for; the fee payer is
+/// left out since it is only contended by our own transactions.
+pub fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = vec![];
+    for meta in instructions.iter().flat_map(|ix| ix.accounts.iter()) {
+        if meta.is_writable && !meta.is_signer && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+    accounts.truncate(MAX_FEE_ACCOUNTS);
+    accounts
+}
+
+/// Returns the fee at `percentile` of `fees`, or `None` when there are no samples.
+pub fn percentile_fee(mut fees: Vec<u64>, percentile: u8) -> Option<u64> {
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    let rank = (percentile.min(100) as usize * fees.len()).div_ceil(100);
+    Some(fees[rank.saturating_sub(1)])
+}
+
+/// Estimates the compute unit price (micro-lamports) for `instructions` from
+/// recent prioritization fees on the accounts they write to, clamped to the
+/// configured floor and per-direction cap.
+pub async fn estimate_unit_price(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    swap_direction: &SwapDirection,
+    config: &PriorityFeeConfig,
+    logger: &Logger,
+) -> u64 {
+    let accounts = writable_accounts(instructions);
+    let estimate = match client.get_recent_prioritization_fees(&accounts).await {
+        Ok(fees) => percentile_fee(
+            fees.iter().map(|fee| fee.prioritization_fee).collect(),
+            config.percentile,
+        ),
+        Err(e) => {
+            logger.error(format!("failed to get recent prioritization fees: {}", e));
+            None
+        }
+    };
+
+    let cap = config.cap(swap_direction).max(config.min_unit_price);
+    let unit_price = estimate
+        .unwrap_or(config.min_unit_price)
+        .clamp(config.min_unit_price, cap);
+    logger.debug(format!(
+        "priority fee p{}: {:?} -> {} micro-lamports",
+        config.percentile, estimate, unit_price
+    ));
+    unit_price
+}
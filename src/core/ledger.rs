@@ -0,0 +1,122 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{core::paper::PaperFill, dex::executor::DryRun, engine::swap::SwapDirection};
+
+pub const DEFAULT_LEDGER_PATH: &str = "ledger.jsonl";
+
+fn ledger_path() -> String {
+    env::var("LEDGER_PATH").unwrap_or_else(|_| DEFAULT_LEDGER_PATH.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillMode {
+    /// Quoted and built, never sent; the fill is the quote's expected output.
+    DryRun,
+    /// A dry run filled against the chat's paper account.
+    Paper,
+}
+
+/// A copy of a target's swap, one JSON line of the ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fill {
+    pub timestamp: i64,
+    pub chat_id: String,
+    pub mode: FillMode,
+    pub target: String,
+    pub target_signature: String,
+    pub venue: String,
+    pub mint: String,
+    pub direction: SwapDirection,
+    /// Lamports on buys, coin units on sells.
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub min_amount_out: u64,
+    pub transfer_fee: u64,
+    /// Tip and priority fee in lamports.
+    pub fee_lamports: u64,
+    /// Realized on paper sells.
+    #[serde(default)]
+    pub pnl_lamports: i64,
+}
+
+impl Fill {
+    pub fn dry_run(chat_id: &str, target: &str, target_signature: &str, dry_run: &DryRun) -> Self {
+        Self::new(FillMode::DryRun, chat_id, target, target_signature, dry_run)
+    }
+
+    pub fn paper(
+        chat_id: &str,
+        target: &str,
+        target_signature: &str,
+        dry_run: &DryRun,
+        fill: &PaperFill,
+    ) -> Self {
+        Self {
+            amount_in: fill.amount_in,
+            amount_out: fill.amount_out,
+            fee_lamports: fill.fee_lamports,
+            pnl_lamports: fill.pnl_lamports,
+            ..Self::new(FillMode::Paper, chat_id, target, target_signature, dry_run)
+        }
+    }
+
+    fn new(
+        mode: FillMode,
+        chat_id: &str,
+        target: &str,
+        target_signature: &str,
+        dry_run: &DryRun,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().timestamp(),
+            chat_id: chat_id.to_string(),
+            mode,
+            target: target.to_string(),
+            target_signature: target_signature.to_string(),
+            venue: dry_run.venue.to_string(),
+            mint: dry_run.mint.to_string(),
+            direction: dry_run.direction.clone(),
+            amount_in: dry_run.amount_in,
+            amount_out: dry_run.amount_out,
+            min_amount_out: dry_run.min_amount_out,
+            transfer_fee: dry_run.transfer_fee,
+            fee_lamports: dry_run.fees.total_lamports(),
+            pnl_lamports: 0,
+        }
+    }
+}
+
+/// Appends `fill` to the ledger at `LEDGER_PATH`.
+pub async fn record(fill: &Fill) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ledger_path())?;
+    writeln!(file, "{}", serde_json::to_string(fill)?)?;
+    Ok(())
+}
+
+/// Fills recorded for `chat_id`, oldest first.
+pub async fn read_fills(chat_id: &str) -> Result<Vec<Fill>> {
+    let data = match fs::read_to_string(ledger_path()) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut fills = vec![];
+    for line in data.lines().filter(|line| !line.trim().is_empty()) {
+        let fill: Fill = serde_json::from_str(line)?;
+        if fill.chat_id == chat_id {
+            fills.push(fill);
+        }
+    }
+    Ok(fills)
+}
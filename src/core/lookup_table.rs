@@ -0,0 +1,151 @@
+use std::{collections::HashMap, str::FromStr, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use solana_address_lookup_table_program::{
+    instruction::{create_lookup_table, extend_lookup_table},
+    state::AddressLookupTable,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount, commitment_config::CommitmentConfig,
+    compute_budget, pubkey::Pubkey, signature::Keypair, signer::Signer, system_program, sysvar,
+    transaction::Transaction,
+};
+
+use crate::{
+    dex::{
+        pump_fun::{cached_global, PUMP_ACCOUNT, PUMP_GLOBAL, PUMP_PROGRAM},
+        pump_instructions::{self, PumpLayout},
+        raydium::{AMM_PROGRAM, RAYDIUM_AUTHORITY_V4},
+    },
+    utils::logger::Logger,
+};
+
+// keeps each extend transaction well below the packet size limit
+const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+lazy_static! {
+    static ref LOOKUP_TABLES: Mutex<HashMap<Pubkey, AddressLookupTableAccount>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Accounts shared by every pump.fun and Raydium swap, independent of the mint.
+pub fn fixed_addresses() -> Result<Vec<Pubkey>> {
+    let mut addresses = vec![
+        system_program::id(),
+        sysvar::rent::id(),
+        compute_budget::id(),
+        spl_token::ID,
+        spl_token::native_mint::ID,
+        spl_associated_token_account::ID,
+    ];
+    for address in [
+        PUMP_PROGRAM,
+        PUMP_GLOBAL,
+        PUMP_ACCOUNT,
+        AMM_PROGRAM,
+        RAYDIUM_AUTHORITY_V4,
+    ] {
+        addresses.push(Pubkey::from_str(address)?);
+    }
+    addresses.extend(pump_instructions::fixed_accounts(PumpLayout::current()?));
+    if let Some(global) = cached_global() {
+        addresses.push(global.fee_recipient);
+    }
+    Ok(addresses)
+}
+
+/// Creates a lookup table owned by the wallet and fills it with `fixed_addresses`.
+pub async fn create_wallet_lookup_table(
+    client: &RpcClient,
+    keypair: &Keypair,
+    logger: &Logger,
+) -> Result<Pubkey> {
+    let owner = keypair.pubkey();
+    let recent_slot = client
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .await?;
+    let (create_instruction, table) = create_lookup_table(owner, owner, recent_slot);
+
+    let recent_blockhash = client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &[create_instruction],
+        Some(&owner),
+        &vec![keypair],
+        recent_blockhash,
+    );
+    let sig = client.send_and_confirm_transaction(&txn).await?;
+    logger.log(format!("created lookup table {}: {}", table, sig));
+
+    extend_wallet_lookup_table(client, keypair, &table, fixed_addresses()?, logger).await?;
+    Ok(table)
+}
+
+/// Appends the addresses that are not in the table yet.
+pub async fn extend_wallet_lookup_table(
+    client: &RpcClient,
+    keypair: &Keypair,
+    table: &Pubkey,
+    addresses: Vec<Pubkey>,
+    logger: &Logger,
+) -> Result<()> {
+    let owner = keypair.pubkey();
+    let existing = fetch_lookup_table(client, table).await?.addresses;
+    let mut new_addresses: Vec<Pubkey> = vec![];
+    for address in addresses {
+        if !existing.contains(&address) && !new_addresses.contains(&address) {
+            new_addresses.push(address);
+        }
+    }
+
+    for chunk in new_addresses.chunks(MAX_ADDRESSES_PER_EXTEND) {
+        let extend_instruction = extend_lookup_table(*table, owner, Some(owner), chunk.to_vec());
+        let recent_blockhash = client.get_latest_blockhash().await?;
+        let txn = Transaction::new_signed_with_payer(
+            &[extend_instruction],
+            Some(&owner),
+            &vec![keypair],
+            recent_blockhash,
+        );
+        let sig = client.send_and_confirm_transaction(&txn).await?;
+        logger.log(format!(
+            "extended lookup table {} with {} addresses: {}",
+            table,
+            chunk.len(),
+            sig
+        ));
+    }
+
+    LOOKUP_TABLES.lock().unwrap().remove(table);
+    Ok(())
+}
+
+async fn fetch_lookup_table(
+    client: &RpcClient,
+    table: &Pubkey,
+) -> Result<AddressLookupTableAccount> {
+    let data = client.get_account_data(table).await?;
+    let lookup_table = AddressLookupTable::deserialize(&data)
+        .map_err(|e| anyhow!("failed to deserialize lookup table {}: {}", table, e))?;
+    Ok(AddressLookupTableAccount {
+        key: *table,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+/// Returns the lookup table account, fetching it only on first use.
+pub async fn get_lookup_table(
+    client: &RpcClient,
+    table: &Pubkey,
+) -> Result<AddressLookupTableAccount> {
+    if let Some(account) = LOOKUP_TABLES.lock().unwrap().get(table) {
+        return Ok(account.clone());
+    }
+    let account = fetch_lookup_table(client, table).await?;
+    LOOKUP_TABLES
+        .lock()
+        .unwrap()
+        .insert(*table, account.clone());
+    Ok(account)
+}
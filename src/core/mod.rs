@@ -1,2 +1,9 @@
+pub mod cache;
+pub mod compute;
+pub mod fee;
+pub mod ledger;
+pub mod lookup_table;
+pub mod nonce;
+pub mod paper;
 pub mod token;
 pub mod tx;
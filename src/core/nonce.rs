@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use solana_client::nonblocking::{nonce_utils, rpc_client::RpcClient};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, instruction::Instruction, nonce::State,
+    pubkey::Pubkey, signature::Keypair, signer::Signer, system_instruction,
+    transaction::Transaction,
+};
+use tokio::{
+    sync::{Mutex as AsyncMutex, OwnedMutexGuard},
+    time::Instant,
+};
+
+use crate::{core::fee::env_or, utils::logger::Logger};
+
+pub const NONCE_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+lazy_static! {
+    // nonce account -> durable nonce value not yet consumed by a transaction
+    static ref NONCE_CACHE: Mutex<HashMap<Pubkey, Hash>> = Mutex::new(HashMap::new());
+    // nonce account -> held from taking its nonce until the advance is observed
+    static ref NONCE_LOCKS: Mutex<HashMap<Pubkey, Arc<AsyncMutex<()>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// How long a lease on a sent transaction waits for the nonce to advance
+/// before taking the transaction as dropped, from `NONCE_ADVANCE_TIMEOUT_MS`.
+pub fn nonce_advance_timeout() -> Duration {
+    Duration::from_millis(env_or("NONCE_ADVANCE_TIMEOUT_MS", 30_000))
+}
+
+/// Creates a durable nonce account with the wallet as authority.
+pub async fn create_nonce_account(
+    client: &RpcClient,
+    keypair: &Keypair,
+    logger: &Logger,
+) -> Result<Pubkey> {
+    let owner = keypair.pubkey();
+    let nonce_keypair = Keypair::new();
+    let nonce_account = nonce_keypair.pubkey();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(State::size())
+        .await?;
+
+    let instructions =
+        system_instruction::create_nonce_account(&owner, &nonce_account, &owner, rent);
+    let recent_blockhash = client.get_latest_blockhash().await?;
+    let txn = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&owner),
+        &vec![keypair, &nonce_keypair],
+        recent_blockhash,
+    );
+    let sig = client.send_and_confirm_transaction(&txn).await?;
+    logger.log(format!("created nonce account {}: {}", nonce_account, sig));
+
+    refresh_nonce(client, &nonce_account).await?;
+    Ok(nonce_account)
+}
+
+/// Must be the first instruction of a transaction signed with the durable nonce.
+pub fn advance_instruction(nonce_account: &Pubkey, authority: &Pubkey) -> Instruction {
+    system_instruction::advance_nonce_account(nonce_account, authority)
+}
+
+/// Reads the current nonce value from chain into the cache.
+pub async fn refresh_nonce(client: &RpcClient, nonce_account: &Pubkey) -> Result<Hash> {
+    let blockhash = read_nonce(client, nonce_account).await?;
+    NONCE_CACHE
+        .lock()
+        .unwrap()
+        .insert(*nonce_account, blockhash);
+    Ok(blockhash)
+}
+
+async fn read_nonce(client: &RpcClient, nonce_account: &Pubkey) -> Result<Hash> {
+    let account = nonce_utils::get_account_with_commitment(
+        client,
+        nonce_account,
+        CommitmentConfig::confirmed(),
+    )
+    .await
+    .map_err(|e| anyhow!("failed to get nonce account {}: {}", nonce_account, e))?;
+    let data = nonce_utils::data_from_account(&account)
+        .map_err(|e| anyhow!("invalid nonce account {}: {}", nonce_account, e))?;
+    Ok(data.blockhash())
+}
+
+/// A nonce value taken for one transaction. No other transaction can take a
+/// nonce of the same account until the lease is released, so swaps signed
+/// with one nonce account are sent one at a time: after a send the next one
+/// waits for the advance to confirm, or for `nonce_advance_timeout` when the
+/// transaction was dropped. Lower the timeout to give up on dropped sends
+/// sooner, at the risk of reusing a nonce whose advance is merely slow.
+pub struct NonceLease {
+    nonce_account: Pubkey,
+    blockhash: Hash,
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// Waits for the account's previous lease to be released, then returns its
+/// cached nonce value, removed since the advance instruction consumes it.
+/// Falls back to an RPC read when nothing is cached.
+pub async fn take_nonce(client: &RpcClient, nonce_account: &Pubkey) -> Result<NonceLease> {
+    let lock = NONCE_LOCKS
+        .lock()
+        .unwrap()
+        .entry(*nonce_account)
+        .or_default()
+        .clone();
+    let guard = lock.lock_owned().await;
+    let cached = NONCE_CACHE.lock().unwrap().remove(nonce_account);
+    let blockhash = match cached {
+        Some(blockhash) => blockhash,
+        None => read_nonce(client, nonce_account).await?,
+    };
+    Ok(NonceLease {
+        nonce_account: *nonce_account,
+        blockhash,
+        _guard: guard,
+    })
+}
+
+impl NonceLease {
+    pub fn blockhash(&self) -> Hash {
+        self.blockhash
+    }
+
+    /// Caches the account's next nonce value and releases the lease in the
+    /// background. When the transaction was `sent`, it waits until the account
+    /// has advanced past the taken value, so a consumed nonce is never handed
+    /// out again. Otherwise the value on chain is still unused.
+    pub fn release(self, client: Arc<RpcClient>, sent: bool) {
+        tokio::spawn(async move {
+            let deadline = Instant::now() + nonce_advance_timeout();
+            loop {
+                match read_nonce(&client, &self.nonce_account).await {
+                    // an unchanged value past the deadline was never consumed
+                    Ok(blockhash)
+                        if !sent || blockhash != self.blockhash || Instant::now() >= deadline =>
+                    {
+                        NONCE_CACHE
+                            .lock()
+                            .unwrap()
+                            .insert(self.nonce_account, blockhash);
+                        return;
+                    }
+                    Ok(_) => {}
+                    // left uncached; the next copy reads it itself
+                    Err(e) if Instant::now() >= deadline => {
+                        println!("Failed to refresh nonce {}: {}", self.nonce_account, e);
+                        return;
+                    }
+                    Err(_) => {}
+                }
+                tokio::time::sleep(NONCE_POLL_INTERVAL).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use spl_token::{amount_to_ui_amount, native_mint::DECIMALS};
+
+use crate::{
+    dex::executor::DryRun,
+    engine::swap::SwapDirection,
+    utils::file::{read_info, update_info},
+};
+
+pub const DEFAULT_PAPER_SOL: f64 = 10.0;
+
+/// Coins held in a paper account and the lamports paid for them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub amount: u64,
+    pub cost_lamports: u64,
+}
+
+/// A chat's virtual SOL balance and coin positions, kept in data.json under
+/// `paper`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperAccount {
+    pub enabled: bool,
+    pub starting_lamports: u64,
+    pub sol_lamports: u64,
+    pub realized_pnl_lamports: i64,
+    pub positions: BTreeMap<String, Position>,
+}
+
+/// What a paper copy actually filled; sells are capped at the position held.
+#[derive(Debug, Clone)]
+pub struct PaperFill {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_lamports: u64,
+    /// Realized on sells, zero on buys.
+    pub pnl_lamports: i64,
+}
+
+impl PaperAccount {
+    pub fn new(starting_lamports: u64) -> Self {
+        Self {
+            enabled: true,
+            starting_lamports,
+            sol_lamports: starting_lamports,
+            realized_pnl_lamports: 0,
+            positions: BTreeMap::new(),
+        }
+    }
+
+    /// Fills a dry-run copy at its quoted price.
+    pub fn apply_dry_run(&mut self, dry_run: &DryRun) -> Result<PaperFill> {
+        self.apply(
+            &dry_run.mint.to_string(),
+            &dry_run.direction,
+            dry_run.amount_in,
+            dry_run.amount_out,
+            dry_run.fees.total_lamports(),
+        )
+    }
+
+    /// Fills a swap of `amount_in` for `amount_out`: lamports for coin units on
+    /// buys, coin units for lamports on sells.
+    pub fn apply(
+        &mut self,
+        mint: &str,
+        direction: &SwapDirection,
+        amount_in: u64,
+        amount_out: u64,
+        fee_lamports: u64,
+    ) -> Result<PaperFill> {
+        let mint = mint.to_string();
+        match direction {
+            SwapDirection::Buy => {
+                let spent = amount_in + fee_lamports;
+                if spent > self.sol_lamports {
+                    return Err(anyhow!(
+                        "paper balance {} SOL can't cover {} SOL",
+                        amount_to_ui_amount(self.sol_lamports, DECIMALS),
+                        amount_to_ui_amount(spent, DECIMALS)
+                    ));
+                }
+                self.sol_lamports -= spent;
+                let position = self.positions.entry(mint).or_default();
+                position.amount += amount_out;
+                position.cost_lamports += spent;
+                Ok(PaperFill {
+                    amount_in,
+                    amount_out,
+                    fee_lamports,
+                    pnl_lamports: 0,
+                })
+            }
+            SwapDirection::Sell => {
+                let position = self
+                    .positions
+                    .get_mut(&mint)
+                    .filter(|position| position.amount > 0)
+                    .ok_or_else(|| anyhow!("no paper position in {}", mint))?;
+                // the quote is linear at the observed price over what is actually held
+                let sold = amount_in.min(position.amount);
+                let proceeds =
+                    (amount_out as u128 * sold as u128 / amount_in.max(1) as u128) as u64;
+                let cost = (position.cost_lamports as u128 * sold as u128 / position.amount as u128)
+                    as u64;
+                let received = proceeds.saturating_sub(fee_lamports);
+                let pnl_lamports = received as i64 - cost as i64;
+
+                position.amount -= sold;
+                position.cost_lamports -= cost;
+                if position.amount == 0 {
+                    self.positions.remove(&mint);
+                }
+                self.sol_lamports += received;
+                self.realized_pnl_lamports += pnl_lamports;
+                Ok(PaperFill {
+                    amount_in: sold,
+                    amount_out: proceeds,
+                    fee_lamports,
+                    pnl_lamports,
+                })
+            }
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "SOL: {} (started with {})\nRealized PnL: {} SOL",
+            amount_to_ui_amount(self.sol_lamports, DECIMALS),
+            amount_to_ui_amount(self.starting_lamports, DECIMALS),
+            self.realized_pnl_lamports as f64 / 10f64.powi(DECIMALS as i32)
+        );
+        if self.positions.is_empty() {
+            summary.push_str("\nNo open positions");
+        }
+        for (mint, position) in &self.positions {
+            summary.push_str(&format!(
+                "\n{}: {} units for {} SOL",
+                mint,
+                position.amount,
+                amount_to_ui_amount(position.cost_lamports, DECIMALS)
+            ));
+        }
+        summary
+    }
+}
+
+pub async fn load(chat_id: &str) -> Result<Option<PaperAccount>> {
+    let info = read_info(None).await.map_err(|e| anyhow!("{}", e))?;
+    from_info(&info, chat_id)
+}
+
+fn from_info(info: &Value, chat_id: &str) -> Result<Option<PaperAccount>> {
+    match info
+        .get(chat_id)
+        .and_then(|user_data| user_data.get("paper"))
+    {
+        Some(paper) => Ok(Some(serde_json::from_value(paper.clone())?)),
+        None => Ok(None),
+    }
+}
+
+fn store(info: &mut Value, chat_id: &str, account: &PaperAccount) -> Result<()> {
+    let user_data = info
+        .get_mut(chat_id)
+        .and_then(|user_data| user_data.as_object_mut())
+        .ok_or_else(|| anyhow!("no settings for chat {}", chat_id))?;
+    user_data.insert("paper".to_string(), json!(account));
+    Ok(())
+}
+
+pub async fn save(chat_id: &str, account: &PaperAccount) -> Result<()> {
+    update_info(None, |info| Ok(store(info, chat_id, account)?))
+        .await
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Replaces the chat's paper account with what `update` makes of it, saving
+/// nothing when it returns `None`.
+pub async fn update(
+    chat_id: &str,
+    update: impl FnOnce(Option<PaperAccount>) -> Option<PaperAccount>,
+) -> Result<Option<PaperAccount>> {
+    update_info(None, |info| {
+        let account = update(from_info(info, chat_id)?);
+        if let Some(account) = &account {
+            store(info, chat_id, account)?;
+        }
+        Ok(account)
+    })
+    .await
+    .map_err(|e| anyhow!("{}", e))
+}
+
+/// Fills a dry-run copy against the chat's paper account and saves it.
+pub async fn fill(chat_id: &str, dry_run: &DryRun) -> Result<(PaperFill, PaperAccount)> {
+    update_info(None, |info| {
+        let mut account =
+            from_info(info, chat_id)?.ok_or_else(|| anyhow!("paper trading is not set up"))?;
+        let fill = account.apply_dry_run(dry_run)?;
+        store(info, chat_id, &account)?;
+        Ok((fill, account))
+    })
+    .await
+    .map_err(|e| anyhow!("{}", e))
+}
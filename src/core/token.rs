@@ -1,26 +1,76 @@
+use lazy_static::lazy_static;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
 use spl_token_2022::{
-    extension::StateWithExtensionsOwned,
+    extension::{
+        transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensionsOwned,
+    },
     state::{Account, Mint},
 };
 use spl_token_client::{
     client::{ProgramClient, ProgramRpcClient, ProgramRpcClientSendTransaction},
     token::{Token, TokenError, TokenResult},
 };
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::services::rpc_pool::RpcPool;
+
+lazy_static! {
+    // mint -> owning token program, which never changes for a mint
+    static ref MINT_PROGRAMS: RwLock<HashMap<Pubkey, Pubkey>> = RwLock::new(HashMap::new());
+}
+
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::ID || *program_id == spl_token_2022::ID
+}
+
+/// Records the token program of `mint` when it is already known, e.g. from a
+/// target's transaction, so `get_token_program` doesn't have to fetch it.
+pub fn set_token_program(mint: Pubkey, token_program: Pubkey) {
+    if is_token_program(&token_program) {
+        MINT_PROGRAMS.write().unwrap().insert(mint, token_program);
+    }
+}
+
+/// Returns the token program (SPL Token or Token-2022) that owns `mint`.
+pub async fn get_token_program(rpc_pool: Arc<RpcPool>, mint: Pubkey) -> TokenResult<Pubkey> {
+    if mint == spl_token::native_mint::ID {
+        return Ok(spl_token::ID);
+    }
+    if let Some(token_program) = MINT_PROGRAMS.read().unwrap().get(&mint) {
+        return Ok(*token_program);
+    }
+    let account = rpc_pool
+        .read(|client| async move {
+            ProgramRpcClient::new(client, ProgramRpcClientSendTransaction)
+                .get_account(mint)
+                .await
+        })
+        .await
+        .map_err(TokenError::Client)?
+        .ok_or(TokenError::AccountNotFound)?;
+    if !is_token_program(&account.owner) {
+        return Err(TokenError::AccountInvalidOwner);
+    }
+    set_token_program(mint, account.owner);
+    Ok(account.owner)
+}
 
 pub fn get_associated_token_address(
-    client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    rpc_pool: Arc<RpcPool>,
     keypair: Arc<Keypair>,
     address: &Pubkey,
     owner: &Pubkey,
+    token_program: &Pubkey,
 ) -> Pubkey {
     let token_client = Token::new(
         Arc::new(ProgramRpcClient::new(
-            client.clone(),
+            rpc_pool.read_client(),
             ProgramRpcClientSendTransaction,
         )),
-        &spl_token::ID,
+        token_program,
         address,
         None,
         Arc::new(Keypair::from_bytes(&keypair.to_bytes()).expect("failed to copy keypair")),
@@ -29,16 +79,16 @@ pub fn get_associated_token_address(
 }
 
 pub async fn get_account_info(
-    client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    rpc_pool: Arc<RpcPool>,
     address: Pubkey,
     account: Pubkey,
 ) -> TokenResult<StateWithExtensionsOwned<Account>> {
-    let program_client = Arc::new(ProgramRpcClient::new(
-        client.clone(),
-        ProgramRpcClientSendTransaction,
-    ));
-    let account = program_client
-        .get_account(account)
+    let account = rpc_pool
+        .read(|client| async move {
+            ProgramRpcClient::new(client, ProgramRpcClientSendTransaction)
+                .get_account(account)
+                .await
+        })
         .await
         .map_err(TokenError::Client)?
         .ok_or(TokenError::AccountNotFound)
@@ -49,7 +99,7 @@ pub async fn get_account_info(
             // ));
         })?;
 
-    if account.owner != spl_token::ID {
+    if !is_token_program(&account.owner) {
         return Err(TokenError::AccountInvalidOwner);
     }
     let account = StateWithExtensionsOwned::<Account>::unpack(account.data)?;
@@ -61,24 +111,25 @@ pub async fn get_account_info(
 }
 
 pub async fn get_mint_info(
-    client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    rpc_pool: Arc<RpcPool>,
     _keypair: Arc<Keypair>,
     address: Pubkey,
 ) -> TokenResult<StateWithExtensionsOwned<Mint>> {
-    let program_client = Arc::new(ProgramRpcClient::new(
-        client.clone(),
-        ProgramRpcClientSendTransaction,
-    ));
-    let account = program_client
-        .get_account(address)
+    let account = rpc_pool
+        .read(|client| async move {
+            ProgramRpcClient::new(client, ProgramRpcClientSendTransaction)
+                .get_account(address)
+                .await
+        })
         .await
         .map_err(TokenError::Client)?
         .ok_or(TokenError::AccountNotFound)
         .inspect_err(|err| println!("{} {}: mint {}", address, err, address))?;
 
-    if account.owner != spl_token::ID {
+    if !is_token_program(&account.owner) {
         return Err(TokenError::AccountInvalidOwner);
     }
+    set_token_program(address, account.owner);
 
     let mint_result = StateWithExtensionsOwned::<Mint>::unpack(account.data).map_err(Into::into);
     let decimals: Option<u8> = None;
@@ -90,3 +141,23 @@ pub async fn get_mint_info(
 
     mint_result
 }
+
+/// Fee withheld by the Token-2022 transfer fee extension when `amount` of
+/// `mint` is transferred; zero for mints without the extension. The epoch is
+/// only fetched for mints that carry a fee.
+pub async fn get_transfer_fee(
+    rpc_pool: Arc<RpcPool>,
+    mint: &StateWithExtensionsOwned<Mint>,
+    amount: u64,
+) -> TokenResult<u64> {
+    let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    let epoch_info = rpc_pool
+        .read(|client| async move { client.get_epoch_info().await })
+        .await
+        .map_err(|e| TokenError::Client(Box::new(e)))?;
+    Ok(transfer_fee_config
+        .calculate_epoch_fee(epoch_info.epoch, amount)
+        .unwrap_or(0))
+}
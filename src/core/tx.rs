@@ -1,189 +1,346 @@
-use std::{env, str::FromStr, sync::Arc, time::Duration};
-
-use anyhow::Result;
-use solana_client::rpc_client::RpcClient;
+use anyhow::{anyhow, Result};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
 use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    compute_budget::ComputeBudgetInstruction,
     hash::Hash,
     instruction::Instruction,
-    signature::Keypair,
+    message::{v0, VersionedMessage},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
     signer::Signer,
     system_instruction, system_transaction,
-    transaction::{Transaction, VersionedTransaction},
+    transaction::VersionedTransaction,
 };
 use spl_token::ui_amount_to_amount;
-
-use jito_json_rpc_client::jsonrpc_client::rpc_client::RpcClient as JitoRpcClient;
 use tokio::time::Instant;
 
 use crate::{
-    services::jito::{get_tip_account, wait_for_bundle_confirmation, JitoClient},
-    utils::logger::Logger,
+    core::{
+        cache, compute,
+        fee::{self, PriorityFeeConfig},
+        lookup_table, nonce,
+    },
+    services::{
+        fanout::JitoFanout,
+        jito::{get_tip_account, JitoClient, BUNDLE_CONFIRM_TIMEOUT, BUNDLE_POLL_INTERVAL},
+        rpc_pool::RpcPool,
+    },
+    utils::{config::SwapConfig, logger::Logger},
 };
 
-fn get_unit_price() -> u64 {
-    env::var("UNIT_PRICE")
-        .ok()
-        .and_then(|v| u64::from_str(&v).ok())
-        .unwrap_or(1)
+/// What a swap pays to land on top of its own instructions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SendFees {
+    pub tip_lamports: u64,
+    /// Compute unit limit and price (micro-lamports); `None` when the venue
+    /// builds its own transaction.
+    pub compute_budget: Option<(u32, u64)>,
 }
 
-fn get_unit_limit() -> u32 {
-    env::var("UNIT_LIMIT")
-        .ok()
-        .and_then(|v| u32::from_str(&v).ok())
-        .unwrap_or(300_000)
+impl SendFees {
+    pub fn priority_fee_lamports(&self) -> u64 {
+        self.compute_budget
+            .map(|(units, micro_lamports)| {
+                (units as u128 * micro_lamports as u128).div_ceil(1_000_000) as u64
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn total_lamports(&self) -> u64 {
+        self.tip_lamports + self.priority_fee_lamports()
+    }
 }
 
+/// Sends `version_tx` together with a tip transfer as a Jito bundle and waits
+/// until the bundle is confirmed.
 pub async fn jito_confirm(
     jito_url: String,
     jito_tip_amount: f64,
-    client: &RpcClient,
     keypair: &Keypair,
     version_tx: VersionedTransaction,
     recent_block_hash: &Hash,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let tip_account = get_tip_account()?;
-    let jito_client = Arc::new(JitoRpcClient::new(format!("{}/api/v1/bundles", jito_url)));
     // jito tip, the upper limit is 0.1
     let tip_value = jito_tip_amount;
     let tip_lamports = ui_amount_to_amount(tip_value, spl_token::native_mint::DECIMALS);
 
-    // let simulate_result = client.simulate_transaction(&version_tx)?;
-    // logger.log("Tx Stimulate".to_string());
-    // if let Some(logs) = simulate_result.value.logs {
-    //     for log in logs {
-    //         logger.log(log.to_string());
-    //     }
-    // }
-    // if let Some(err) = simulate_result.value.err {
-    //     return Err(anyhow::anyhow!("{}", err));
-    // };
     let bundle: Vec<VersionedTransaction> = vec![
-            version_tx,
-            VersionedTransaction::from(system_transaction::transfer(
-                keypair,
-                &tip_account,
-                tip_lamports,
-                *recent_block_hash,
-            )),
-        ];
+        version_tx,
+        VersionedTransaction::from(system_transaction::transfer(
+            keypair,
+            &tip_account,
+            tip_lamports,
+            *recent_block_hash,
+        )),
+    ];
+    send_bundle_and_confirm(&jito_url, &bundle, logger).await
+}
+
+/// Sends `bundle` to the block engine and waits until it is confirmed.
+pub async fn send_bundle_and_confirm(
+    jito_url: &str,
+    bundle: &[VersionedTransaction],
+    logger: &Logger,
+) -> Result<Vec<String>> {
+    let jito_client = JitoClient::new(jito_url);
     let start_time = Instant::now();
-    let bundle_id = jito_client.send_bundle(&bundle).await.unwrap();
+    let bundle_id = jito_client
+        .send_bundle(bundle)
+        .await
+        .map_err(|e| anyhow!("{}", e))?;
     logger.log(format!(
-        "tx ellapsed({}): {:?}",
+        "bundle sent({}): {:?}",
         bundle_id,
         start_time.elapsed()
     ));
-    Ok(vec![bundle_id])
-    // wait_for_bundle_confirmation(
-    //     move |id: String| {
-    //         let client = Arc::clone(&jito_client);
-    //         async move {
-    //             let response = client.get_bundle_statuses(&[id]).await;
-    //             let statuses = response.inspect_err(|err| {
-    //                 println!("Error fetching bundle status: {:?}", err);
-    //             })?;
-    //             Ok(statuses.value)
-    //         }
-    //     },
-    //     bundle_id,
-    //     Duration::from_millis(1000),
-    //     Duration::from_secs(10),
-    // )
-    // .await
+
+    let signatures = jito_client
+        .wait_for_bundle(
+            &bundle_id,
+            BUNDLE_POLL_INTERVAL,
+            BUNDLE_CONFIRM_TIMEOUT,
+            logger,
+        )
+        .await
+        .map_err(|e| anyhow!("{}", e))?;
+    logger.log(format!(
+        "bundle confirmed({}): {:?}",
+        bundle_id,
+        start_time.elapsed()
+    ));
+    Ok(signatures)
+}
+
+/// Compiles `instructions` into a signed v0 transaction, resolving accounts
+/// through `lookup_tables` where possible.
+pub fn build_v0_transaction(
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let message = v0::Message::try_compile(
+        &keypair.pubkey(),
+        instructions,
+        lookup_tables,
+        recent_blockhash,
+    )?;
+    let txn = VersionedTransaction::try_new(VersionedMessage::V0(message), &[keypair])?;
+    Ok(txn)
+}
+
+/// Prepends the estimated compute unit price and the simulated unit limit,
+/// returning both. Jito sends get them too; the tip doesn't replace either.
+async fn add_compute_budget(
+    client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &mut Vec<Instruction>,
+    swap_config: &SwapConfig,
+    logger: &Logger,
+) -> (u32, u64) {
+    let units = compute::unit_limit_for(client, payer, instructions, logger).await;
+    let micro_lamports = fee::estimate_unit_price(
+        client,
+        instructions,
+        &swap_config.swap_direction,
+        &PriorityFeeConfig::from_env(),
+        logger,
+    )
+    .await;
+    instructions.insert(
+        0,
+        ComputeBudgetInstruction::set_compute_unit_price(micro_lamports),
+    );
+    instructions.insert(1, ComputeBudgetInstruction::set_compute_unit_limit(units));
+    (units, micro_lamports)
+}
+
+async fn lookup_tables(
+    client: &RpcClient,
+    swap_config: &SwapConfig,
+    logger: &Logger,
+) -> Vec<AddressLookupTableAccount> {
+    match &swap_config.lookup_table {
+        Some(table) => match lookup_table::get_lookup_table(client, table).await {
+            Ok(account) => vec![account],
+            Err(e) => {
+                logger.error(format!("lookup table {} unavailable: {}", table, e));
+                vec![]
+            }
+        },
+        None => vec![],
+    }
+}
+
+/// Compiles `instructions` the way `new_signed_and_send` would send them,
+/// without signing, sending, or taking the durable nonce. The blockhash is the
+/// cached one, or a placeholder; it doesn't change the size.
+pub async fn dry_run_transaction(
+    jito_tip_amount: f64,
+    rpc_pool: &RpcPool,
+    payer: &Pubkey,
+    mut instructions: Vec<Instruction>,
+    swap_config: &SwapConfig,
+    logger: &Logger,
+) -> Result<(VersionedTransaction, SendFees)> {
+    let client = &rpc_pool.read_client();
+    let mut fees = SendFees {
+        compute_budget: Some(
+            add_compute_budget(client, payer, &mut instructions, swap_config, logger).await,
+        ),
+        ..SendFees::default()
+    };
+    let lookup_tables = lookup_tables(client, swap_config, logger).await;
+    if let Some(nonce_account) = &swap_config.nonce_account {
+        instructions.insert(0, nonce::advance_instruction(nonce_account, payer));
+    }
+    if swap_config.use_jito {
+        fees.tip_lamports = ui_amount_to_amount(jito_tip_amount, spl_token::native_mint::DECIMALS);
+        // bundles without a nonce tip in a transaction of their own
+        if !swap_config.use_bundle || swap_config.nonce_account.is_some() {
+            instructions.push(system_instruction::transfer(
+                payer,
+                &get_tip_account()?,
+                fees.tip_lamports,
+            ));
+        }
+    }
+    let recent_blockhash = cache::latest_blockhash().unwrap_or_default();
+    let message = v0::Message::try_compile(payer, &instructions, &lookup_tables, recent_blockhash)?;
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header.num_required_signatures as usize],
+        message: VersionedMessage::V0(message),
+    };
+    Ok((transaction, fees))
 }
 
 pub async fn new_signed_and_send(
     jito_url: String,
     jito_tip_amount: f64,
-    client: &RpcClient,
+    rpc_pool: &RpcPool,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
-    use_jito: bool,
+    swap_config: &SwapConfig,
     logger: &Logger,
 ) -> Result<Vec<String>> {
-    let micro_lamports = get_unit_price();
-    let units = get_unit_limit();
-    // If not using Jito, manually set the compute unit price and limit
-    if !use_jito {
-        let modify_compute_units =
-            solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(
-                micro_lamports,
+    let client = &rpc_pool.read_client();
+    let use_jito = swap_config.use_jito;
+    let use_bundle = swap_config.use_bundle;
+    add_compute_budget(
+        client,
+        &keypair.pubkey(),
+        &mut instructions,
+        swap_config,
+        logger,
+    )
+    .await;
+
+    let lookup_tables = lookup_tables(client, swap_config, logger).await;
+
+    // With a durable nonce the transaction is signed against the cached nonce
+    // value instead of a freshly fetched blockhash.
+    let (recent_blockhash, nonce_lease) = match &swap_config.nonce_account {
+        Some(nonce_account) => {
+            instructions.insert(
+                0,
+                nonce::advance_instruction(nonce_account, &keypair.pubkey()),
             );
-        let add_priority_fee =
-            solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(units);
+            let lease = nonce::take_nonce(client, nonce_account).await?;
+            (lease.blockhash(), Some(lease))
+        }
+        None => match cache::latest_blockhash() {
+            Some(blockhash) => (blockhash, None),
+            None => (client.get_latest_blockhash().await?, None),
+        },
+    };
 
-        instructions.insert(0, modify_compute_units);
-        instructions.insert(1, add_priority_fee);
-    }
+    let sent: Result<Vec<String>> = async {
+        let mut txs = vec![];
+        if use_jito && use_bundle && swap_config.nonce_account.is_some() {
+            // a second tip transaction can't share the nonce, so tip inside the swap
+            let tip_lamports =
+                ui_amount_to_amount(jito_tip_amount, spl_token::native_mint::DECIMALS);
+            instructions.push(system_instruction::transfer(
+                &keypair.pubkey(),
+                &get_tip_account()?,
+                tip_lamports,
+            ));
+            let txn =
+                build_v0_transaction(keypair, &instructions, &lookup_tables, recent_blockhash)?;
+            let signatures = send_bundle_and_confirm(&jito_url, &[txn], logger).await?;
+            txs.extend(signatures);
+        } else if use_jito && use_bundle {
+            // the tip goes in its own transaction at the end of the bundle
+            let txn =
+                build_v0_transaction(keypair, &instructions, &lookup_tables, recent_blockhash)?;
+            let signatures = jito_confirm(
+                jito_url,
+                jito_tip_amount,
+                keypair,
+                txn,
+                &recent_blockhash,
+                logger,
+            )
+            .await?;
+            txs.extend(signatures);
+        } else if use_jito {
+            let tip_account = get_tip_account()?;
+            // jito tip, the upper limit is 0.1
+            let tip_value = jito_tip_amount;
+            let tip_lamports = ui_amount_to_amount(tip_value, spl_token::native_mint::DECIMALS);
+
+            let jito_tip_instruction =
+                system_instruction::transfer(&keypair.pubkey(), &tip_account, tip_lamports);
+            instructions.push(jito_tip_instruction);
+
+            // send init tx
+            let txn =
+                build_v0_transaction(keypair, &instructions, &lookup_tables, recent_blockhash)?;
 
-    let mut txs = vec![];
-    if use_jito {
-        let tip_account = get_tip_account()?;
-        // let jito_client = Arc::new(JitoRpcClient::new(format!(
-        //     "{}/api/v1/bundles",
-        //     *jito::BLOCK_ENGINE_URL
-        // )));
-        // jito tip, the upper limit is 0.1
-        let tip_value = jito_tip_amount;
-        let tip_lamports = ui_amount_to_amount(tip_value, spl_token::native_mint::DECIMALS);
-
-        let jito_tip_instruction =
-            system_instruction::transfer(&keypair.pubkey(), &tip_account, tip_lamports);
-        instructions.push(jito_tip_instruction);
-
-        // send init tx
-        let recent_blockhash = client.get_latest_blockhash()?;
-        let txn = Transaction::new_signed_with_payer(
-            &instructions,
-            Some(&keypair.pubkey()),
-            &vec![keypair],
-            recent_blockhash,
-        );
-
-        // let simulate_result = client.simulate_transaction(&txn)?;
-        // logger.log("Tx Stimulate".to_string());
-        // if let Some(logs) = simulate_result.value.logs {
-        //     for log in logs {
-        //         logger.log(log.to_string());
-        //     }
-        // }
-        // if let Some(err) = simulate_result.value.err {
-        //     return Err(anyhow::anyhow!("{}", err));
-        // };
-
-        let start_time = Instant::now();
-
-        let jito_client = Arc::new(JitoClient::new(
-            format!("{}/api/v1/transactions", jito_url).as_str(),
-        ));
-        let sig = match jito_client.send_transaction(&txn).await {
-            Ok(signature) => signature,
-            Err(_) => {
-                // logger.log(format!("{}", e));
-                return Err(anyhow::anyhow!("Bundle status get timeout"));
+            let start_time = Instant::now();
+
+            // fan out to every configured block engine when available
+            let sig = match JitoFanout::global() {
+                Some(fanout) => fanout.send_transaction(&txn, logger).await,
+                None => JitoClient::new(&jito_url).send_transaction(&txn).await,
             }
-        };
-        txs.push(sig.clone().to_string());
-        logger.log(format!("tx ellapsed: {:?}", start_time.elapsed()));
-    } else {
-        // send init tx
-        let recent_blockhash = client.get_latest_blockhash()?;
-        let txn = Transaction::new_signed_with_payer(
-            &instructions,
-            Some(&keypair.pubkey()),
-            &vec![keypair],
-            recent_blockhash,
-        );
-        let sig = common::rpc::send_txn(client, &txn, true)?;
-        logger.log(format!(
-            "signature({}): {:#?}",
-            chrono::Utc::now().timestamp(),
-            sig
-        ));
-        txs.push(sig.to_string());
+            .map_err(|e| anyhow!("{}", e))?;
+            txs.push(sig.to_string());
+            logger.log(format!("tx ellapsed: {:?}", start_time.elapsed()));
+        } else {
+            // send init tx
+            let txn =
+                build_v0_transaction(keypair, &instructions, &lookup_tables, recent_blockhash)?;
+            let txn = &txn;
+            let sig = rpc_pool
+                .send(|client| async move {
+                    client
+                        .send_transaction_with_config(
+                            txn,
+                            RpcSendTransactionConfig {
+                                skip_preflight: true,
+                                ..RpcSendTransactionConfig::default()
+                            },
+                        )
+                        .await
+                })
+                .await?;
+            logger.log(format!(
+                "signature({}): {:#?}",
+                chrono::Utc::now().timestamp(),
+                sig
+            ));
+            txs.push(sig.to_string());
+        }
+        Ok(txs)
     }
-
-    Ok(txs)
+    .await;
+    // the next copy on this nonce account waits until it has advanced
+    if let Some(lease) = nonce_lease {
+        lease.release(client.clone(), sent.is_ok());
+    }
+    sent
 }
@@ -0,0 +1,359 @@
+use crate::dex::pump_fun::BondingCurveAccount;
+
+pub const FEE_DENOMINATOR: u64 = 10_000;
+
+/// Constant-product quote math of the pump.fun bonding curve, matching the
+/// program's integer rounding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BondingCurve {
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_token_reserves: u64,
+    pub fee_basis_points: u64,
+    /// Charged on top of the protocol fee on curves with a creator, 0 otherwise.
+    pub creator_fee_basis_points: u64,
+}
+
+impl BondingCurve {
+    pub fn from_account(
+        account: &BondingCurveAccount,
+        fee_basis_points: u64,
+        creator_fee_basis_points: u64,
+    ) -> Self {
+        Self {
+            virtual_sol_reserves: account.virtual_sol_reserves,
+            virtual_token_reserves: account.virtual_token_reserves,
+            real_token_reserves: account.real_token_reserves,
+            fee_basis_points,
+            creator_fee_basis_points,
+        }
+    }
+
+    /// Most tokens a single buy can take out of the curve.
+    pub fn max_buy_tokens(&self) -> u64 {
+        self.real_token_reserves
+    }
+
+    /// Protocol and creator fee on `lamports`, each rounded up like the program.
+    fn fee(&self, lamports: u64) -> u64 {
+        let fee = |basis_points: u64| {
+            (lamports as u128 * basis_points as u128).div_ceil(FEE_DENOMINATOR as u128) as u64
+        };
+        fee(self.fee_basis_points) + fee(self.creator_fee_basis_points)
+    }
+
+    /// Tokens received for spending `sol_amount` lamports, fees included.
+    pub fn buy_quote(&self, sol_amount: u64) -> u64 {
+        if sol_amount == 0 || self.virtual_token_reserves == 0 {
+            return 0;
+        }
+        // part of the input goes to the fee recipients, the rest into the curve;
+        // one lamport is held back for the program rounding its cost up
+        let sol_in = (sol_amount - 1) as u128 * FEE_DENOMINATOR as u128
+            / (FEE_DENOMINATOR + self.fee_basis_points + self.creator_fee_basis_points) as u128;
+        let tokens_out = sol_in * self.virtual_token_reserves as u128
+            / (self.virtual_sol_reserves as u128 + sol_in);
+        (tokens_out as u64).min(self.max_buy_tokens())
+    }
+
+    /// Lamports the program charges for buying exactly `token_amount`, fees included.
+    pub fn buy_cost(&self, token_amount: u64) -> Option<u64> {
+        let token_amount = token_amount.min(self.max_buy_tokens());
+        if token_amount >= self.virtual_token_reserves {
+            return None;
+        }
+        let sol_cost = self.virtual_sol_reserves as u128 * token_amount as u128
+            / (self.virtual_token_reserves - token_amount) as u128
+            + 1;
+        let sol_cost = u64::try_from(sol_cost).ok()?;
+        sol_cost.checked_add(self.fee(sol_cost))
+    }
+
+    /// Lamports received for selling `token_amount`, fees deducted.
+    pub fn sell_quote(&self, token_amount: u64) -> u64 {
+        if token_amount == 0 {
+            return 0;
+        }
+        let sol_out = token_amount as u128 * self.virtual_sol_reserves as u128
+            / (self.virtual_token_reserves as u128 + token_amount as u128);
+        let sol_out = sol_out as u64;
+        sol_out.saturating_sub(self.fee(sol_out))
+    }
+
+    /// Spot price in lamports per base token unit.
+    pub fn price(&self) -> f64 {
+        if self.virtual_token_reserves == 0 {
+            return 0.0;
+        }
+        self.virtual_sol_reserves as f64 / self.virtual_token_reserves as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `TradeEvent` fields of one fill: post-trade reserves, the lamports
+    /// that moved through the curve and the fees charged on top. The fills
+    /// below are worked out by hand with the program's rounding, they are not
+    /// taken from chain.
+    struct Fill {
+        is_buy: bool,
+        sol_amount: u64,
+        token_amount: u64,
+        virtual_sol_reserves: u64,
+        virtual_token_reserves: u64,
+        real_token_reserves: u64,
+        fee_basis_points: u64,
+        fee: u64,
+        creator_fee_basis_points: u64,
+        creator_fee: u64,
+    }
+
+    impl Fill {
+        /// The curve the fill traded against, from the event's post-trade reserves.
+        fn curve_before(&self) -> BondingCurve {
+            let (virtual_sol_reserves, virtual_token_reserves, real_token_reserves) = if self.is_buy
+            {
+                (
+                    self.virtual_sol_reserves - self.sol_amount,
+                    self.virtual_token_reserves + self.token_amount,
+                    self.real_token_reserves + self.token_amount,
+                )
+            } else {
+                (
+                    self.virtual_sol_reserves + self.sol_amount,
+                    self.virtual_token_reserves - self.token_amount,
+                    self.real_token_reserves - self.token_amount,
+                )
+            };
+            BondingCurve {
+                virtual_sol_reserves,
+                virtual_token_reserves,
+                real_token_reserves,
+                fee_basis_points: self.fee_basis_points,
+                creator_fee_basis_points: self.creator_fee_basis_points,
+            }
+        }
+    }
+
+    // first buy on a fresh curve with a creator
+    const FRESH_BUY: Fill = Fill {
+        is_buy: true,
+        sol_amount: 1_011_560_694,
+        token_amount: 35_000_000_000_000,
+        virtual_sol_reserves: 31_011_560_694,
+        virtual_token_reserves: 1_038_000_000_000_000,
+        real_token_reserves: 758_100_000_000_000,
+        fee_basis_points: 95,
+        fee: 9_609_827,
+        creator_fee_basis_points: 5,
+        creator_fee: 505_781,
+    };
+
+    // buy on a curve from before the creator fee
+    const LEGACY_BUY: Fill = Fill {
+        is_buy: true,
+        sol_amount: 32_861_477,
+        token_amount: 1_000_000_000_000,
+        virtual_sol_reserves: 32_532_861_477,
+        virtual_token_reserves: 989_000_000_000_000,
+        real_token_reserves: 709_100_000_000_000,
+        fee_basis_points: 100,
+        fee: 328_615,
+        creator_fee_basis_points: 0,
+        creator_fee: 0,
+    };
+
+    const MID_CURVE_SELL: Fill = Fill {
+        is_buy: false,
+        sol_amount: 763_812_265,
+        token_amount: 12_345_678_901_234,
+        virtual_sol_reserves: 44_236_187_735,
+        virtual_token_reserves: 727_345_678_901_234,
+        real_token_reserves: 447_445_678_901_234,
+        fee_basis_points: 95,
+        fee: 7_256_217,
+        creator_fee_basis_points: 5,
+        creator_fee: 381_907,
+    };
+
+    // the buy that completed the curve, taking every real token left
+    const COMPLETING_BUY: Fill = Fill {
+        is_buy: true,
+        sol_amount: 630_225_081,
+        token_amount: 2_100_000_000_000,
+        virtual_sol_reserves: 84_630_225_081,
+        virtual_token_reserves: 279_900_000_000_000,
+        real_token_reserves: 0,
+        fee_basis_points: 95,
+        fee: 5_987_139,
+        creator_fee_basis_points: 5,
+        creator_fee: 315_113,
+    };
+
+    fn assert_buy(fill: &Fill) {
+        let curve = fill.curve_before();
+        let paid = fill.sol_amount + fill.fee + fill.creator_fee;
+        assert_eq!(curve.buy_cost(fill.token_amount), Some(paid));
+
+        // the quote for what the fill paid must be affordable at that price
+        let tokens = curve.buy_quote(paid);
+        assert!(curve.buy_cost(tokens).unwrap() <= paid);
+        assert!(tokens.abs_diff(fill.token_amount) <= fill.token_amount / 100_000);
+    }
+
+    #[test]
+    fn buy_matches_fill_with_creator_fee() {
+        assert_buy(&FRESH_BUY);
+    }
+
+    #[test]
+    fn buy_matches_legacy_fill() {
+        assert_buy(&LEGACY_BUY);
+    }
+
+    #[test]
+    fn sell_matches_fill_with_creator_fee() {
+        let fill = &MID_CURVE_SELL;
+        assert_eq!(
+            fill.curve_before().sell_quote(fill.token_amount),
+            fill.sol_amount - fill.fee - fill.creator_fee
+        );
+    }
+
+    #[test]
+    fn creator_fee_is_charged_on_top() {
+        let with_creator = FRESH_BUY.curve_before();
+        let without_creator = BondingCurve {
+            creator_fee_basis_points: 0,
+            ..with_creator
+        };
+        assert_eq!(
+            with_creator.buy_cost(FRESH_BUY.token_amount).unwrap()
+                - without_creator.buy_cost(FRESH_BUY.token_amount).unwrap(),
+            FRESH_BUY.creator_fee
+        );
+        assert!(
+            with_creator.sell_quote(MID_CURVE_SELL.token_amount)
+                < without_creator.sell_quote(MID_CURVE_SELL.token_amount)
+        );
+    }
+
+    #[test]
+    fn buys_are_capped_at_real_token_reserves() {
+        let fill = &COMPLETING_BUY;
+        let curve = fill.curve_before();
+        let paid = fill.sol_amount + fill.fee + fill.creator_fee;
+        assert_eq!(curve.buy_cost(fill.token_amount), Some(paid));
+        // asking for more than is left costs the same as what is left
+        assert_eq!(curve.buy_cost(fill.token_amount * 2), Some(paid));
+        assert_eq!(curve.buy_quote(10 * paid), fill.token_amount);
+    }
+
+    #[test]
+    fn zero_amounts_quote_nothing() {
+        let curve = FRESH_BUY.curve_before();
+        assert_eq!(curve.buy_quote(0), 0);
+        assert_eq!(curve.sell_quote(0), 0);
+    }
+
+    /// Curves from launch to near completion, with and without a creator fee.
+    fn curves() -> Vec<BondingCurve> {
+        // the launch curve's constant product, and the virtual tokens that are
+        // never sold
+        let k = 30_000_000_000u128 * 1_073_000_000_000_000;
+        let unsold_tokens = 279_900_000_000_000;
+        let mut curves = vec![];
+        for step in 0..12u64 {
+            let virtual_sol_reserves = 30_000_000_000 + step * 4_800_000_000;
+            let virtual_token_reserves = (k / virtual_sol_reserves as u128) as u64;
+            for (fee_basis_points, creator_fee_basis_points) in [(95, 5), (100, 0), (93, 30)] {
+                curves.push(BondingCurve {
+                    virtual_sol_reserves,
+                    virtual_token_reserves,
+                    real_token_reserves: virtual_token_reserves - unsold_tokens,
+                    fee_basis_points,
+                    creator_fee_basis_points,
+                });
+            }
+        }
+        curves
+    }
+
+    /// Amounts from a few lamports or units to beyond what any curve holds.
+    fn amounts() -> impl Iterator<Item = u64> {
+        std::iter::successors(Some(1u64), |amount| Some(amount * 3 / 2 + 1))
+            .take_while(|amount| *amount < 2_000_000_000_000_000)
+    }
+
+    #[test]
+    fn quoted_buys_are_affordable() {
+        for curve in curves() {
+            for sol_amount in amounts() {
+                let tokens = curve.buy_quote(sol_amount);
+                if tokens > 0 {
+                    assert!(
+                        curve.buy_cost(tokens).unwrap() <= sol_amount,
+                        "{:?} quoted {} for {}",
+                        curve,
+                        tokens,
+                        sol_amount
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_rise_with_the_amount() {
+        for curve in curves() {
+            let mut last = (0, 0, Some(0));
+            for amount in amounts() {
+                let quotes = (
+                    curve.buy_quote(amount),
+                    curve.sell_quote(amount),
+                    curve.buy_cost(amount),
+                );
+                assert!(
+                    quotes.0 >= last.0,
+                    "{:?} buy quote fell at {}",
+                    curve,
+                    amount
+                );
+                assert!(
+                    quotes.1 >= last.1,
+                    "{:?} sell quote fell at {}",
+                    curve,
+                    amount
+                );
+                if let (Some(cost), Some(last_cost)) = (quotes.2, last.2) {
+                    assert!(cost >= last_cost, "{:?} buy cost fell at {}", curve, amount);
+                }
+                last = quotes;
+            }
+        }
+    }
+
+    #[test]
+    fn sells_never_pay_out_more_than_the_virtual_sol_reserves() {
+        for curve in curves() {
+            for token_amount in amounts().chain([u64::MAX]) {
+                assert!(curve.sell_quote(token_amount) < curve.virtual_sol_reserves);
+            }
+        }
+    }
+
+    #[test]
+    fn buys_never_take_more_than_real_token_reserves() {
+        for curve in curves() {
+            let all = curve.buy_cost(curve.real_token_reserves);
+            for amount in amounts() {
+                assert!(curve.buy_quote(amount) <= curve.real_token_reserves);
+                if amount > curve.real_token_reserves {
+                    assert_eq!(curve.buy_cost(amount), all);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::{future::Future, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use solana_sdk::{
+    instruction::Instruction, packet::PACKET_DATA_SIZE, pubkey::Pubkey, signature::Keypair,
+    signer::Signer, system_instruction, transaction::VersionedTransaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token::ui_amount_to_amount;
+use tokio::time::Instant;
+
+use crate::{
+    core::{
+        token,
+        tx::{self, SendFees},
+    },
+    engine::swap::{SwapDirection, SwapInType},
+    services::rpc_pool::RpcPool,
+    utils::{config::SwapConfig, logger::Logger},
+};
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// The bound a swap is sent with, which the program enforces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapBound {
+    /// Exact input, for at least this much output.
+    MinAmountOut(u64),
+    /// Exact output, for at most this much input.
+    MaxAmountIn(u64),
+}
+
+/// A priced swap of a coin against SOL, ready to be built.
+#[derive(Debug, Clone)]
+pub struct SwapQuote<R> {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub direction: SwapDirection,
+    /// Lamports on buys, coin units on sells.
+    pub amount_in: u64,
+    /// Token-2022 fee withheld from a sell before it reaches the pool.
+    pub transfer_fee: u64,
+    /// Expected output before slippage.
+    pub amount_out: u64,
+    pub bound: SwapBound,
+    /// Closes the coin account after a sell-all.
+    pub close_instruction: Option<Instruction>,
+    /// Venue accounts the swap instruction is built from.
+    pub route: R,
+}
+
+impl<R> SwapQuote<R> {
+    /// Most the swap may take from the wallet.
+    pub fn max_amount_in(&self) -> u64 {
+        match self.bound {
+            SwapBound::MaxAmountIn(max_amount_in) => max_amount_in,
+            SwapBound::MinAmountOut(_) => self.amount_in,
+        }
+    }
+
+    /// Least the swap may pay out.
+    pub fn min_amount_out(&self) -> u64 {
+        match self.bound {
+            SwapBound::MinAmountOut(min_amount_out) => min_amount_out,
+            SwapBound::MaxAmountIn(_) => self.amount_out,
+        }
+    }
+
+    /// The same swap over another venue's accounts.
+    pub fn with_route<T>(&self, route: T) -> SwapQuote<T> {
+        SwapQuote {
+            mint: self.mint,
+            token_program: self.token_program,
+            direction: self.direction.clone(),
+            amount_in: self.amount_in,
+            transfer_fee: self.transfer_fee,
+            amount_out: self.amount_out,
+            bound: self.bound,
+            close_instruction: self.close_instruction.clone(),
+            route,
+        }
+    }
+
+    pub fn map_route<T>(self, f: impl FnOnce(R) -> T) -> SwapQuote<T> {
+        SwapQuote {
+            mint: self.mint,
+            token_program: self.token_program,
+            direction: self.direction,
+            amount_in: self.amount_in,
+            transfer_fee: self.transfer_fee,
+            amount_out: self.amount_out,
+            bound: self.bound,
+            close_instruction: self.close_instruction,
+            route: f(self.route),
+        }
+    }
+}
+
+/// A quoted swap compiled into the transaction it would be sent as, neither
+/// signed nor sent.
+#[derive(Debug, Clone)]
+pub struct DryRun {
+    pub venue: &'static str,
+    pub mint: Pubkey,
+    pub direction: SwapDirection,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub min_amount_out: u64,
+    pub max_amount_in: u64,
+    pub transfer_fee: u64,
+    pub fees: SendFees,
+    pub transaction: VersionedTransaction,
+    pub serialized_size: usize,
+}
+
+impl DryRun {
+    pub fn new<R>(
+        venue: &'static str,
+        quote: &SwapQuote<R>,
+        transaction: VersionedTransaction,
+        fees: SendFees,
+    ) -> Result<Self> {
+        let serialized_size = bincode::serialized_size(&transaction)? as usize;
+        Ok(Self {
+            venue,
+            mint: quote.mint,
+            direction: quote.direction.clone(),
+            amount_in: quote.amount_in,
+            amount_out: quote.amount_out,
+            min_amount_out: quote.min_amount_out(),
+            max_amount_in: quote.max_amount_in(),
+            transfer_fee: quote.transfer_fee,
+            fees,
+            transaction,
+            serialized_size,
+        })
+    }
+
+    /// Accounts the transaction loads directly, lookup table entries aside.
+    pub fn accounts(&self) -> &[Pubkey] {
+        self.transaction.message.static_account_keys()
+    }
+
+    /// Whether the transaction fits in a single packet and could be sent.
+    pub fn fits_packet(&self) -> bool {
+        self.serialized_size <= PACKET_DATA_SIZE
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{:?} {} on {}: in {} (max {}), out {} (min {}), transfer fee {}, tip {}, priority fee {}, {} instructions over {} accounts, {} bytes{}",
+            self.direction,
+            self.mint,
+            self.venue,
+            self.amount_in,
+            self.max_amount_in,
+            self.amount_out,
+            self.min_amount_out,
+            self.transfer_fee,
+            self.fees.tip_lamports,
+            self.fees.priority_fee_lamports(),
+            self.transaction.message.instructions().len(),
+            self.accounts().len(),
+            self.serialized_size,
+            if self.fits_packet() { "" } else { " (too large to send)" }
+        )
+    }
+}
+
+/// A venue that swaps coins against SOL for one wallet.
+pub trait DexExecutor: Sync {
+    /// Venue accounts a quote carries into the swap built from it.
+    type Route: Send;
+
+    /// What a target's trade on the venue tells about the pool or curve it
+    /// swapped on; `()` on venues copies aren't routed to.
+    type Trade: Send;
+
+    /// Venue name for log prefixes.
+    fn venue(&self) -> &'static str;
+
+    fn rpc_pool(&self) -> &Arc<RpcPool>;
+
+    fn keypair(&self) -> &Arc<Keypair>;
+
+    /// Prices a swap of `mint` for `swap_config` without sending anything.
+    fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> impl Future<Output = Result<SwapQuote<Self::Route>>> + Send;
+
+    /// Prices the copy of a target's trade from what `trade` carries, or as
+    /// `quote` does without one.
+    fn quote_trade(
+        &self,
+        mint: Pubkey,
+        trade: Option<Self::Trade>,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> impl Future<Output = Result<SwapQuote<Self::Route>>> + Send {
+        async move {
+            drop(trade);
+            self.quote(mint, swap_config, logger).await
+        }
+    }
+
+    /// Instructions of a quoted swap, including account setup and cleanup.
+    fn build_instructions(&self, quote: &SwapQuote<Self::Route>) -> Result<Vec<Instruction>>;
+
+    /// Quotes, builds and sends a swap of `mint_str`.
+    fn execute(
+        &self,
+        mint_str: &str,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_url: String,
+        jito_tip_amount: f64,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send {
+        self.swap_by_trade(
+            mint_str,
+            None,
+            swap_config,
+            start_time,
+            jito_url,
+            jito_tip_amount,
+        )
+    }
+
+    /// Quotes, builds and sends the copy of a target's `trade` in `mint_str`.
+    fn swap_by_trade(
+        &self,
+        mint_str: &str,
+        trade: Option<Self::Trade>,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_url: String,
+        jito_tip_amount: f64,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send {
+        async move {
+            let logger = swap_logger(self.venue(), start_time);
+            let mint = Pubkey::from_str(mint_str)
+                .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+            let quote = self.quote_trade(mint, trade, &swap_config, &logger).await?;
+            self.execute_quote(
+                quote,
+                swap_config,
+                start_time,
+                jito_url,
+                jito_tip_amount,
+                &logger,
+            )
+            .await
+        }
+    }
+
+    /// Quotes and builds a swap of `mint_str` without signing or sending it.
+    fn dry_run(
+        &self,
+        mint_str: &str,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_tip_amount: f64,
+    ) -> impl Future<Output = Result<DryRun>> + Send {
+        self.dry_run_by_trade(mint_str, None, swap_config, start_time, jito_tip_amount)
+    }
+
+    /// Quotes and builds the copy of a target's `trade` without signing or
+    /// sending it.
+    fn dry_run_by_trade(
+        &self,
+        mint_str: &str,
+        trade: Option<Self::Trade>,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_tip_amount: f64,
+    ) -> impl Future<Output = Result<DryRun>> + Send {
+        async move {
+            let logger = swap_logger(self.venue(), start_time);
+            let mint = Pubkey::from_str(mint_str)
+                .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+            let quote = self.quote_trade(mint, trade, &swap_config, &logger).await?;
+            self.dry_run_quote(quote, swap_config, jito_tip_amount, &logger)
+                .await
+        }
+    }
+
+    /// Builds a quoted swap into the transaction `execute_quote` would send.
+    fn dry_run_quote(
+        &self,
+        quote: SwapQuote<Self::Route>,
+        swap_config: SwapConfig,
+        jito_tip_amount: f64,
+        logger: &Logger,
+    ) -> impl Future<Output = Result<DryRun>> + Send {
+        async move {
+            if quote.amount_in == 0 {
+                return Err(anyhow!("instructions is empty, no tx required"));
+            }
+            let instructions = self.build_instructions(&quote)?;
+            let (transaction, fees) = tx::dry_run_transaction(
+                jito_tip_amount,
+                self.rpc_pool(),
+                &self.keypair().pubkey(),
+                instructions,
+                &swap_config,
+                logger,
+            )
+            .await?;
+            DryRun::new(self.venue(), &quote, transaction, fees)
+        }
+    }
+
+    /// Builds and sends a quoted swap.
+    fn execute_quote(
+        &self,
+        quote: SwapQuote<Self::Route>,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_url: String,
+        jito_tip_amount: f64,
+        logger: &Logger,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send {
+        async move {
+            if quote.amount_in == 0 {
+                return Err(anyhow!("instructions is empty, no tx required"));
+            }
+            let instructions = self.build_instructions(&quote)?;
+            logger.log(format!("sending tx: {:?}", start_time.elapsed()));
+            tx::new_signed_and_send(
+                jito_url,
+                jito_tip_amount,
+                self.rpc_pool(),
+                self.keypair(),
+                instructions,
+                &swap_config,
+                logger,
+            )
+            .await
+        }
+    }
+}
+
+pub fn swap_logger(venue: &str, start_time: Instant) -> Logger {
+    Logger::new(format!(
+        "[SWAP IN {}]({}:{:?}) => ",
+        venue,
+        chrono::Utc::now().timestamp(),
+        start_time.elapsed()
+    ))
+}
+
+pub fn slippage_bps(swap_config: &SwapConfig) -> u64 {
+    swap_config.slippage * 100
+}
+
+pub fn min_amount_with_slippage(amount: u64, slippage_bps: u64) -> u64 {
+    (amount as u128 * BPS_DENOMINATOR.saturating_sub(slippage_bps) as u128
+        / BPS_DENOMINATOR as u128) as u64
+}
+
+pub fn max_amount_with_slippage(amount: u64, slippage_bps: u64) -> u64 {
+    (amount as u128 * (BPS_DENOMINATOR + slippage_bps) as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// What a swap spends, per `swap_config.in_type` on sells.
+#[derive(Debug, Clone)]
+pub struct SwapAmount {
+    pub amount: u64,
+    pub transfer_fee: u64,
+    pub close_instruction: Option<Instruction>,
+}
+
+/// Lamports to spend on a buy, or coin units to sell with the Token-2022
+/// transfer fee withheld on them and a close of the coin account when
+/// everything is sold. `decimals` saves the mint read for SPL Token quantity
+/// sells.
+pub async fn swap_amount(
+    rpc_pool: &Arc<RpcPool>,
+    keypair: &Arc<Keypair>,
+    mint: Pubkey,
+    token_program: Pubkey,
+    decimals: Option<u8>,
+    swap_config: &SwapConfig,
+) -> Result<SwapAmount> {
+    if swap_config.swap_direction == SwapDirection::Buy {
+        return Ok(SwapAmount {
+            amount: ui_amount_to_amount(swap_config.amount_in, spl_token::native_mint::DECIMALS),
+            transfer_fee: 0,
+            close_instruction: None,
+        });
+    }
+    if let (SwapInType::Qty, Some(decimals)) = (&swap_config.in_type, decimals) {
+        if token_program == spl_token::ID {
+            return Ok(SwapAmount {
+                amount: ui_amount_to_amount(swap_config.amount_in, decimals),
+                transfer_fee: 0,
+                close_instruction: None,
+            });
+        }
+    }
+    let owner = keypair.pubkey();
+    let ata = get_associated_token_address_with_program_id(&owner, &mint, &token_program);
+    let in_mint = token::get_mint_info(rpc_pool.clone(), keypair.clone(), mint).await?;
+    let mut close_instruction = None;
+    let amount = match swap_config.in_type {
+        SwapInType::Qty => ui_amount_to_amount(swap_config.amount_in, in_mint.base.decimals),
+        SwapInType::Pct => {
+            let in_account = token::get_account_info(rpc_pool.clone(), mint, ata).await?;
+            let amount_in_pct = swap_config.amount_in.min(1.0);
+            if amount_in_pct == 1.0 {
+                close_instruction = Some(spl_token_2022::instruction::close_account(
+                    &token_program,
+                    &ata,
+                    &owner,
+                    &owner,
+                    &[&owner],
+                )?);
+                in_account.base.amount
+            } else {
+                (((amount_in_pct * 100.0) as u64) * in_account.base.amount) / 100
+            }
+        }
+    };
+    let transfer_fee = token::get_transfer_fee(rpc_pool.clone(), &in_mint, amount).await?;
+    Ok(SwapAmount {
+        amount,
+        transfer_fee,
+        close_instruction,
+    })
+}
+
+pub fn wsol_ata(owner: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, &spl_token::native_mint::ID, &spl_token::ID)
+}
+
+pub fn coin_ata<R>(owner: &Pubkey, quote: &SwapQuote<R>) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, &quote.mint, &quote.token_program)
+}
+
+/// The wallet's (input, output) token accounts of a swap through WSOL.
+pub fn swap_token_accounts<R>(owner: &Pubkey, quote: &SwapQuote<R>) -> (Pubkey, Pubkey) {
+    match quote.direction {
+        SwapDirection::Buy => (wsol_ata(owner), coin_ata(owner, quote)),
+        SwapDirection::Sell => (coin_ata(owner, quote), wsol_ata(owner)),
+    }
+}
+
+/// Surrounds a swap paid in native SOL: creates the coin account on buys and
+/// closes it after a sell-all.
+pub fn sol_swap_instructions<R>(
+    owner: &Pubkey,
+    quote: &SwapQuote<R>,
+    swap_instruction: Instruction,
+) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    if quote.direction == SwapDirection::Buy {
+        // idempotent create avoids an RPC round-trip to check whether the ATA exists
+        instructions.push(create_associated_token_account_idempotent(
+            owner,
+            owner,
+            &quote.mint,
+            &quote.token_program,
+        ));
+    }
+    instructions.push(swap_instruction);
+    instructions.extend(quote.close_instruction.clone());
+    instructions
+}
+
+/// Surrounds a swap paid in WSOL: opens the wsol account, funding it on buys
+/// with the most the swap may spend, and unwraps whatever is left or was
+/// received afterwards.
+pub fn wsol_swap_instructions<R>(
+    owner: &Pubkey,
+    quote: &SwapQuote<R>,
+    swap_instruction: Instruction,
+) -> Result<Vec<Instruction>> {
+    let native_mint = spl_token::native_mint::ID;
+    let wsol_ata = wsol_ata(owner);
+    let mut instructions = vec![create_associated_token_account_idempotent(
+        owner,
+        owner,
+        &native_mint,
+        &spl_token::ID,
+    )];
+    if quote.direction == SwapDirection::Buy {
+        instructions.push(create_associated_token_account_idempotent(
+            owner,
+            owner,
+            &quote.mint,
+            &quote.token_program,
+        ));
+        instructions.push(system_instruction::transfer(
+            owner,
+            &wsol_ata,
+            quote.max_amount_in(),
+        ));
+        instructions.push(spl_token::instruction::sync_native(
+            &spl_token::ID,
+            &wsol_ata,
+        )?);
+    }
+    instructions.push(swap_instruction);
+    instructions.push(spl_token::instruction::close_account(
+        &spl_token::ID,
+        &wsol_ata,
+        owner,
+        owner,
+        &[owner],
+    )?);
+    instructions.extend(quote.close_instruction.clone());
+    Ok(instructions)
+}
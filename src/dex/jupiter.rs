@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bincode::Options;
+use serde::Serialize;
+use serde_json::Value;
+use solana_sdk::{
+    instruction::Instruction, message::VersionedMessage, pubkey::Pubkey, signature::Keypair,
+    signer::Signer, transaction::VersionedTransaction,
+};
+use spl_token::ui_amount_to_amount;
+use tokio::time::Instant;
+
+use crate::{
+    core::{
+        token::get_token_program,
+        tx::{self, SendFees},
+    },
+    dex::executor::{swap_amount, DexExecutor, DryRun, SwapBound, SwapQuote},
+    engine::swap::SwapDirection,
+    services::rpc_pool::RpcPool,
+    utils::{config::SwapConfig, logger::Logger},
+};
+
+pub const JUPITER_ORDER_URL: &str = "https://ultra-api.jup.ag/order";
+pub const JUPITER_SWAP_URL: &str = "https://quote-api.jup.ag/v6/swap";
+
+#[derive(Serialize)]
+struct SwapRequest {
+    quote_response: Value,
+    user_public_key: String,
+    wrap_and_unwrap_sol: bool,
+    dynamic_compute_unit_limit: bool,
+    prioritization_fee_lamports: u64,
+}
+
+/// Routes through the Jupiter aggregator, which returns a whole transaction
+/// rather than instructions; the coin account is left open after a sell-all.
+#[derive(Clone)]
+pub struct Jupiter {
+    pub rpc_pool: Arc<RpcPool>,
+    pub keypair: Arc<Keypair>,
+}
+
+/// Jupiter's order for a quoted swap, sent back to build the transaction.
+#[derive(Debug, Clone)]
+pub struct JupiterRoute {
+    pub order: Value,
+}
+
+impl Jupiter {
+    pub fn new(rpc_pool: Arc<RpcPool>, keypair: Arc<Keypair>) -> Self {
+        Self { rpc_pool, keypair }
+    }
+
+    /// The unsigned swap transaction Jupiter builds for `order`.
+    async fn swap_transaction(&self, order: Value) -> Result<VersionedTransaction> {
+        let swap_request = SwapRequest {
+            quote_response: order,
+            user_public_key: self.keypair.pubkey().to_string(),
+            wrap_and_unwrap_sol: true,
+            dynamic_compute_unit_limit: true,
+            prioritization_fee_lamports: 52000,
+        };
+        let res: Value = reqwest::Client::new()
+            .post(JUPITER_SWAP_URL)
+            .header("Content-Type", "application/json")
+            .json(&swap_request)
+            .send()
+            .await?
+            .json()
+            .await?;
+        let tx =
+            base64::decode(res["swapTransaction"].as_str().unwrap_or_default()).unwrap_or_default();
+        bincode::options()
+            .with_fixint_encoding()
+            .reject_trailing_bytes()
+            .deserialize::<VersionedTransaction>(&tx)
+            .map_err(|_| anyhow!("Not tradable token."))
+    }
+}
+
+impl DexExecutor for Jupiter {
+    type Route = JupiterRoute;
+    type Trade = ();
+
+    fn venue(&self) -> &'static str {
+        "JUPITER"
+    }
+
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
+    }
+
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
+
+    async fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<JupiterRoute>> {
+        let token_program = get_token_program(self.rpc_pool.clone(), mint).await?;
+        let swap_amount = swap_amount(
+            &self.rpc_pool,
+            &self.keypair,
+            mint,
+            token_program,
+            None,
+            swap_config,
+        )
+        .await?;
+        let native_mint = spl_token::native_mint::ID.to_string();
+        let mint_str = mint.to_string();
+        let (input_mint, output_mint) = match swap_config.swap_direction {
+            SwapDirection::Buy => (&native_mint, &mint_str),
+            SwapDirection::Sell => (&mint_str, &native_mint),
+        };
+        let url = format!(
+            "{}?inputMint={}&outputMint={}&amount={}&slippageBps=10000",
+            JUPITER_ORDER_URL, input_mint, output_mint, swap_amount.amount
+        );
+        logger.log(format!("Requesting the URL: {}", url));
+        let response = reqwest::get(&url).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Not tradable token."));
+        }
+        let order: Value = response.json().await?;
+        let amount = |field: &str| {
+            order[field]
+                .as_str()
+                .and_then(|amount| amount.parse::<u64>().ok())
+                .ok_or_else(|| anyhow!("jupiter order has no {}", field))
+        };
+        let amount_out = amount("outAmount")?;
+        let other_amount_threshold = amount("otherAmountThreshold")?;
+
+        Ok(SwapQuote {
+            mint,
+            token_program,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound: SwapBound::MinAmountOut(other_amount_threshold),
+            close_instruction: None,
+            route: JupiterRoute { order },
+        })
+    }
+
+    fn build_instructions(&self, _quote: &SwapQuote<JupiterRoute>) -> Result<Vec<Instruction>> {
+        Err(anyhow!(
+            "jupiter builds whole transactions, not instructions"
+        ))
+    }
+
+    async fn dry_run_quote(
+        &self,
+        quote: SwapQuote<JupiterRoute>,
+        _swap_config: SwapConfig,
+        jito_tip_amount: f64,
+        _logger: &Logger,
+    ) -> Result<DryRun> {
+        let transaction = self.swap_transaction(quote.route.order.clone()).await?;
+        // the priority fee is already in Jupiter's transaction; the tip is bundled separately
+        let fees = SendFees {
+            tip_lamports: ui_amount_to_amount(jito_tip_amount, spl_token::native_mint::DECIMALS),
+            compute_budget: None,
+        };
+        DryRun::new(self.venue(), &quote, transaction, fees)
+    }
+
+    async fn execute_quote(
+        &self,
+        quote: SwapQuote<JupiterRoute>,
+        _swap_config: SwapConfig,
+        _start_time: Instant,
+        jito_url: String,
+        jito_tip_amount: f64,
+        logger: &Logger,
+    ) -> Result<Vec<String>> {
+        let wallet = self.keypair.as_ref();
+        let transaction = self.swap_transaction(quote.route.order).await?;
+        let signed_tx = VersionedTransaction::try_new(transaction.message.clone(), &[wallet])?;
+        let recent_blockhash = VersionedMessage::recent_blockhash(&transaction.message);
+        tx::jito_confirm(
+            jito_url,
+            jito_tip_amount,
+            wallet,
+            signed_tx,
+            recent_blockhash,
+            logger,
+        )
+        .await
+    }
+}
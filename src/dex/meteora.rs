@@ -0,0 +1,873 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use borsh::BorshDeserialize as _;
+use borsh_derive::BorshDeserialize;
+use serde_json::Value;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::Account,
+    bs58,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, Mint},
+};
+
+use crate::{
+    core::token,
+    dex::{
+        executor::{
+            min_amount_with_slippage, slippage_bps, swap_amount, swap_token_accounts,
+            wsol_swap_instructions, DexExecutor, SwapBound, SwapQuote,
+        },
+        raydium_common::get_vault_balances,
+    },
+    engine::swap::SwapDirection,
+    services::rpc_pool::RpcPool,
+    utils::{config::SwapConfig, logger::Logger},
+};
+
+pub const DLMM_PROGRAM: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+pub const DAMM_PROGRAM: &str = "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB";
+pub const VAULT_PROGRAM: &str = "24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi";
+// both programs name their swap `swap`, so the anchor discriminator is shared
+pub const METEORA_SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+pub const DLMM_SWAP2_DISCRIMINATOR: [u8; 8] = [65, 75, 63, 76, 235, 91, 91, 136];
+pub const DLMM_LB_PAIR_LEN: u64 = 904;
+pub const MAX_BIN_PER_ARRAY: i32 = 70;
+// DLMM fee rates are expressed in billionths
+pub const DLMM_FEE_PRECISION: u64 = 1_000_000_000;
+// offsets of the mints in the pool accounts, for program account filters
+const DLMM_TOKEN_X_MINT_OFFSET: usize = 88;
+const DLMM_TOKEN_Y_MINT_OFFSET: usize = 120;
+const DAMM_TOKEN_A_MINT_OFFSET: usize = 40;
+const DAMM_TOKEN_B_MINT_OFFSET: usize = 72;
+// bin arrays passed to a swap, starting with the active one
+const SWAP_BIN_ARRAYS: i64 = 3;
+
+fn dlmm_program() -> Pubkey {
+    Pubkey::from_str(DLMM_PROGRAM).unwrap()
+}
+
+fn damm_program() -> Pubkey {
+    Pubkey::from_str(DAMM_PROGRAM).unwrap()
+}
+
+// On-chain layout of a DLMM pair, up to the reserves.
+#[derive(Debug, BorshDeserialize)]
+struct LbPairAccount {
+    _discriminator: u64,
+    base_factor: u16,
+    _static_parameters: [u8; 24],
+    base_fee_power_factor: u8,
+    _static_padding: [u8; 5],
+    _variable_parameters: [u8; 32],
+    _bump_seed: u8,
+    _bin_step_seed: [u8; 2],
+    _pair_type: u8,
+    active_id: i32,
+    bin_step: u16,
+    _status: u8,
+    _require_base_factor_seed: u8,
+    _base_factor_seed: [u8; 2],
+    _activation_type: u8,
+    _creator_pool_on_off_control: u8,
+    token_x_mint: [u8; 32],
+    token_y_mint: [u8; 32],
+    reserve_x: [u8; 32],
+    reserve_y: [u8; 32],
+    _protocol_fee: [u8; 16],
+    _padding: [u8; 32],
+    _reward_infos: [[u8; 144]; 2],
+    oracle: [u8; 32],
+}
+
+/// A Meteora DLMM pair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DlmmPool {
+    pub id: Pubkey,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub reserve_x: Pubkey,
+    pub reserve_y: Pubkey,
+    pub oracle: Pubkey,
+    pub active_id: i32,
+    pub bin_step: u16,
+    /// Base fee in `DLMM_FEE_PRECISION`; the volatility fee is not included.
+    pub base_fee_rate: u64,
+}
+
+impl DlmmPool {
+    pub fn decode(id: Pubkey, data: &[u8]) -> Result<Self> {
+        let account = LbPairAccount::deserialize(&mut &data[..])
+            .map_err(|e| anyhow!("failed to deserialize dlmm pair {}: {}", id, e))?;
+        Ok(Self {
+            id,
+            token_x_mint: Pubkey::new_from_array(account.token_x_mint),
+            token_y_mint: Pubkey::new_from_array(account.token_y_mint),
+            reserve_x: Pubkey::new_from_array(account.reserve_x),
+            reserve_y: Pubkey::new_from_array(account.reserve_y),
+            oracle: Pubkey::new_from_array(account.oracle),
+            active_id: account.active_id,
+            bin_step: account.bin_step,
+            base_fee_rate: account.base_factor as u64
+                * account.bin_step as u64
+                * 10
+                * 10u64.pow(account.base_fee_power_factor as u32),
+        })
+    }
+
+    /// Vault holding the pair's SOL side.
+    pub fn sol_vault(&self) -> Pubkey {
+        if self.token_x_mint == spl_token::native_mint::ID {
+            self.reserve_x
+        } else {
+            self.reserve_y
+        }
+    }
+
+    /// Price of the active bin in raw Y units per raw X unit.
+    pub fn price(&self) -> f64 {
+        (1.0 + self.bin_step as f64 / 10_000.0).powi(self.active_id)
+    }
+
+    /// Output for `amount_in` at the active bin price, with the base fee taken
+    /// from the input. Bin crossings are ignored, so large swaps are
+    /// overestimated and rely on the slippage bound.
+    pub fn amount_out(&self, amount_in: u64, swap_for_y: bool) -> u64 {
+        let fee = (amount_in as u128 * self.base_fee_rate as u128)
+            .div_ceil(DLMM_FEE_PRECISION as u128) as u64;
+        let amount_in = amount_in.saturating_sub(fee) as f64;
+        let amount_out = if swap_for_y {
+            amount_in * self.price()
+        } else {
+            amount_in / self.price()
+        };
+        amount_out as u64
+    }
+}
+
+// On-chain layout of a dynamic AMM pool, up to the trade fee.
+#[derive(Debug, BorshDeserialize)]
+struct DammPoolAccount {
+    _discriminator: u64,
+    _lp_mint: [u8; 32],
+    token_a_mint: [u8; 32],
+    token_b_mint: [u8; 32],
+    a_vault: [u8; 32],
+    b_vault: [u8; 32],
+    a_vault_lp: [u8; 32],
+    b_vault_lp: [u8; 32],
+    _a_vault_lp_bump: u8,
+    _enabled: bool,
+    protocol_token_a_fee: [u8; 32],
+    protocol_token_b_fee: [u8; 32],
+    _fee_last_updated_at: u64,
+    _padding: [u8; 24],
+    trade_fee_numerator: u64,
+    trade_fee_denominator: u64,
+}
+
+/// A Meteora dynamic AMM pool. Its tokens sit in shared Meteora vaults; the
+/// pool owns vault LP tokens instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DammPool {
+    pub id: Pubkey,
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    pub a_vault: Pubkey,
+    pub b_vault: Pubkey,
+    pub a_vault_lp: Pubkey,
+    pub b_vault_lp: Pubkey,
+    pub protocol_token_a_fee: Pubkey,
+    pub protocol_token_b_fee: Pubkey,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+}
+
+impl DammPool {
+    pub fn decode(id: Pubkey, data: &[u8]) -> Result<Self> {
+        let account = DammPoolAccount::deserialize(&mut &data[..])
+            .map_err(|e| anyhow!("failed to deserialize dynamic amm pool {}: {}", id, e))?;
+        Ok(Self {
+            id,
+            token_a_mint: Pubkey::new_from_array(account.token_a_mint),
+            token_b_mint: Pubkey::new_from_array(account.token_b_mint),
+            a_vault: Pubkey::new_from_array(account.a_vault),
+            b_vault: Pubkey::new_from_array(account.b_vault),
+            a_vault_lp: Pubkey::new_from_array(account.a_vault_lp),
+            b_vault_lp: Pubkey::new_from_array(account.b_vault_lp),
+            protocol_token_a_fee: Pubkey::new_from_array(account.protocol_token_a_fee),
+            protocol_token_b_fee: Pubkey::new_from_array(account.protocol_token_b_fee),
+            trade_fee_numerator: account.trade_fee_numerator,
+            trade_fee_denominator: account.trade_fee_denominator,
+        })
+    }
+
+    /// Constant-product output for `amount_in`, with the trade fee taken from
+    /// the input. Stable pools give out more than this, so the bound is safe.
+    pub fn amount_out(&self, reserves: &DammReserves, amount_in: u64, a_to_b: bool) -> u64 {
+        let (input_reserve, output_reserve) = if a_to_b {
+            (reserves.token_a, reserves.token_b)
+        } else {
+            (reserves.token_b, reserves.token_a)
+        };
+        if output_reserve == 0 || self.trade_fee_denominator == 0 {
+            return 0;
+        }
+        let fee = (amount_in as u128 * self.trade_fee_numerator as u128)
+            .div_ceil(self.trade_fee_denominator as u128);
+        let amount_in = (amount_in as u128).saturating_sub(fee);
+        (output_reserve as u128 * amount_in / (input_reserve as u128 + amount_in)) as u64
+    }
+}
+
+/// Fields of a Meteora vault used to trade through it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeteoraVault {
+    pub total_amount: u64,
+    pub token_vault: Pubkey,
+    pub lp_mint: Pubkey,
+}
+
+impl MeteoraVault {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        // discriminator, enabled and bumps precede the total amount
+        let field = |offset: usize, len: usize| {
+            data.get(offset..offset + len)
+                .ok_or_else(|| anyhow!("invalid meteora vault"))
+        };
+        Ok(Self {
+            total_amount: u64::from_le_bytes(field(11, 8)?.try_into()?),
+            token_vault: Pubkey::try_from(field(19, 32)?)?,
+            lp_mint: Pubkey::try_from(field(115, 32)?)?,
+        })
+    }
+}
+
+/// Token amounts a dynamic AMM pool owns through its vault LP shares.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DammReserves {
+    pub token_a: u64,
+    pub token_b: u64,
+    pub a_vault: MeteoraVault,
+    pub b_vault: MeteoraVault,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeteoraPoolKind {
+    Dlmm,
+    Damm,
+}
+
+/// A SOL pool of a mint on one of the Meteora programs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeteoraPool {
+    Dlmm(DlmmPool),
+    Damm(DammPool),
+}
+
+impl MeteoraPool {
+    pub fn id(&self) -> Pubkey {
+        match self {
+            MeteoraPool::Dlmm(pool) => pool.id,
+            MeteoraPool::Damm(pool) => pool.id,
+        }
+    }
+}
+
+/// Pool and coin of a target's Meteora swap.
+#[derive(Clone, Debug)]
+pub struct MeteoraTrade {
+    pub kind: MeteoraPoolKind,
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    /// WSOL held by the pool side of the swap before and after it.
+    pub sol_reserve_before: u64,
+    pub sol_reserve_after: u64,
+}
+
+impl MeteoraTrade {
+    /// Decodes a SOL-paired DLMM or dynamic AMM swap from a
+    /// `transactionSubscribe` notification. Returns `None` when the
+    /// transaction has none.
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let transaction = &json["params"]["result"]["transaction"];
+        let meta = &transaction["meta"];
+
+        // the swap may be a top-level instruction or a CPI from a router
+        let outer = transaction["transaction"]["message"]["instructions"]
+            .as_array()
+            .into_iter()
+            .flatten();
+        let inner = meta["innerInstructions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|inner| inner["instructions"].as_array())
+            .flatten();
+        let (kind, pool) = outer.chain(inner).find_map(|ix| {
+            let kind = match ix["programId"].as_str()? {
+                DLMM_PROGRAM => MeteoraPoolKind::Dlmm,
+                DAMM_PROGRAM => MeteoraPoolKind::Damm,
+                _ => return None,
+            };
+            let data = bs58::decode(ix["data"].as_str()?).into_vec().ok()?;
+            let discriminator = data.get(..8)?;
+            if discriminator != METEORA_SWAP_DISCRIMINATOR
+                && !(kind == MeteoraPoolKind::Dlmm && discriminator == DLMM_SWAP2_DISCRIMINATOR)
+            {
+                return None;
+            }
+            let pool = Pubkey::from_str(ix["accounts"].as_array()?.first()?.as_str()?).ok()?;
+            Some((kind, pool))
+        })?;
+
+        // the coin is the only other mint a single-hop SOL swap moves
+        let native_mint = spl_token::native_mint::ID.to_string();
+        let mint = meta["postTokenBalances"]
+            .as_array()?
+            .iter()
+            .filter_map(|balance| balance["mint"].as_str())
+            .find(|mint| *mint != native_mint)?;
+
+        // the pool's wsol account is the one not owned by the signer; a dynamic
+        // AMM keeps it in a shared vault, which moves just the same
+        let signer = transaction["transaction"]["message"]["accountKeys"]
+            .as_array()?
+            .iter()
+            .find(|key| key["signer"].as_bool().unwrap_or(false))?["pubkey"]
+            .as_str()?;
+        let wsol_amount = |balances: &Value, account_index: u64| {
+            balances
+                .as_array()?
+                .iter()
+                .find(|b| b["accountIndex"].as_u64() == Some(account_index))?["uiTokenAmount"]
+                ["amount"]
+                .as_str()?
+                .parse::<u64>()
+                .ok()
+        };
+        let (sol_reserve_before, sol_reserve_after) = meta["postTokenBalances"]
+            .as_array()?
+            .iter()
+            .filter(|b| b["mint"] == native_mint.as_str() && b["owner"] != signer)
+            .find_map(|b| {
+                let account_index = b["accountIndex"].as_u64()?;
+                Some((
+                    wsol_amount(&meta["preTokenBalances"], account_index)?,
+                    wsol_amount(&meta["postTokenBalances"], account_index)?,
+                ))
+            })?;
+
+        Some(Self {
+            kind,
+            pool,
+            mint: Pubkey::from_str(mint).ok()?,
+            sol_reserve_before,
+            sol_reserve_after,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Meteora {
+    pub rpc_pool: Arc<RpcPool>,
+    pub keypair: Arc<Keypair>,
+}
+
+/// Pool of a quoted Meteora swap and what its instruction needs besides.
+#[derive(Debug, Clone)]
+pub enum MeteoraRoute {
+    Dlmm {
+        pool: DlmmPool,
+        swap_for_y: bool,
+        bin_arrays: Vec<Pubkey>,
+    },
+    Damm {
+        pool: DammPool,
+        reserves: DammReserves,
+        a_to_b: bool,
+    },
+}
+
+impl Meteora {
+    pub fn new(rpc_pool: Arc<RpcPool>, keypair: Arc<Keypair>) -> Self {
+        Self { rpc_pool, keypair }
+    }
+
+    pub async fn quote_pool(
+        &self,
+        mint: Pubkey,
+        pool: MeteoraPool,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<MeteoraRoute>> {
+        let slippage_bps = slippage_bps(swap_config);
+        let program_id = token::get_token_program(self.rpc_pool.clone(), mint).await?;
+        let swap_amount = swap_amount(
+            &self.rpc_pool,
+            &self.keypair,
+            mint,
+            program_id,
+            None,
+            swap_config,
+        )
+        .await?;
+        let input_mint = match swap_config.swap_direction {
+            SwapDirection::Buy => spl_token::native_mint::ID,
+            SwapDirection::Sell => mint,
+        };
+        // the pool only receives what is left after the transfer fee
+        let pool_amount_in = swap_amount.amount - swap_amount.transfer_fee;
+
+        let (amount_out, route) = match pool {
+            MeteoraPool::Dlmm(pool) => {
+                let swap_for_y = input_mint == pool.token_x_mint;
+                let bin_arrays = get_bin_arrays(&self.rpc_pool, &pool, swap_for_y).await?;
+                let amount_out = pool.amount_out(pool_amount_in, swap_for_y);
+                logger.debug(format!(
+                    "dlmm {}: amount_in: {}, amount_out: {}, active bin: {}",
+                    pool.id, swap_amount.amount, amount_out, pool.active_id
+                ));
+                (
+                    amount_out,
+                    MeteoraRoute::Dlmm {
+                        pool,
+                        swap_for_y,
+                        bin_arrays,
+                    },
+                )
+            }
+            MeteoraPool::Damm(pool) => {
+                let a_to_b = input_mint == pool.token_a_mint;
+                let reserves = get_damm_reserves(&self.rpc_pool, &pool).await?;
+                let amount_out = pool.amount_out(&reserves, pool_amount_in, a_to_b);
+                logger.debug(format!(
+                    "dynamic amm {}: amount_in: {}, amount_out: {}",
+                    pool.id, swap_amount.amount, amount_out
+                ));
+                (
+                    amount_out,
+                    MeteoraRoute::Damm {
+                        pool,
+                        reserves,
+                        a_to_b,
+                    },
+                )
+            }
+        };
+
+        Ok(SwapQuote {
+            mint,
+            token_program: program_id,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound: SwapBound::MinAmountOut(min_amount_with_slippage(amount_out, slippage_bps)),
+            close_instruction: swap_amount.close_instruction,
+            route,
+        })
+    }
+}
+
+impl DexExecutor for Meteora {
+    type Route = MeteoraRoute;
+    type Trade = MeteoraTrade;
+
+    fn venue(&self) -> &'static str {
+        "METEORA"
+    }
+
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
+    }
+
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
+
+    async fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<MeteoraRoute>> {
+        let pool = find_deepest_pool(self.rpc_pool.clone(), &mint, logger).await?;
+        self.quote_pool(mint, pool, swap_config, logger).await
+    }
+
+    /// Quotes on the pool `trade` swapped on, or the deepest pool of `mint`.
+    async fn quote_trade(
+        &self,
+        mint: Pubkey,
+        trade: Option<MeteoraTrade>,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<MeteoraRoute>> {
+        let pool = match trade {
+            Some(trade) => get_pool(&self.rpc_pool, trade.kind, &trade.pool).await?,
+            None => find_deepest_pool(self.rpc_pool.clone(), &mint, logger).await?,
+        };
+        self.quote_pool(mint, pool, swap_config, logger).await
+    }
+
+    fn build_instructions(&self, quote: &SwapQuote<MeteoraRoute>) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let SwapBound::MinAmountOut(min_amount_out) = quote.bound else {
+            return Err(anyhow!("meteora swaps are exact input"));
+        };
+        let token_accounts = swap_token_accounts(&owner, quote);
+        let swap_instruction = match &quote.route {
+            MeteoraRoute::Dlmm {
+                pool, bin_arrays, ..
+            } => {
+                let token_program = |token_mint: &Pubkey| {
+                    if *token_mint == quote.mint {
+                        quote.token_program
+                    } else {
+                        spl_token::ID
+                    }
+                };
+                dlmm_swap(
+                    pool,
+                    &owner,
+                    token_accounts,
+                    (
+                        token_program(&pool.token_x_mint),
+                        token_program(&pool.token_y_mint),
+                    ),
+                    bin_arrays,
+                    quote.amount_in,
+                    min_amount_out,
+                )
+            }
+            MeteoraRoute::Damm {
+                pool,
+                reserves,
+                a_to_b,
+            } => damm_swap(
+                pool,
+                reserves,
+                &owner,
+                token_accounts,
+                *a_to_b,
+                quote.amount_in,
+                min_amount_out,
+            )?,
+        };
+        wsol_swap_instructions(&owner, quote, swap_instruction)
+    }
+}
+
+pub fn bin_array_pda(lb_pair: &Pubkey, index: i64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"bin_array", lb_pair.as_ref(), &index.to_le_bytes()],
+        &dlmm_program(),
+    )
+    .0
+}
+
+pub fn dlmm_event_authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &dlmm_program()).0
+}
+
+/// Initialized bin arrays the swap walks through, starting with the active
+/// one and moving in the price direction of the swap.
+pub async fn get_bin_arrays(
+    rpc_pool: &RpcPool,
+    pool: &DlmmPool,
+    swap_for_y: bool,
+) -> Result<Vec<Pubkey>> {
+    let active = pool.active_id.div_euclid(MAX_BIN_PER_ARRAY) as i64;
+    // selling X moves the price, and the active bin, down
+    let step = if swap_for_y { -1 } else { 1 };
+    let candidates: Vec<Pubkey> = (0..SWAP_BIN_ARRAYS)
+        .map(|i| bin_array_pda(&pool.id, active + i * step))
+        .collect();
+    let candidates = &candidates;
+    let accounts = rpc_pool
+        .read(|client| async move { client.get_multiple_accounts(candidates).await })
+        .await?;
+    let bin_arrays: Vec<Pubkey> = candidates
+        .iter()
+        .zip(accounts)
+        .filter(|(_, account)| account.is_some())
+        .map(|(bin_array, _)| *bin_array)
+        .collect();
+    if bin_arrays.is_empty() {
+        return Err(anyhow!(
+            "dlmm pair {} has no initialized bin arrays",
+            pool.id
+        ));
+    }
+    Ok(bin_arrays)
+}
+
+/// Swaps exactly `amount_in` for at least `min_amount_out` on a DLMM pair;
+/// `token_accounts` are (input, output), `token_programs` are (X, Y).
+pub fn dlmm_swap(
+    pool: &DlmmPool,
+    user: &Pubkey,
+    token_accounts: (Pubkey, Pubkey),
+    token_programs: (Pubkey, Pubkey),
+    bin_arrays: &[Pubkey],
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Instruction {
+    let program = dlmm_program();
+    let mut accounts = vec![
+        AccountMeta::new(pool.id, false),
+        // no bitmap extension; anchor reads the program id as `None`
+        AccountMeta::new_readonly(program, false),
+        AccountMeta::new(pool.reserve_x, false),
+        AccountMeta::new(pool.reserve_y, false),
+        AccountMeta::new(token_accounts.0, false),
+        AccountMeta::new(token_accounts.1, false),
+        AccountMeta::new_readonly(pool.token_x_mint, false),
+        AccountMeta::new_readonly(pool.token_y_mint, false),
+        AccountMeta::new(pool.oracle, false),
+        // no host fee
+        AccountMeta::new_readonly(program, false),
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new_readonly(token_programs.0, false),
+        AccountMeta::new_readonly(token_programs.1, false),
+        AccountMeta::new_readonly(dlmm_event_authority_pda(), false),
+        AccountMeta::new_readonly(program, false),
+    ];
+    accounts.extend(
+        bin_arrays
+            .iter()
+            .map(|bin_array| AccountMeta::new(*bin_array, false)),
+    );
+
+    let mut data = METEORA_SWAP_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    Instruction::new_with_bytes(program, &data, accounts)
+}
+
+/// Swaps exactly `amount_in` for at least `min_amount_out` on a dynamic AMM
+/// pool; `token_accounts` are (input, output).
+pub fn damm_swap(
+    pool: &DammPool,
+    reserves: &DammReserves,
+    user: &Pubkey,
+    token_accounts: (Pubkey, Pubkey),
+    a_to_b: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Result<Instruction> {
+    let protocol_token_fee = if a_to_b {
+        pool.protocol_token_a_fee
+    } else {
+        pool.protocol_token_b_fee
+    };
+    let accounts = vec![
+        AccountMeta::new(pool.id, false),
+        AccountMeta::new(token_accounts.0, false),
+        AccountMeta::new(token_accounts.1, false),
+        AccountMeta::new(pool.a_vault, false),
+        AccountMeta::new(pool.b_vault, false),
+        AccountMeta::new(reserves.a_vault.token_vault, false),
+        AccountMeta::new(reserves.b_vault.token_vault, false),
+        AccountMeta::new(reserves.a_vault.lp_mint, false),
+        AccountMeta::new(reserves.b_vault.lp_mint, false),
+        AccountMeta::new(pool.a_vault_lp, false),
+        AccountMeta::new(pool.b_vault_lp, false),
+        AccountMeta::new(protocol_token_fee, false),
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new_readonly(Pubkey::from_str(VAULT_PROGRAM)?, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+    ];
+
+    let mut data = METEORA_SWAP_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    Ok(Instruction::new_with_bytes(damm_program(), &data, accounts))
+}
+
+async fn get_accounts(rpc_pool: &RpcPool, pubkeys: &[Pubkey]) -> Result<Vec<Account>> {
+    let accounts = rpc_pool
+        .read(|client| async move { client.get_multiple_accounts(pubkeys).await })
+        .await?;
+    pubkeys
+        .iter()
+        .zip(accounts)
+        .map(|(pubkey, account)| account.ok_or_else(|| anyhow!("account {} not found", pubkey)))
+        .collect()
+}
+
+/// What the pool's vault LP shares are worth in each token.
+pub async fn get_damm_reserves(rpc_pool: &RpcPool, pool: &DammPool) -> Result<DammReserves> {
+    let accounts = get_accounts(
+        rpc_pool,
+        &[pool.a_vault, pool.b_vault, pool.a_vault_lp, pool.b_vault_lp],
+    )
+    .await?;
+    let a_vault = MeteoraVault::decode(&accounts[0].data)?;
+    let b_vault = MeteoraVault::decode(&accounts[1].data)?;
+    let pool_lp = |account: &Account| -> Result<u64> {
+        Ok(StateWithExtensions::<TokenAccount>::unpack(&account.data)?
+            .base
+            .amount)
+    };
+    let (a_pool_lp, b_pool_lp) = (pool_lp(&accounts[2])?, pool_lp(&accounts[3])?);
+
+    let lp_mints = get_accounts(rpc_pool, &[a_vault.lp_mint, b_vault.lp_mint]).await?;
+    let supply = |account: &Account| -> Result<u64> {
+        Ok(StateWithExtensions::<Mint>::unpack(&account.data)?
+            .base
+            .supply)
+    };
+    let share = |total: u64, pool_lp: u64, supply: u64| {
+        if supply == 0 {
+            0
+        } else {
+            (total as u128 * pool_lp as u128 / supply as u128) as u64
+        }
+    };
+    Ok(DammReserves {
+        token_a: share(a_vault.total_amount, a_pool_lp, supply(&lp_mints[0])?),
+        token_b: share(b_vault.total_amount, b_pool_lp, supply(&lp_mints[1])?),
+        a_vault,
+        b_vault,
+    })
+}
+
+/// Reads and decodes the pool at `address`.
+pub async fn get_pool(
+    rpc_pool: &RpcPool,
+    kind: MeteoraPoolKind,
+    address: &Pubkey,
+) -> Result<MeteoraPool> {
+    let data = rpc_pool
+        .read(|client| async move { client.get_account_data(address).await })
+        .await?;
+    match kind {
+        MeteoraPoolKind::Dlmm => Ok(MeteoraPool::Dlmm(DlmmPool::decode(*address, &data)?)),
+        MeteoraPoolKind::Damm => Ok(MeteoraPool::Damm(DammPool::decode(*address, &data)?)),
+    }
+}
+
+async fn get_program_pools(
+    rpc_pool: &RpcPool,
+    program: Pubkey,
+    data_size: Option<u64>,
+    mint_offsets: (usize, usize),
+    mint: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>> {
+    let native_mint = spl_token::native_mint::ID;
+    let mut pools = vec![];
+    for (mint_0, mint_1) in [(native_mint, *mint), (*mint, native_mint)] {
+        let mut filters = vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                mint_offsets.0,
+                &mint_0.to_bytes(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                mint_offsets.1,
+                &mint_1.to_bytes(),
+            )),
+        ];
+        filters.extend(data_size.map(RpcFilterType::DataSize));
+        let accounts = rpc_pool
+            .read(|client| {
+                let config = RpcProgramAccountsConfig {
+                    filters: Some(filters.clone()),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                };
+                async move {
+                    client
+                        .get_program_accounts_with_config(&program, config)
+                        .await
+                }
+            })
+            .await?;
+        pools.extend(accounts);
+    }
+    Ok(pools)
+}
+
+/// SOL pools of `mint` on DLMM and dynamic AMM. A program whose lookup fails
+/// contributes no pools.
+pub async fn find_pools(rpc_pool: &RpcPool, mint: &Pubkey) -> Vec<MeteoraPool> {
+    let (dlmm, damm) = tokio::join!(
+        get_program_pools(
+            rpc_pool,
+            dlmm_program(),
+            Some(DLMM_LB_PAIR_LEN),
+            (DLMM_TOKEN_X_MINT_OFFSET, DLMM_TOKEN_Y_MINT_OFFSET),
+            mint,
+        ),
+        get_program_pools(
+            rpc_pool,
+            damm_program(),
+            None,
+            (DAMM_TOKEN_A_MINT_OFFSET, DAMM_TOKEN_B_MINT_OFFSET),
+            mint,
+        ),
+    );
+    let dlmm = dlmm
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(id, account)| {
+            DlmmPool::decode(id, &account.data)
+                .ok()
+                .map(MeteoraPool::Dlmm)
+        });
+    let damm = damm
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(id, account)| {
+            DammPool::decode(id, &account.data)
+                .ok()
+                .map(MeteoraPool::Damm)
+        });
+    dlmm.chain(damm).collect()
+}
+
+/// The pool of `mint` holding the most SOL, so a copy moves the price least.
+pub async fn find_deepest_pool(
+    rpc_pool: Arc<RpcPool>,
+    mint: &Pubkey,
+    logger: &Logger,
+) -> Result<MeteoraPool> {
+    let mut deepest = None;
+    for pool in find_pools(&rpc_pool, mint).await {
+        let sol_balance = match &pool {
+            MeteoraPool::Dlmm(pair) => get_vault_balances(&rpc_pool, &[pair.sol_vault()]).await?[0],
+            MeteoraPool::Damm(damm) => {
+                let reserves = get_damm_reserves(&rpc_pool, damm).await?;
+                if damm.token_a_mint == spl_token::native_mint::ID {
+                    reserves.token_a
+                } else {
+                    reserves.token_b
+                }
+            }
+        };
+        if deepest
+            .as_ref()
+            .map_or(true, |(_, deepest_balance)| sol_balance > *deepest_balance)
+        {
+            deepest = Some((pool, sol_balance));
+        }
+    }
+    let (pool, balance) = deepest.ok_or(anyhow!("NotFoundPool: meteora pool not found"))?;
+    logger.debug(format!("deepest pool {}: {} lamports", pool.id(), balance));
+    Ok(pool)
+}
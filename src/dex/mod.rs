@@ -1,2 +1,11 @@
+pub mod bonding_curve;
+pub mod executor;
+pub mod jupiter;
+pub mod meteora;
 pub mod pump_fun;
+pub mod pump_instructions;
+pub mod pump_swap;
 pub mod raydium;
+pub mod raydium_clmm;
+pub mod raydium_common;
+pub mod raydium_cpmm;
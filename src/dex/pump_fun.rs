@@ -1,27 +1,34 @@
-use std::{str::FromStr, sync::Arc, time::Duration};
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
-use anyhow::{anyhow, Context, Result};
-use borsh::from_slice;
+use anyhow::{anyhow, Result};
+use borsh::BorshDeserialize as _;
 use borsh_derive::{BorshDeserialize, BorshSerialize};
-use raydium_amm::math::U128;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
-    pubkey::Pubkey,
-    signature::Keypair,
-    signer::Signer,
-    system_program,
-};
-use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+    bs58, instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer,
 };
-use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
-use spl_token_client::token::TokenError;
-use tokio::time::Instant;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token::ui_amount_to_amount;
+use tokio::{sync::OnceCell, time::Instant};
 
 use crate::{
-    core::{token, tx},
+    core::{cache, token},
+    dex::{
+        bonding_curve::BondingCurve,
+        executor::{
+            max_amount_with_slippage, min_amount_with_slippage, slippage_bps,
+            sol_swap_instructions, swap_amount, DexExecutor, SwapAmount, SwapBound, SwapQuote,
+        },
+        pump_instructions::{self, PumpLayout, PumpSwapAccounts},
+    },
     engine::swap::{SwapDirection, SwapInType},
+    services::rpc_pool::RpcPool,
     utils::{config::SwapConfig, logger::Logger},
 };
 pub const TEN_THOUSAND: u64 = 10000;
@@ -29,308 +36,221 @@ pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
 pub const RENT_PROGRAM: &str = "SysvarRent111111111111111111111111111111111";
 pub const ASSOCIATED_TOKEN_PROGRAM: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
 pub const PUMP_GLOBAL: &str = "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf";
-pub const PUMP_FEE_RECIPIENT: &str = "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM";
 pub const PUMP_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
 // pub const PUMP_FUN_MINT_AUTHORITY: &str = "TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM";
 pub const PUMP_ACCOUNT: &str = "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1";
 pub const PUMP_BUY_METHOD: u64 = 16927863322537952870;
 pub const PUMP_SELL_METHOD: u64 = 12502976635542562355;
+pub const PUMP_TRADE_EVENT_DISCRIMINATOR: [u8; 8] = [189, 219, 127, 211, 78, 230, 97, 238];
+
+// how often the Global account is re-read to pick up fee recipient changes
+pub const GLOBAL_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+static GLOBAL_REFRESHER: OnceCell<()> = OnceCell::const_new();
+
+lazy_static! {
+    static ref PUMP_GLOBAL_STATE: RwLock<Option<PumpGlobal>> = RwLock::new(None);
+}
 
 #[derive(Clone)]
 pub struct Pump {
-    pub rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    pub rpc_pool: Arc<RpcPool>,
     pub keypair: Arc<Keypair>,
-    pub rpc_client: Option<Arc<solana_client::rpc_client::RpcClient>>,
+}
+
+/// Accounts of a quoted pump.fun swap.
+#[derive(Debug, Clone)]
+pub struct PumpRoute {
+    pub layout: PumpLayout,
+    pub accounts: PumpSwapAccounts,
 }
 
 impl Pump {
-    pub fn new(
-        rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
-        rpc_client: Arc<solana_client::rpc_client::RpcClient>,
-        keypair: Arc<Keypair>,
-    ) -> Self {
-        Self {
-            rpc_nonblocking_client,
-            keypair,
-            rpc_client: Some(rpc_client),
-        }
+    pub fn new(rpc_pool: Arc<RpcPool>, keypair: Arc<Keypair>) -> Self {
+        Self { rpc_pool, keypair }
+    }
+}
+
+impl DexExecutor for Pump {
+    type Route = PumpRoute;
+    type Trade = PumpTrade;
+
+    fn venue(&self) -> &'static str {
+        "PUMPFUN"
+    }
+
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
     }
 
-    pub async fn swap_by_mint(
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
+
+    async fn quote(
         &self,
-        mint_str: &str,
-        swap_config: SwapConfig,
-        start_time: Instant,
-        jito_url: String,
-        jito_tip_amount: f64,
-    ) -> Result<Vec<String>> {
-        let logger = Logger::new(format!(
-            "[SWAP IN PUMPFUN BY MINT]({}:{:?}) => ",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed()
-        ));
-        let slippage_bps = swap_config.slippage * 100;
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<PumpRoute>> {
+        self.quote_trade(mint, None, swap_config, logger).await
+    }
+
+    /// Quotes on the curve state `trade` left behind, so the curve and mint
+    /// are not read from RPC; reads them when `trade` is `None`.
+    async fn quote_trade(
+        &self,
+        mint: Pubkey,
+        trade: Option<PumpTrade>,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<PumpRoute>> {
+        let slippage_bps = slippage_bps(swap_config);
         let owner = self.keypair.pubkey();
-        let mint = Pubkey::from_str(mint_str)
-            .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
-        let program_id = spl_token::ID;
-        let native_mint = spl_token::native_mint::ID;
-
-        let (token_in, token_out, pump_method) = match swap_config.swap_direction {
-            SwapDirection::Buy => (native_mint, mint, PUMP_BUY_METHOD),
-            SwapDirection::Sell => (mint, native_mint, PUMP_SELL_METHOD),
+        // the mint may be owned by Token-2022; the trade already names its program
+        let program_id = match &trade {
+            Some(trade) => trade.token_program,
+            None => token::get_token_program(self.rpc_pool.clone(), mint).await?,
         };
 
-        println!("Token_in account: {}, {}", token_in, pump_method);
-
         let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
-        let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
-            get_bonding_curve_account(self.rpc_client.clone().unwrap(), mint, pump_program).await?;
-
-        let in_ata = token::get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_in,
-            &owner,
-        );
-        let out_ata = token::get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_out,
-            &owner,
-        );
-
-        let mut create_instruction = None;
-        let mut close_instruction = None;
-
-        let (amount_specified, _amount_ui_pretty) = match swap_config.swap_direction {
-            SwapDirection::Buy => {
-                // Create base ATA if it doesn't exist.
-                match token::get_account_info(
-                    self.rpc_nonblocking_client.clone(),
-                    token_out,
-                    out_ata,
-                )
-                .await
-                {
-                    Ok(_) => {
-                        // logger.log("Base ata exists. skipping creation..".to_string());
-                    }
-                    Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                        // logger.log(format!(
-                        //     "Base ATA for mint {} does not exist. will be create",
-                        //     token_out
-                        // ));
-                        create_instruction = Some(create_associated_token_account(
-                            &owner,
-                            &owner,
-                            &token_out,
-                            &program_id,
-                        ));
-                    }
-                    Err(_) => {
-                        // logger.log(format!("Error retrieving out ATA: {}", error));
-                    }
-                }
-
+        let global = get_global(self.rpc_pool.clone()).await?;
+        let fee_basis_points = global.fee_basis_points;
+        let layout = global.layout;
+        let (bonding_curve, associated_bonding_curve, mut curve, mut creator) = match &trade {
+            Some(trade) => (
+                trade.bonding_curve,
+                trade.associated_bonding_curve,
+                BondingCurve {
+                    virtual_sol_reserves: trade.virtual_sol_reserves,
+                    virtual_token_reserves: trade.virtual_token_reserves,
+                    real_token_reserves: trade.real_token_reserves,
+                    fee_basis_points,
+                    creator_fee_basis_points: 0,
+                },
+                trade.creator,
+            ),
+            None => {
+                let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
+                    get_bonding_curve_account(
+                        self.rpc_pool.clone(),
+                        mint,
+                        pump_program,
+                        &program_id,
+                    )
+                    .await?;
                 (
-                    ui_amount_to_amount(swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                    (swap_config.amount_in, spl_token::native_mint::DECIMALS),
+                    bonding_curve,
+                    associated_bonding_curve,
+                    BondingCurve::from_account(&bonding_curve_account, fee_basis_points, 0),
+                    bonding_curve_account.creator,
                 )
             }
-            SwapDirection::Sell => {
-                let in_account_handle = tokio::spawn(token::get_account_info(
-                    self.rpc_nonblocking_client.clone(),
-                    token_in,
-                    in_ata,
-                ));
-                let in_mint_handle = tokio::spawn(token::get_mint_info(
-                    self.rpc_nonblocking_client.clone(),
-                    self.keypair.clone(),
-                    token_in,
-                ));
-                let (in_account, in_mint) =
-                    match tokio::try_join!(in_account_handle, in_mint_handle) {
-                        Ok((in_account_result, in_mint_result)) => {
-                            let in_account_result = in_account_result?;
-                            let in_mint_result = in_mint_result?;
-                            (in_account_result, in_mint_result)
-                        }
-                        Err(err) => {
-                            println!("Failed with {:?}, ", err);
-                            return Err(anyhow!(format!("{}", err)));
-                        }
-                    };
-
-                println!("in_account: {:?}, in_mint: {:?}", in_account, in_mint);
+        };
+        if creator.is_none() && layout.needs_creator_vault() {
+            // events from before the creator fee upgrade don't name the creator
+            let (_, _, bonding_curve_account) =
+                get_bonding_curve_account(self.rpc_pool.clone(), mint, pump_program, &program_id)
+                    .await?;
+            creator = bonding_curve_account.creator;
+        }
+        // only curves with a creator pay the creator fee
+        if creator.is_some() {
+            curve.creator_fee_basis_points = global.creator_fee_basis_points;
+        }
 
-                let amount = match swap_config.in_type {
-                    SwapInType::Qty => {
-                        ui_amount_to_amount(swap_config.amount_in, in_mint.base.decimals)
-                    }
-                    SwapInType::Pct => {
-                        let amount_in_pct = swap_config.amount_in.min(1.0);
-                        if amount_in_pct == 1.0 {
-                            // logger
-                            //     .log(format!("Sell all. will be close ATA for mint {}", token_in));
-                            close_instruction = Some(spl_token::instruction::close_account(
-                                &program_id,
-                                &in_ata,
-                                &owner,
-                                &owner,
-                                &[&owner],
-                            )?);
-                            in_account.base.amount
-                        } else {
-                            (amount_in_pct * 100.0) as u64 * in_account.base.amount / 100
-                        }
-                    }
-                };
-                (
-                    amount,
-                    (
-                        amount_to_ui_amount(amount, in_mint.base.decimals),
-                        in_mint.base.decimals,
-                    ),
+        let swap_amount = match (&swap_config.swap_direction, &swap_config.in_type, &trade) {
+            // the mint decimals come with the trade, no account reads needed;
+            // pump.fun mints carry no transfer fee
+            (SwapDirection::Sell, SwapInType::Qty, Some(trade)) => SwapAmount {
+                amount: ui_amount_to_amount(swap_config.amount_in, trade.token_decimals),
+                transfer_fee: 0,
+                close_instruction: None,
+            },
+            _ => {
+                swap_amount(
+                    &self.rpc_pool,
+                    &self.keypair,
+                    mint,
+                    program_id,
+                    None,
+                    swap_config,
                 )
+                .await?
             }
         };
 
-        // logger.log(format!(
-        //     "swap: {}, value: {:?} -> {}",
-        //     token_in, amount_ui_pretty, token_out
-        // ));
-
-        let client = self
-            .rpc_client
-            .clone()
-            .context("failed to get rpc client")?;
-
-        // Calculate tokens out
-        let virtual_sol_reserves = U128::from(bonding_curve_account.virtual_sol_reserves);
-        let virtual_token_reserves = U128::from(bonding_curve_account.virtual_token_reserves);
-        let unit_price = (bonding_curve_account.virtual_sol_reserves as f64
-            / bonding_curve_account.virtual_token_reserves as f64)
-            / 1000.0;
-
-        let (token_amount, sol_amount_threshold, input_accouts) = match swap_config.swap_direction {
+        let (amount_out, bound) = match swap_config.swap_direction {
             SwapDirection::Buy => {
-                let max_sol_cost = max_amount_with_slippage(amount_specified, slippage_bps);
-
-                (
-                    U128::from(amount_specified)
-                        .checked_mul(virtual_token_reserves)
-                        .unwrap()
-                        .checked_div(virtual_sol_reserves)
-                        .unwrap()
-                        .as_u64(),
-                    max_sol_cost,
-                    vec![
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
-                        AccountMeta::new(Pubkey::from_str(PUMP_FEE_RECIPIENT)?, false),
-                        AccountMeta::new_readonly(mint, false),
-                        AccountMeta::new(bonding_curve, false),
-                        AccountMeta::new(associated_bonding_curve, false),
-                        AccountMeta::new(out_ata, false),
-                        AccountMeta::new(owner, true),
-                        AccountMeta::new_readonly(system_program::id(), false),
-                        AccountMeta::new_readonly(program_id, false),
-                        AccountMeta::new_readonly(Pubkey::from_str(RENT_PROGRAM)?, false),
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
-                        AccountMeta::new_readonly(pump_program, false),
-                    ],
-                )
+                let token_amount = curve.buy_quote(swap_amount.amount);
+                if token_amount == 0 {
+                    return Err(anyhow!("bonding curve has no tokens left to buy"));
+                }
+                let max_sol_cost = max_amount_with_slippage(swap_amount.amount, slippage_bps);
+                (token_amount, SwapBound::MaxAmountIn(max_sol_cost))
             }
             SwapDirection::Sell => {
-                let sol_output = U128::from(amount_specified)
-                    .checked_mul(virtual_sol_reserves)
-                    .unwrap()
-                    .checked_div(virtual_token_reserves)
-                    .unwrap()
-                    .as_u64();
+                // the curve only receives what is left after the transfer fee
+                let sol_output = curve.sell_quote(swap_amount.amount - swap_amount.transfer_fee);
                 let min_sol_output = min_amount_with_slippage(sol_output, slippage_bps);
-
-                (
-                    amount_specified,
-                    min_sol_output,
-                    vec![
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
-                        AccountMeta::new(Pubkey::from_str(PUMP_FEE_RECIPIENT)?, false),
-                        AccountMeta::new_readonly(mint, false),
-                        AccountMeta::new(bonding_curve, false),
-                        AccountMeta::new(associated_bonding_curve, false),
-                        AccountMeta::new(in_ata, false),
-                        AccountMeta::new(owner, true),
-                        AccountMeta::new_readonly(system_program::id(), false),
-                        AccountMeta::new_readonly(
-                            Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM)?,
-                            false,
-                        ),
-                        AccountMeta::new_readonly(program_id, false),
-                        AccountMeta::new_readonly(Pubkey::from_str(PUMP_ACCOUNT)?, false),
-                        AccountMeta::new_readonly(pump_program, false),
-                    ],
-                )
+                (sol_output, SwapBound::MinAmountOut(min_sol_output))
             }
         };
+        logger.debug(format!(
+            "amount_in: {}, amount_out: {}, bound: {:?}, price: {} lamports",
+            swap_amount.amount,
+            amount_out,
+            bound,
+            curve.price()
+        ));
 
-        // logger.log(format!(
-        //     "token_amount: {}, sol_amount_threshold: {}, unit_price: {} sol",
-        //     token_amount, sol_amount_threshold, unit_price
-        // ));
-
-        let build_swap_instruction = Instruction::new_with_bincode(
-            pump_program,
-            &(pump_method, token_amount, sol_amount_threshold),
-            input_accouts,
-        );
-        // if swap_config.swap_direction == SwapDirection::Buy
-        //     && start_time.elapsed() > Duration::from_millis(700)
-        // {
-        //     return Err(anyhow!("Long RPC Connection with Pool State."));
-        // }
-        // build instructions
-        let mut instructions = vec![];
-        if let Some(create_instruction) = create_instruction {
-            instructions.push(create_instruction);
-        }
-        if amount_specified > 0 {
-            instructions.push(build_swap_instruction)
-        }
-        if let Some(close_instruction) = close_instruction {
-            instructions.push(close_instruction);
-        }
-        if instructions.is_empty() {
-            return Err(anyhow!("instructions is empty, no tx required"));
-        }
-        logger.log(format!("sending tx: {:?}", start_time.elapsed()));
-        tx::new_signed_and_send(
-            jito_url,
-            jito_tip_amount,
-            &client,
-            &self.keypair,
-            instructions,
-            swap_config.use_jito,
-            &logger,
-        )
-        .await
+        Ok(SwapQuote {
+            mint,
+            token_program: program_id,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound,
+            close_instruction: swap_amount.close_instruction,
+            route: PumpRoute {
+                layout,
+                accounts: PumpSwapAccounts {
+                    fee_recipient: global.fee_recipient,
+                    mint,
+                    bonding_curve,
+                    associated_bonding_curve,
+                    user_ata: get_associated_token_address_with_program_id(
+                        &owner,
+                        &mint,
+                        &program_id,
+                    ),
+                    user: owner,
+                    token_program: program_id,
+                    creator,
+                },
+            },
+        })
     }
-}
 
-fn min_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    input_amount
-        .checked_mul(TEN_THOUSAND.checked_sub(slippage_bps).unwrap())
-        .unwrap()
-        .checked_div(TEN_THOUSAND)
-        .unwrap()
-}
-fn max_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    input_amount
-        .checked_mul(slippage_bps.checked_add(TEN_THOUSAND).unwrap())
-        .unwrap()
-        .checked_div(TEN_THOUSAND)
-        .unwrap()
+    fn build_instructions(&self, quote: &SwapQuote<PumpRoute>) -> Result<Vec<Instruction>> {
+        let PumpRoute { layout, accounts } = &quote.route;
+        let swap_instruction = match quote.bound {
+            SwapBound::MaxAmountIn(max_sol_cost) => {
+                pump_instructions::buy(*layout, accounts, quote.amount_out, max_sol_cost)?
+            }
+            SwapBound::MinAmountOut(min_sol_output) => {
+                pump_instructions::sell(*layout, accounts, quote.amount_in, min_sol_output)?
+            }
+        };
+        Ok(sol_swap_instructions(
+            &accounts.user,
+            quote,
+            swap_instruction,
+        ))
+    }
 }
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RaydiumInfo {
     pub base: f64,
@@ -359,24 +279,315 @@ pub struct BondingCurveAccount {
     pub real_sol_reserves: u64,
     pub token_total_supply: u64,
     pub complete: bool,
+    /// Coin creator, appended by the creator fee upgrade; `None` on curves
+    /// created before it.
+    #[borsh(skip)]
+    pub creator: Option<Pubkey>,
+}
+
+impl BondingCurveAccount {
+    /// Decodes the curve, ignoring fields newer program versions append
+    /// beyond the creator.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut data = data;
+        let mut account = BondingCurveAccount::deserialize(&mut data)
+            .map_err(|e| anyhow!("Failed to deserialize bonding curve account: {}", e))?;
+        account.creator = <[u8; 32]>::deserialize(&mut data)
+            .ok()
+            .map(Pubkey::new_from_array)
+            .filter(|creator| *creator != Pubkey::default());
+        Ok(account)
+    }
+}
+
+// Emitted by the program on every buy and sell; newer program versions append
+// fields after these, which are ignored.
+#[derive(Debug, BorshDeserialize)]
+pub struct TradeEvent {
+    pub mint: [u8; 32],
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub is_buy: bool,
+    pub user: [u8; 32],
+    pub timestamp: i64,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub real_token_reserves: u64,
+}
+
+// Fields the creator fee upgrade appended to `TradeEvent`
+#[derive(Debug, BorshDeserialize)]
+pub struct TradeEventFees {
+    pub fee_recipient: [u8; 32],
+    pub fee_basis_points: u64,
+    pub fee: u64,
+    pub creator: [u8; 32],
+}
+
+/// Curve accounts and post-trade reserves taken from a target's transaction.
+#[derive(Clone, Debug)]
+pub struct PumpTrade {
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub real_token_reserves: u64,
+    pub token_decimals: u8,
+    pub token_program: Pubkey,
+    pub creator: Option<Pubkey>,
+}
+
+impl PumpTrade {
+    /// Decodes the pump.fun trade of `mint` from a `transactionSubscribe`
+    /// notification. Returns `None` when any piece is missing.
+    pub fn from_json(json: &Value, mint: &Pubkey) -> Option<Self> {
+        let transaction = &json["params"]["result"]["transaction"];
+        let meta = &transaction["meta"];
+
+        // the swap may be a top-level instruction or a CPI from a router
+        let outer = transaction["transaction"]["message"]["instructions"]
+            .as_array()
+            .into_iter()
+            .flatten();
+        let inner = meta["innerInstructions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|inner| inner["instructions"].as_array())
+            .flatten();
+        let accounts = outer.chain(inner).find_map(|ix| {
+            if ix["programId"].as_str() != Some(PUMP_PROGRAM) {
+                return None;
+            }
+            let data = bs58::decode(ix["data"].as_str()?).into_vec().ok()?;
+            let method = u64::from_le_bytes(data.get(..8)?.try_into().ok()?);
+            if method != PUMP_BUY_METHOD && method != PUMP_SELL_METHOD {
+                return None;
+            }
+            let accounts = ix["accounts"]
+                .as_array()?
+                .iter()
+                .map(|account| Pubkey::from_str(account.as_str()?).ok())
+                .collect::<Option<Vec<_>>>()?;
+            (accounts.get(2) == Some(mint)).then_some(accounts)
+        })?;
+
+        // the last event of the mint holds the reserves after the target's trade
+        let (event, fees) = meta["logMessages"]
+            .as_array()?
+            .iter()
+            .filter_map(|log| log.as_str()?.strip_prefix("Program data: "))
+            .filter_map(|data| base64::decode(data).ok())
+            .filter(|data| data.starts_with(&PUMP_TRADE_EVENT_DISCRIMINATOR))
+            .filter_map(|data| {
+                let mut data = &data[8..];
+                let event = TradeEvent::deserialize(&mut data).ok()?;
+                Some((event, TradeEventFees::deserialize(&mut data).ok()))
+            })
+            .filter(|(event, _)| event.mint == mint.to_bytes())
+            .last()?;
+
+        let token_balance = meta["postTokenBalances"]
+            .as_array()?
+            .iter()
+            .find(|balance| balance["mint"].as_str() == Some(&mint.to_string()))?;
+        let token_decimals = token_balance["uiTokenAmount"]["decimals"].as_u64()?;
+        let token_program = Pubkey::from_str(token_balance["programId"].as_str()?).ok()?;
+
+        Some(Self {
+            bonding_curve: *accounts.get(3)?,
+            associated_bonding_curve: *accounts.get(4)?,
+            virtual_sol_reserves: event.virtual_sol_reserves,
+            virtual_token_reserves: event.virtual_token_reserves,
+            real_token_reserves: event.real_token_reserves,
+            token_decimals: token_decimals as u8,
+            token_program,
+            creator: fees
+                .map(|fees| Pubkey::new_from_array(fees.creator))
+                .filter(|creator| *creator != Pubkey::default()),
+        })
+    }
 }
 
+// On-chain layout of the Global account; newer program versions append
+// fields after these, which are ignored.
+#[derive(Debug, BorshDeserialize)]
+struct GlobalAccount {
+    discriminator: u64,
+    initialized: bool,
+    authority: [u8; 32],
+    fee_recipient: [u8; 32],
+    initial_virtual_token_reserves: u64,
+    initial_virtual_sol_reserves: u64,
+    initial_real_token_reserves: u64,
+    token_total_supply: u64,
+    fee_basis_points: u64,
+}
+
+// Fields the creator fee upgrade appended to the Global account
+#[derive(Debug, BorshDeserialize)]
+struct GlobalAccountCreatorFee {
+    _withdraw_authority: [u8; 32],
+    _enable_migrate: bool,
+    _pool_migration_fee: u64,
+    creator_fee_basis_points: u64,
+}
+
+/// Program-wide pump.fun settings from the `PUMP_GLOBAL` account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PumpGlobal {
+    pub authority: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+    /// 0 before the creator fee upgrade.
+    pub creator_fee_basis_points: u64,
+    /// Swap layout of the deployed program, detected from the upgrades it has
+    /// been through.
+    pub layout: PumpLayout,
+}
+
+impl PumpGlobal {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut data = data;
+        let account = GlobalAccount::deserialize(&mut data)
+            .map_err(|e| anyhow!("failed to deserialize pump global account: {}", e))?;
+        let creator_fee = GlobalAccountCreatorFee::deserialize(&mut data).ok();
+        if !account.initialized {
+            return Err(anyhow!(
+                "pump global account {} is not initialized",
+                account.discriminator
+            ));
+        }
+        Ok(Self {
+            authority: Pubkey::new_from_array(account.authority),
+            fee_recipient: Pubkey::new_from_array(account.fee_recipient),
+            initial_virtual_token_reserves: account.initial_virtual_token_reserves,
+            initial_virtual_sol_reserves: account.initial_virtual_sol_reserves,
+            initial_real_token_reserves: account.initial_real_token_reserves,
+            token_total_supply: account.token_total_supply,
+            fee_basis_points: account.fee_basis_points,
+            creator_fee_basis_points: creator_fee
+                .as_ref()
+                .map(|fees| fees.creator_fee_basis_points)
+                .unwrap_or(0),
+            // the fee program layout is only visible from its config account,
+            // see `refresh_global`
+            layout: match creator_fee {
+                Some(_) => PumpLayout::CreatorFee,
+                None => PumpLayout::Legacy,
+            },
+        })
+    }
+}
+
+/// Reads the Global account from chain and replaces the cached copy. The
+/// layout is upgraded to `FeeProgram` once the fee program's config account
+/// for pump.fun exists.
+pub async fn refresh_global(rpc_pool: Arc<RpcPool>) -> Result<PumpGlobal> {
+    let addresses = [
+        Pubkey::from_str(PUMP_GLOBAL)?,
+        pump_instructions::fee_config_pda(),
+    ];
+    let addresses = &addresses;
+    let accounts = rpc_pool
+        .read(|client| async move { client.get_multiple_accounts(addresses).await })
+        .await?;
+    let data = match accounts.first() {
+        Some(Some(account)) => &account.data,
+        _ => return Err(anyhow!("pump global account {} not found", PUMP_GLOBAL)),
+    };
+    let mut global = PumpGlobal::decode(data)?;
+    if global.layout == PumpLayout::CreatorFee && matches!(accounts.get(1), Some(Some(_))) {
+        global.layout = PumpLayout::FeeProgram;
+    }
+    *PUMP_GLOBAL_STATE.write().unwrap() = Some(global.clone());
+    Ok(global)
+}
+
+/// The cached Global account, if it has been loaded.
+pub fn cached_global() -> Option<PumpGlobal> {
+    PUMP_GLOBAL_STATE.read().unwrap().clone()
+}
+
+/// The cached Global account, fetched on first use.
+pub async fn get_global(rpc_pool: Arc<RpcPool>) -> Result<PumpGlobal> {
+    match cached_global() {
+        Some(global) => Ok(global),
+        None => refresh_global(rpc_pool).await,
+    }
+}
+
+/// Loads the Global account and keeps re-reading it in the background once
+/// per process, so rotated fee recipients are picked up without a restart.
+pub async fn start_global_refresher(rpc_pool: Arc<RpcPool>) -> Result<PumpGlobal> {
+    let global = refresh_global(rpc_pool.clone()).await?;
+    GLOBAL_REFRESHER
+        .get_or_init(|| async {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(GLOBAL_REFRESH_INTERVAL);
+                // the first tick completes immediately and the account was just read
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let previous = cached_global();
+                    match refresh_global(rpc_pool.clone()).await {
+                        Ok(global) => {
+                            if previous.as_ref().map(|p| p.fee_recipient)
+                                != Some(global.fee_recipient)
+                            {
+                                println!("pump.fun fee recipient is now {}", global.fee_recipient);
+                            }
+                            if previous.map(|p| p.layout) != Some(global.layout) {
+                                println!("pump.fun swap layout is now {:?}", global.layout);
+                            }
+                        }
+                        Err(e) => println!("Failed to refresh pump global account: {}", e),
+                    }
+                }
+            });
+        })
+        .await;
+    Ok(global)
+}
+
+/// `token_program` owns the mint; the curve's token account is derived under it.
 pub async fn get_bonding_curve_account(
-    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    rpc_pool: Arc<RpcPool>,
     mint: Pubkey,
     program_id: Pubkey,
+    token_program: &Pubkey,
 ) -> Result<(Pubkey, Pubkey, BondingCurveAccount)> {
     let bonding_curve = get_pda(&mint, &program_id)?;
-    let associated_bonding_curve = get_associated_token_address(&bonding_curve, &mint);
+    let associated_bonding_curve =
+        get_associated_token_address_with_program_id(&bonding_curve, &mint, token_program);
     let start_time = Instant::now();
     // println!("mint: {}, Start: {:?}", mint, start_time.elapsed());
 
+    // Served from the account subscription when the curve is being watched
+    if let Some(data) = cache::account_data(&bonding_curve) {
+        if let Ok(bonding_curve_account) = BondingCurveAccount::decode(&data) {
+            return Ok((
+                bonding_curve,
+                associated_bonding_curve,
+                bonding_curve_account,
+            ));
+        }
+    }
+
     let max_retries = 30;
     let time_exceed = 300;
     let timeout = Duration::from_millis(time_exceed);
     let mut retry_count = 0;
     let bonding_curve_data = loop {
-        match rpc_client.get_account_data(&bonding_curve) {
+        match rpc_pool
+            .read(|client| async move { client.get_account_data(&bonding_curve).await })
+            .await
+        {
             Ok(data) => {
                 // println!("Data: {:?}", data);
                 break data;
@@ -403,13 +614,7 @@ pub async fn get_bonding_curve_account(
         }
     };
 
-    let bonding_curve_account =
-        from_slice::<BondingCurveAccount>(&bonding_curve_data).map_err(|e| {
-            anyhow!(
-                "Failed to deserialize bonding curve account: {}",
-                e.to_string()
-            )
-        })?;
+    let bonding_curve_account = BondingCurveAccount::decode(&bonding_curve_data)?;
 
     Ok((
         bonding_curve,
@@ -425,14 +630,12 @@ pub fn get_pda(mint: &Pubkey, program_id: &Pubkey) -> Result<Pubkey> {
 }
 
 // https://frontend-api.pump.fun/coins/8zSLdDzM1XsqnfrHmHvA9ir6pvYDjs8UXz6B2Tydd6b2
-pub async fn get_pump_info(
-    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
-    mint: &str,
-) -> Result<PumpInfo> {
+pub async fn get_pump_info(rpc_pool: Arc<RpcPool>, mint: &str) -> Result<PumpInfo> {
     let mint = Pubkey::from_str(mint)?;
     let program_id = Pubkey::from_str(PUMP_PROGRAM)?;
+    let token_program = token::get_token_program(rpc_pool.clone(), mint).await?;
     let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
-        get_bonding_curve_account(rpc_client, mint, program_id).await?;
+        get_bonding_curve_account(rpc_pool, mint, program_id, &token_program).await?;
 
     let pump_info = PumpInfo {
         mint: mint.to_string(),
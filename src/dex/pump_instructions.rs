@@ -0,0 +1,446 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::dex::pump_fun::{
+    cached_global, ASSOCIATED_TOKEN_PROGRAM, PUMP_ACCOUNT, PUMP_BUY_METHOD, PUMP_GLOBAL,
+    PUMP_PROGRAM, PUMP_SELL_METHOD, RENT_PROGRAM,
+};
+
+pub const PUMP_FEE_PROGRAM: &str = "pfeeUxB6jkeY1Hxd7CsFCAjcbHA9rWtchMGdZ6VojVZ";
+
+/// Account layouts of the pump.fun buy and sell instructions, oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PumpLayout {
+    /// The original 12 accounts, with the rent sysvar on buy and the
+    /// associated token program on sell.
+    Legacy,
+    /// Adds the coin creator's fee vault in place of the rent sysvar and the
+    /// associated token program.
+    CreatorFee,
+    /// Adds the volume accumulators on buy and the fee config and fee program
+    /// on both sides.
+    FeeProgram,
+}
+
+impl PumpLayout {
+    /// The layout the deployed program expects, as detected from the cached
+    /// Global account. Fails until the account has been loaded rather than
+    /// guessing, since a wrong layout fails every swap.
+    pub fn current() -> Result<Self> {
+        cached_global()
+            .map(|global| global.layout)
+            .ok_or_else(|| anyhow!("pump.fun global account is not loaded, layout unknown"))
+    }
+
+    pub fn needs_creator_vault(&self) -> bool {
+        *self != PumpLayout::Legacy
+    }
+}
+
+/// Accounts of one buy or sell that don't follow from PDAs.
+#[derive(Debug, Clone)]
+pub struct PumpSwapAccounts {
+    pub fee_recipient: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub user_ata: Pubkey,
+    pub user: Pubkey,
+    pub token_program: Pubkey,
+    /// Coin creator from the bonding curve or the trade event; required by
+    /// every layout after `Legacy`.
+    pub creator: Option<Pubkey>,
+}
+
+impl PumpSwapAccounts {
+    fn creator_vault(&self) -> Result<Pubkey> {
+        let creator = self
+            .creator
+            .ok_or_else(|| anyhow!("creator of {} is unknown", self.mint))?;
+        Ok(creator_vault_pda(&creator))
+    }
+}
+
+fn pump_program() -> Pubkey {
+    Pubkey::from_str(PUMP_PROGRAM).unwrap()
+}
+
+fn fee_program() -> Pubkey {
+    Pubkey::from_str(PUMP_FEE_PROGRAM).unwrap()
+}
+
+pub fn event_authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &pump_program()).0
+}
+
+pub fn creator_vault_pda(creator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"creator-vault", creator.as_ref()], &pump_program()).0
+}
+
+pub fn global_volume_accumulator_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"global_volume_accumulator"], &pump_program()).0
+}
+
+pub fn user_volume_accumulator_pda(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"user_volume_accumulator", user.as_ref()],
+        &pump_program(),
+    )
+    .0
+}
+
+pub fn fee_config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"fee_config", pump_program().as_ref()], &fee_program()).0
+}
+
+/// Program-owned accounts every swap of `layout` touches, for lookup tables.
+pub fn fixed_accounts(layout: PumpLayout) -> Vec<Pubkey> {
+    match layout {
+        PumpLayout::Legacy | PumpLayout::CreatorFee => vec![],
+        PumpLayout::FeeProgram => vec![
+            global_volume_accumulator_pda(),
+            fee_config_pda(),
+            fee_program(),
+        ],
+    }
+}
+
+/// Buys `token_amount` for at most `max_sol_cost` lamports.
+pub fn buy(
+    layout: PumpLayout,
+    accounts: &PumpSwapAccounts,
+    token_amount: u64,
+    max_sol_cost: u64,
+) -> Result<Instruction> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
+        AccountMeta::new(accounts.fee_recipient, false),
+        AccountMeta::new_readonly(accounts.mint, false),
+        AccountMeta::new(accounts.bonding_curve, false),
+        AccountMeta::new(accounts.associated_bonding_curve, false),
+        AccountMeta::new(accounts.user_ata, false),
+        AccountMeta::new(accounts.user, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(accounts.token_program, false),
+    ];
+    match layout {
+        PumpLayout::Legacy => {
+            metas.push(AccountMeta::new_readonly(
+                Pubkey::from_str(RENT_PROGRAM)?,
+                false,
+            ));
+            metas.push(AccountMeta::new_readonly(
+                Pubkey::from_str(PUMP_ACCOUNT)?,
+                false,
+            ));
+            metas.push(AccountMeta::new_readonly(pump_program(), false));
+        }
+        PumpLayout::CreatorFee | PumpLayout::FeeProgram => {
+            metas.push(AccountMeta::new(accounts.creator_vault()?, false));
+            metas.push(AccountMeta::new_readonly(event_authority_pda(), false));
+            metas.push(AccountMeta::new_readonly(pump_program(), false));
+        }
+    }
+    if layout == PumpLayout::FeeProgram {
+        metas.push(AccountMeta::new(global_volume_accumulator_pda(), false));
+        metas.push(AccountMeta::new(
+            user_volume_accumulator_pda(&accounts.user),
+            false,
+        ));
+        metas.push(AccountMeta::new_readonly(fee_config_pda(), false));
+        metas.push(AccountMeta::new_readonly(fee_program(), false));
+    }
+
+    Ok(Instruction::new_with_bincode(
+        pump_program(),
+        &(PUMP_BUY_METHOD, token_amount, max_sol_cost),
+        metas,
+    ))
+}
+
+/// Sells `token_amount` for at least `min_sol_output` lamports.
+pub fn sell(
+    layout: PumpLayout,
+    accounts: &PumpSwapAccounts,
+    token_amount: u64,
+    min_sol_output: u64,
+) -> Result<Instruction> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(Pubkey::from_str(PUMP_GLOBAL)?, false),
+        AccountMeta::new(accounts.fee_recipient, false),
+        AccountMeta::new_readonly(accounts.mint, false),
+        AccountMeta::new(accounts.bonding_curve, false),
+        AccountMeta::new(accounts.associated_bonding_curve, false),
+        AccountMeta::new(accounts.user_ata, false),
+        AccountMeta::new(accounts.user, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    match layout {
+        PumpLayout::Legacy => {
+            metas.push(AccountMeta::new_readonly(
+                Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM)?,
+                false,
+            ));
+            metas.push(AccountMeta::new_readonly(accounts.token_program, false));
+            metas.push(AccountMeta::new_readonly(
+                Pubkey::from_str(PUMP_ACCOUNT)?,
+                false,
+            ));
+            metas.push(AccountMeta::new_readonly(pump_program(), false));
+        }
+        PumpLayout::CreatorFee | PumpLayout::FeeProgram => {
+            metas.push(AccountMeta::new(accounts.creator_vault()?, false));
+            metas.push(AccountMeta::new_readonly(accounts.token_program, false));
+            metas.push(AccountMeta::new_readonly(event_authority_pda(), false));
+            metas.push(AccountMeta::new_readonly(pump_program(), false));
+        }
+    }
+    if layout == PumpLayout::FeeProgram {
+        metas.push(AccountMeta::new_readonly(fee_config_pda(), false));
+        metas.push(AccountMeta::new_readonly(fee_program(), false));
+    }
+
+    Ok(Instruction::new_with_bincode(
+        pump_program(),
+        &(PUMP_SELL_METHOD, token_amount, min_sol_output),
+        metas,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLOBAL: &str = "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf";
+    const FEE_RECIPIENT: &str = "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi";
+    const MINT: &str = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR";
+    const BONDING_CURVE: &str = "CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8";
+    const ASSOCIATED_BONDING_CURVE: &str = "GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq";
+    const USER_ATA: &str = "LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY";
+    const USER: &str = "QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF";
+    const CREATOR: &str = "YMN9Qj5jPNp7j14VPcML1B6xGgcPWVZUGLFU3Mnyfaf";
+    const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+    const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const RENT: &str = "SysvarRent111111111111111111111111111111111";
+    const ASSOCIATED_TOKEN: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+    const PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+    const EVENT_AUTHORITY: &str = "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1";
+    const CREATOR_VAULT: &str = "KXcmUT1cpxNF4zvGaJbE1YpXJmm1UpwUgkzPrhXCc84";
+    const GLOBAL_VOLUME_ACCUMULATOR: &str = "Hq2wp8uJ9jCPsYgNHex8RtqdvMPfVGoYwjvF1ATiwn2Y";
+    const USER_VOLUME_ACCUMULATOR: &str = "6U2k8qaiBUxF8HP8gbxHaiPjFQdfqLhVVijE7kkPim8q";
+    const FEE_CONFIG: &str = "8Wf5TiAheLUqBrKXeYg2JtAFFMWtKdG2BSFgqUcPVwTt";
+    const FEE_PROGRAM: &str = "pfeeUxB6jkeY1Hxd7CsFCAjcbHA9rWtchMGdZ6VojVZ";
+
+    // discriminator, 1_000_000 tokens, 2_000_000 lamports at most
+    const BUY_DATA: [u8; 24] = [
+        102, 6, 61, 18, 1, 218, 235, 234, 64, 66, 15, 0, 0, 0, 0, 0, 128, 132, 30, 0, 0, 0, 0, 0,
+    ];
+    // discriminator, 1_000_000 tokens, 900_000 lamports at least
+    const SELL_DATA: [u8; 24] = [
+        51, 230, 133, 164, 1, 127, 131, 173, 64, 66, 15, 0, 0, 0, 0, 0, 160, 187, 13, 0, 0, 0, 0, 0,
+    ];
+
+    fn key(address: &str) -> Pubkey {
+        Pubkey::from_str(address).unwrap()
+    }
+
+    fn writable(address: &str) -> AccountMeta {
+        AccountMeta::new(key(address), false)
+    }
+
+    fn readonly(address: &str) -> AccountMeta {
+        AccountMeta::new_readonly(key(address), false)
+    }
+
+    fn signer(address: &str) -> AccountMeta {
+        AccountMeta::new(key(address), true)
+    }
+
+    fn accounts() -> PumpSwapAccounts {
+        PumpSwapAccounts {
+            fee_recipient: key(FEE_RECIPIENT),
+            mint: key(MINT),
+            bonding_curve: key(BONDING_CURVE),
+            associated_bonding_curve: key(ASSOCIATED_BONDING_CURVE),
+            user_ata: key(USER_ATA),
+            user: key(USER),
+            token_program: key(TOKEN_PROGRAM),
+            creator: Some(key(CREATOR)),
+        }
+    }
+
+    fn buy_instruction(layout: PumpLayout) -> Instruction {
+        buy(layout, &accounts(), 1_000_000, 2_000_000).unwrap()
+    }
+
+    fn sell_instruction(layout: PumpLayout) -> Instruction {
+        sell(layout, &accounts(), 1_000_000, 900_000).unwrap()
+    }
+
+    #[test]
+    fn legacy_buy() {
+        let instruction = buy_instruction(PumpLayout::Legacy);
+        assert_eq!(instruction.program_id, key(PROGRAM));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                readonly(GLOBAL),
+                writable(FEE_RECIPIENT),
+                readonly(MINT),
+                writable(BONDING_CURVE),
+                writable(ASSOCIATED_BONDING_CURVE),
+                writable(USER_ATA),
+                signer(USER),
+                readonly(SYSTEM_PROGRAM),
+                readonly(TOKEN_PROGRAM),
+                readonly(RENT),
+                readonly(EVENT_AUTHORITY),
+                readonly(PROGRAM),
+            ]
+        );
+        assert_eq!(instruction.data, BUY_DATA);
+    }
+
+    #[test]
+    fn legacy_sell() {
+        let instruction = sell_instruction(PumpLayout::Legacy);
+        assert_eq!(instruction.program_id, key(PROGRAM));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                readonly(GLOBAL),
+                writable(FEE_RECIPIENT),
+                readonly(MINT),
+                writable(BONDING_CURVE),
+                writable(ASSOCIATED_BONDING_CURVE),
+                writable(USER_ATA),
+                signer(USER),
+                readonly(SYSTEM_PROGRAM),
+                readonly(ASSOCIATED_TOKEN),
+                readonly(TOKEN_PROGRAM),
+                readonly(EVENT_AUTHORITY),
+                readonly(PROGRAM),
+            ]
+        );
+        assert_eq!(instruction.data, SELL_DATA);
+    }
+
+    #[test]
+    fn creator_fee_buy() {
+        let instruction = buy_instruction(PumpLayout::CreatorFee);
+        assert_eq!(instruction.program_id, key(PROGRAM));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                readonly(GLOBAL),
+                writable(FEE_RECIPIENT),
+                readonly(MINT),
+                writable(BONDING_CURVE),
+                writable(ASSOCIATED_BONDING_CURVE),
+                writable(USER_ATA),
+                signer(USER),
+                readonly(SYSTEM_PROGRAM),
+                readonly(TOKEN_PROGRAM),
+                writable(CREATOR_VAULT),
+                readonly(EVENT_AUTHORITY),
+                readonly(PROGRAM),
+            ]
+        );
+        assert_eq!(instruction.data, BUY_DATA);
+    }
+
+    #[test]
+    fn creator_fee_sell() {
+        let instruction = sell_instruction(PumpLayout::CreatorFee);
+        assert_eq!(instruction.program_id, key(PROGRAM));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                readonly(GLOBAL),
+                writable(FEE_RECIPIENT),
+                readonly(MINT),
+                writable(BONDING_CURVE),
+                writable(ASSOCIATED_BONDING_CURVE),
+                writable(USER_ATA),
+                signer(USER),
+                readonly(SYSTEM_PROGRAM),
+                writable(CREATOR_VAULT),
+                readonly(TOKEN_PROGRAM),
+                readonly(EVENT_AUTHORITY),
+                readonly(PROGRAM),
+            ]
+        );
+        assert_eq!(instruction.data, SELL_DATA);
+    }
+
+    #[test]
+    fn fee_program_buy() {
+        let instruction = buy_instruction(PumpLayout::FeeProgram);
+        assert_eq!(instruction.program_id, key(PROGRAM));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                readonly(GLOBAL),
+                writable(FEE_RECIPIENT),
+                readonly(MINT),
+                writable(BONDING_CURVE),
+                writable(ASSOCIATED_BONDING_CURVE),
+                writable(USER_ATA),
+                signer(USER),
+                readonly(SYSTEM_PROGRAM),
+                readonly(TOKEN_PROGRAM),
+                writable(CREATOR_VAULT),
+                readonly(EVENT_AUTHORITY),
+                readonly(PROGRAM),
+                writable(GLOBAL_VOLUME_ACCUMULATOR),
+                writable(USER_VOLUME_ACCUMULATOR),
+                readonly(FEE_CONFIG),
+                readonly(FEE_PROGRAM),
+            ]
+        );
+        assert_eq!(instruction.data, BUY_DATA);
+    }
+
+    #[test]
+    fn fee_program_sell() {
+        let instruction = sell_instruction(PumpLayout::FeeProgram);
+        assert_eq!(instruction.program_id, key(PROGRAM));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                readonly(GLOBAL),
+                writable(FEE_RECIPIENT),
+                readonly(MINT),
+                writable(BONDING_CURVE),
+                writable(ASSOCIATED_BONDING_CURVE),
+                writable(USER_ATA),
+                signer(USER),
+                readonly(SYSTEM_PROGRAM),
+                writable(CREATOR_VAULT),
+                readonly(TOKEN_PROGRAM),
+                readonly(EVENT_AUTHORITY),
+                readonly(PROGRAM),
+                readonly(FEE_CONFIG),
+                readonly(FEE_PROGRAM),
+            ]
+        );
+        assert_eq!(instruction.data, SELL_DATA);
+    }
+
+    #[test]
+    fn creator_vault_layouts_need_the_creator() {
+        let accounts = PumpSwapAccounts {
+            creator: None,
+            ..accounts()
+        };
+        assert!(buy(PumpLayout::Legacy, &accounts, 1, 1).is_ok());
+        assert!(buy(PumpLayout::CreatorFee, &accounts, 1, 1).is_err());
+        assert!(sell(PumpLayout::FeeProgram, &accounts, 1, 1).is_err());
+    }
+}
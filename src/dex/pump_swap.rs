@@ -0,0 +1,741 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Result};
+use borsh::BorshDeserialize as _;
+use borsh_derive::BorshDeserialize;
+use lazy_static::lazy_static;
+use serde_json::Value;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    bs58,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+use tokio::sync::OnceCell;
+
+use crate::{
+    core::token,
+    dex::{
+        bonding_curve::FEE_DENOMINATOR,
+        executor::{
+            max_amount_with_slippage, min_amount_with_slippage, slippage_bps, swap_amount,
+            wsol_swap_instructions, DexExecutor, SwapBound, SwapQuote,
+        },
+        pump_fun::PUMP_PROGRAM,
+        pump_instructions::{PumpLayout, PUMP_FEE_PROGRAM},
+    },
+    engine::swap::SwapDirection,
+    services::rpc_pool::RpcPool,
+    utils::{config::SwapConfig, logger::Logger},
+};
+
+pub const PUMP_SWAP_PROGRAM: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+pub const PUMP_SWAP_BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+pub const PUMP_SWAP_SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+// offsets of the mints in the pool account, for program account filters
+const POOL_BASE_MINT_OFFSET: usize = 43;
+const POOL_QUOTE_MINT_OFFSET: usize = 75;
+
+static GLOBAL_CONFIG: OnceCell<PumpSwapGlobalConfig> = OnceCell::const_new();
+
+lazy_static! {
+    // base mint -> pool, which never changes once a coin has migrated
+    static ref POOLS: RwLock<HashMap<Pubkey, PumpSwapPool>> = RwLock::new(HashMap::new());
+    // vault authority of pools without a coin creator
+    static ref NO_COIN_CREATOR_VAULT_AUTHORITY: Pubkey =
+        coin_creator_vault_authority_pda(&Pubkey::default());
+}
+
+#[derive(Clone)]
+pub struct PumpSwap {
+    pub rpc_pool: Arc<RpcPool>,
+    pub keypair: Arc<Keypair>,
+}
+
+/// Accounts of a quoted PumpSwap swap.
+#[derive(Debug, Clone)]
+pub struct PumpSwapRoute {
+    pub layout: PumpLayout,
+    pub pool: PumpSwapPool,
+    pub protocol_fee_recipient: Pubkey,
+}
+
+impl PumpSwap {
+    pub fn new(rpc_pool: Arc<RpcPool>, keypair: Arc<Keypair>) -> Self {
+        Self { rpc_pool, keypair }
+    }
+}
+
+impl DexExecutor for PumpSwap {
+    type Route = PumpSwapRoute;
+    type Trade = PumpSwapTrade;
+
+    fn venue(&self) -> &'static str {
+        "PUMPSWAP"
+    }
+
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
+    }
+
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
+
+    async fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<PumpSwapRoute>> {
+        self.quote_trade(mint, None, swap_config, logger).await
+    }
+
+    /// Quotes on the pool reserves `trade` left behind, so the pool is not
+    /// read from RPC; reads it when `trade` is `None`.
+    async fn quote_trade(
+        &self,
+        mint: Pubkey,
+        trade: Option<PumpSwapTrade>,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<PumpSwapRoute>> {
+        let slippage_bps = slippage_bps(swap_config);
+        let global_config = get_global_config(self.rpc_pool.clone()).await?;
+        let (pool, reserves, base_program, protocol_fee_recipient) = match trade {
+            Some(trade) => (
+                trade.pool,
+                trade.reserves,
+                trade.base_token_program,
+                trade.protocol_fee_recipient,
+            ),
+            None => {
+                let pool = get_pool_by_mint(self.rpc_pool.clone(), &mint).await?;
+                let reserves = get_reserves(&self.rpc_pool, &pool).await?;
+                let base_program = token::get_token_program(self.rpc_pool.clone(), mint).await?;
+                (
+                    pool,
+                    reserves,
+                    base_program,
+                    global_config.protocol_fee_recipient()?,
+                )
+            }
+        };
+        let reserves = PoolReserves {
+            fee_basis_points: global_config.total_fee_basis_points(&pool),
+            ..reserves
+        };
+
+        // the decimals come with the trade, no account reads needed
+        let swap_amount = swap_amount(
+            &self.rpc_pool,
+            &self.keypair,
+            mint,
+            base_program,
+            Some(reserves.base_decimals).filter(|_| reserves.base_decimals_known),
+            swap_config,
+        )
+        .await?;
+        let (amount_out, bound) = match swap_config.swap_direction {
+            SwapDirection::Buy => {
+                let base_amount_out = reserves.buy_quote(swap_amount.amount);
+                if base_amount_out == 0 {
+                    return Err(anyhow!("pool {} has no tokens to buy", pool.address));
+                }
+                (
+                    base_amount_out,
+                    SwapBound::MaxAmountIn(max_amount_with_slippage(
+                        swap_amount.amount,
+                        slippage_bps,
+                    )),
+                )
+            }
+            SwapDirection::Sell => {
+                // the pool only receives what is left after the transfer fee
+                let quote_amount_out =
+                    reserves.sell_quote(swap_amount.amount - swap_amount.transfer_fee);
+                (
+                    quote_amount_out,
+                    SwapBound::MinAmountOut(min_amount_with_slippage(
+                        quote_amount_out,
+                        slippage_bps,
+                    )),
+                )
+            }
+        };
+        logger.debug(format!(
+            "amount_in: {}, amount_out: {}, bound: {:?}, price: {} lamports",
+            swap_amount.amount,
+            amount_out,
+            bound,
+            reserves.price()
+        ));
+
+        Ok(SwapQuote {
+            mint,
+            token_program: base_program,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound,
+            close_instruction: swap_amount.close_instruction,
+            route: PumpSwapRoute {
+                layout: global_config.layout,
+                pool,
+                protocol_fee_recipient,
+            },
+        })
+    }
+
+    fn build_instructions(&self, quote: &SwapQuote<PumpSwapRoute>) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        // buys name the base amount out, sells the base amount in
+        let (base_amount, quote_limit) = match quote.bound {
+            SwapBound::MaxAmountIn(max_quote_amount_in) => (quote.amount_out, max_quote_amount_in),
+            SwapBound::MinAmountOut(min_quote_amount_out) => {
+                (quote.amount_in, min_quote_amount_out)
+            }
+        };
+        let swap_instruction = pump_swap_instruction(
+            quote.route.layout,
+            quote.direction.clone(),
+            &quote.route.pool,
+            &owner,
+            &quote.token_program,
+            &quote.route.protocol_fee_recipient,
+            base_amount,
+            quote_limit,
+        )?;
+        wsol_swap_instructions(&owner, quote, swap_instruction)
+    }
+}
+
+/// Constant-product quote math of a PumpSwap pool; fees are charged on the
+/// quote side in both directions.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolReserves {
+    pub base_reserve: u64,
+    pub quote_reserve: u64,
+    pub base_decimals: u8,
+    // set when the decimals came with a target's trade
+    base_decimals_known: bool,
+    pub fee_basis_points: u64,
+}
+
+impl PoolReserves {
+    fn fee(&self, lamports: u64) -> u64 {
+        (lamports as u128 * self.fee_basis_points as u128).div_ceil(FEE_DENOMINATOR as u128) as u64
+    }
+
+    /// Base tokens received for spending `quote_amount`, fees included.
+    pub fn buy_quote(&self, quote_amount: u64) -> u64 {
+        if quote_amount == 0 || self.quote_reserve == 0 {
+            return 0;
+        }
+        let quote_in = quote_amount as u128 * FEE_DENOMINATOR as u128
+            / (FEE_DENOMINATOR + self.fee_basis_points) as u128;
+        let base_out =
+            self.base_reserve as u128 * quote_in / (self.quote_reserve as u128 + quote_in);
+        // the program rounds the cost of the output up
+        (base_out as u64).saturating_sub(1)
+    }
+
+    /// Quote lamports received for selling `base_amount`, fees deducted.
+    pub fn sell_quote(&self, base_amount: u64) -> u64 {
+        if base_amount == 0 {
+            return 0;
+        }
+        let quote_out = self.quote_reserve as u128 * base_amount as u128
+            / (self.base_reserve as u128 + base_amount as u128);
+        let quote_out = quote_out as u64;
+        quote_out.saturating_sub(self.fee(quote_out))
+    }
+
+    /// Spot price in lamports per base token unit.
+    pub fn price(&self) -> f64 {
+        if self.base_reserve == 0 {
+            return 0.0;
+        }
+        self.quote_reserve as f64 / self.base_reserve as f64
+    }
+}
+
+// On-chain layout of a pool; `coin_creator` was appended by the creator fee
+// upgrade and is read separately.
+#[derive(Debug, BorshDeserialize)]
+struct PoolAccount {
+    _discriminator: u64,
+    _pool_bump: u8,
+    _index: u16,
+    creator: [u8; 32],
+    base_mint: [u8; 32],
+    quote_mint: [u8; 32],
+    _lp_mint: [u8; 32],
+    pool_base_token_account: [u8; 32],
+    pool_quote_token_account: [u8; 32],
+    _lp_supply: u64,
+}
+
+/// Accounts of a PumpSwap pool needed to trade it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PumpSwapPool {
+    pub address: Pubkey,
+    pub creator: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub pool_base_token_account: Pubkey,
+    pub pool_quote_token_account: Pubkey,
+    pub coin_creator_vault_authority: Pubkey,
+}
+
+impl PumpSwapPool {
+    pub fn decode(address: Pubkey, data: &[u8]) -> Result<Self> {
+        let mut data = data;
+        let account = PoolAccount::deserialize(&mut data)
+            .map_err(|e| anyhow!("failed to deserialize pump swap pool {}: {}", address, e))?;
+        // pools migrated before the upgrade have no coin creator and pay the
+        // default key's vault
+        let coin_creator = <[u8; 32]>::deserialize(&mut data)
+            .map(Pubkey::new_from_array)
+            .unwrap_or_default();
+        Ok(Self {
+            address,
+            creator: Pubkey::new_from_array(account.creator),
+            base_mint: Pubkey::new_from_array(account.base_mint),
+            quote_mint: Pubkey::new_from_array(account.quote_mint),
+            pool_base_token_account: Pubkey::new_from_array(account.pool_base_token_account),
+            pool_quote_token_account: Pubkey::new_from_array(account.pool_quote_token_account),
+            coin_creator_vault_authority: coin_creator_vault_authority_pda(&coin_creator),
+        })
+    }
+
+    /// Whether the coin has a creator to pay the creator fee to, i.e. whether
+    /// its coin creator isn't the default key. Told from the creator's vault
+    /// authority, which a target's trade names while the creator itself is
+    /// only in the pool account.
+    pub fn has_coin_creator(&self) -> bool {
+        self.coin_creator_vault_authority != *NO_COIN_CREATOR_VAULT_AUTHORITY
+    }
+}
+
+// On-chain layout of the GlobalConfig account, up to the protocol fee
+// recipients; the coin creator fee is read separately.
+#[derive(Debug, BorshDeserialize)]
+struct GlobalConfigAccount {
+    _discriminator: u64,
+    _admin: [u8; 32],
+    lp_fee_basis_points: u64,
+    protocol_fee_basis_points: u64,
+    _disable_flags: u8,
+    protocol_fee_recipients: [[u8; 32]; 8],
+}
+
+/// Fee settings of the PumpSwap program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PumpSwapGlobalConfig {
+    pub lp_fee_basis_points: u64,
+    pub protocol_fee_basis_points: u64,
+    /// 0 before the creator fee upgrade.
+    pub coin_creator_fee_basis_points: u64,
+    pub protocol_fee_recipients: Vec<Pubkey>,
+    /// Swap layout of the deployed program, detected from the upgrades it has
+    /// been through.
+    pub layout: PumpLayout,
+}
+
+impl PumpSwapGlobalConfig {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut data = data;
+        let account = GlobalConfigAccount::deserialize(&mut data)
+            .map_err(|e| anyhow!("failed to deserialize pump swap global config: {}", e))?;
+        let coin_creator_fee_basis_points = u64::deserialize(&mut data).ok();
+        Ok(Self {
+            lp_fee_basis_points: account.lp_fee_basis_points,
+            protocol_fee_basis_points: account.protocol_fee_basis_points,
+            coin_creator_fee_basis_points: coin_creator_fee_basis_points.unwrap_or(0),
+            protocol_fee_recipients: account
+                .protocol_fee_recipients
+                .into_iter()
+                .map(Pubkey::new_from_array)
+                .filter(|recipient| *recipient != Pubkey::default())
+                .collect(),
+            // the fee program layout is only visible from its config account,
+            // see `get_global_config`
+            layout: match coin_creator_fee_basis_points {
+                Some(_) => PumpLayout::CreatorFee,
+                None => PumpLayout::Legacy,
+            },
+        })
+    }
+
+    /// Fees charged on a swap in `pool`; only pools with a coin creator charge
+    /// the creator fee.
+    pub fn total_fee_basis_points(&self, pool: &PumpSwapPool) -> u64 {
+        let coin_creator_fee_basis_points = if pool.has_coin_creator() {
+            self.coin_creator_fee_basis_points
+        } else {
+            0
+        };
+        self.lp_fee_basis_points + self.protocol_fee_basis_points + coin_creator_fee_basis_points
+    }
+
+    pub fn protocol_fee_recipient(&self) -> Result<Pubkey> {
+        self.protocol_fee_recipients
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("pump swap global config has no protocol fee recipient"))
+    }
+}
+
+fn pump_swap_program() -> Pubkey {
+    Pubkey::from_str(PUMP_SWAP_PROGRAM).unwrap()
+}
+
+pub fn global_config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"global_config"], &pump_swap_program()).0
+}
+
+pub fn event_authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &pump_swap_program()).0
+}
+
+pub fn coin_creator_vault_authority_pda(coin_creator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"creator_vault", coin_creator.as_ref()],
+        &pump_swap_program(),
+    )
+    .0
+}
+
+pub fn global_volume_accumulator_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"global_volume_accumulator"], &pump_swap_program()).0
+}
+
+pub fn user_volume_accumulator_pda(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"user_volume_accumulator", user.as_ref()],
+        &pump_swap_program(),
+    )
+    .0
+}
+
+pub fn fee_config_pda() -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"fee_config", pump_swap_program().as_ref()],
+        &Pubkey::from_str(PUMP_FEE_PROGRAM).unwrap(),
+    )
+    .0
+}
+
+/// Creator the pump.fun migration sets on a coin's canonical pool.
+pub fn pool_authority_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"pool-authority", mint.as_ref()],
+        &Pubkey::from_str(PUMP_PROGRAM).unwrap(),
+    )
+    .0
+}
+
+/// Builds a buy (exact `base_amount` out, at most `quote_limit` in) or a sell
+/// (exact `base_amount` in, at least `quote_limit` out) on `pool`. Both share
+/// one account list; `layout` follows the PumpSwap program upgrades.
+#[allow(clippy::too_many_arguments)]
+pub fn pump_swap_instruction(
+    layout: PumpLayout,
+    direction: SwapDirection,
+    pool: &PumpSwapPool,
+    user: &Pubkey,
+    base_token_program: &Pubkey,
+    protocol_fee_recipient: &Pubkey,
+    base_amount: u64,
+    quote_limit: u64,
+) -> Result<Instruction> {
+    let quote_token_program = spl_token::ID;
+    let mut metas = vec![
+        AccountMeta::new_readonly(pool.address, false),
+        AccountMeta::new(*user, true),
+        AccountMeta::new_readonly(global_config_pda(), false),
+        AccountMeta::new_readonly(pool.base_mint, false),
+        AccountMeta::new_readonly(pool.quote_mint, false),
+        AccountMeta::new(
+            get_associated_token_address_with_program_id(user, &pool.base_mint, base_token_program),
+            false,
+        ),
+        AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                user,
+                &pool.quote_mint,
+                &quote_token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new(pool.pool_base_token_account, false),
+        AccountMeta::new(pool.pool_quote_token_account, false),
+        AccountMeta::new_readonly(*protocol_fee_recipient, false),
+        AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                protocol_fee_recipient,
+                &pool.quote_mint,
+                &quote_token_program,
+            ),
+            false,
+        ),
+        AccountMeta::new_readonly(*base_token_program, false),
+        AccountMeta::new_readonly(quote_token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+        AccountMeta::new_readonly(event_authority_pda(), false),
+        AccountMeta::new_readonly(pump_swap_program(), false),
+    ];
+    if layout.needs_creator_vault() {
+        metas.push(AccountMeta::new(
+            get_associated_token_address_with_program_id(
+                &pool.coin_creator_vault_authority,
+                &pool.quote_mint,
+                &quote_token_program,
+            ),
+            false,
+        ));
+        metas.push(AccountMeta::new_readonly(
+            pool.coin_creator_vault_authority,
+            false,
+        ));
+    }
+    if layout == PumpLayout::FeeProgram {
+        if direction == SwapDirection::Buy {
+            metas.push(AccountMeta::new(global_volume_accumulator_pda(), false));
+            metas.push(AccountMeta::new(user_volume_accumulator_pda(user), false));
+        }
+        metas.push(AccountMeta::new_readonly(fee_config_pda(), false));
+        metas.push(AccountMeta::new_readonly(
+            Pubkey::from_str(PUMP_FEE_PROGRAM)?,
+            false,
+        ));
+    }
+
+    let discriminator = match direction {
+        SwapDirection::Buy => PUMP_SWAP_BUY_DISCRIMINATOR,
+        SwapDirection::Sell => PUMP_SWAP_SELL_DISCRIMINATOR,
+    };
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(&base_amount.to_le_bytes());
+    data.extend_from_slice(&quote_limit.to_le_bytes());
+
+    Ok(Instruction::new_with_bytes(
+        pump_swap_program(),
+        &data,
+        metas,
+    ))
+}
+
+/// Pool, post-trade reserves and fee recipient taken from a target's PumpSwap
+/// transaction.
+#[derive(Clone, Debug)]
+pub struct PumpSwapTrade {
+    pub pool: PumpSwapPool,
+    pub reserves: PoolReserves,
+    /// Quote reserve before the target's trade, to size the copy.
+    pub quote_reserve_before: u64,
+    pub base_token_program: Pubkey,
+    pub protocol_fee_recipient: Pubkey,
+}
+
+impl PumpSwapTrade {
+    /// Decodes a SOL-quoted PumpSwap buy or sell from a `transactionSubscribe`
+    /// notification. Returns `None` when the transaction has none.
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let transaction = &json["params"]["result"]["transaction"];
+        let meta = &transaction["meta"];
+
+        // the swap may be a top-level instruction or a CPI from a router
+        let outer = transaction["transaction"]["message"]["instructions"]
+            .as_array()
+            .into_iter()
+            .flatten();
+        let inner = meta["innerInstructions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|inner| inner["instructions"].as_array())
+            .flatten();
+        let accounts = outer.chain(inner).find_map(|ix| {
+            if ix["programId"].as_str() != Some(PUMP_SWAP_PROGRAM) {
+                return None;
+            }
+            let data = bs58::decode(ix["data"].as_str()?).into_vec().ok()?;
+            let discriminator = data.get(..8)?;
+            if discriminator != PUMP_SWAP_BUY_DISCRIMINATOR
+                && discriminator != PUMP_SWAP_SELL_DISCRIMINATOR
+            {
+                return None;
+            }
+            let accounts = ix["accounts"]
+                .as_array()?
+                .iter()
+                .map(|account| Pubkey::from_str(account.as_str()?).ok())
+                .collect::<Option<Vec<_>>>()?;
+            (accounts.get(4) == Some(&spl_token::native_mint::ID)).then_some(accounts)
+        })?;
+
+        let pool = *accounts.first()?;
+        let base_mint = *accounts.get(3)?;
+        let pool_balance = |balances: &str, mint: &Pubkey| {
+            meta[balances].as_array()?.iter().find(|balance| {
+                balance["owner"].as_str() == Some(&pool.to_string())
+                    && balance["mint"].as_str() == Some(&mint.to_string())
+            })
+        };
+        let amount =
+            |balance: &Value| u64::from_str(balance["uiTokenAmount"]["amount"].as_str()?).ok();
+
+        let base_balance = pool_balance("postTokenBalances", &base_mint)?;
+        let quote_mint = spl_token::native_mint::ID;
+        let quote_reserve = amount(pool_balance("postTokenBalances", &quote_mint)?)?;
+        let quote_reserve_before = amount(pool_balance("preTokenBalances", &quote_mint)?)?;
+
+        Some(Self {
+            pool: PumpSwapPool {
+                address: pool,
+                // only known from the pool account; not needed to trade
+                creator: Pubkey::default(),
+                base_mint,
+                quote_mint,
+                pool_base_token_account: *accounts.get(7)?,
+                pool_quote_token_account: *accounts.get(8)?,
+                coin_creator_vault_authority: accounts
+                    .get(18)
+                    .copied()
+                    .unwrap_or_else(|| coin_creator_vault_authority_pda(&Pubkey::default())),
+            },
+            reserves: PoolReserves {
+                base_reserve: amount(base_balance)?,
+                quote_reserve,
+                base_decimals: base_balance["uiTokenAmount"]["decimals"].as_u64()? as u8,
+                base_decimals_known: true,
+                fee_basis_points: 0,
+            },
+            quote_reserve_before,
+            base_token_program: Pubkey::from_str(base_balance["programId"].as_str()?).ok()?,
+            protocol_fee_recipient: *accounts.get(9)?,
+        })
+    }
+}
+
+/// The program's fee settings, read once. The layout is upgraded to
+/// `FeeProgram` once the fee program's config account for PumpSwap exists.
+pub async fn get_global_config(rpc_pool: Arc<RpcPool>) -> Result<PumpSwapGlobalConfig> {
+    let global_config = GLOBAL_CONFIG
+        .get_or_try_init(|| async {
+            let addresses = [global_config_pda(), fee_config_pda()];
+            let addresses = &addresses;
+            let accounts = rpc_pool
+                .read(|client| async move { client.get_multiple_accounts(addresses).await })
+                .await?;
+            let data = match accounts.first() {
+                Some(Some(account)) => &account.data,
+                _ => {
+                    return Err(anyhow!(
+                        "pump swap global config {} not found",
+                        addresses[0]
+                    ))
+                }
+            };
+            let mut global_config = PumpSwapGlobalConfig::decode(data)?;
+            if global_config.layout == PumpLayout::CreatorFee
+                && matches!(accounts.get(1), Some(Some(_)))
+            {
+                global_config.layout = PumpLayout::FeeProgram;
+            }
+            Ok(global_config)
+        })
+        .await?;
+    Ok(global_config.clone())
+}
+
+/// Finds the SOL-quoted pool of `mint`, preferring the one created by the
+/// pump.fun migration when there are several.
+pub async fn get_pool_by_mint(rpc_pool: Arc<RpcPool>, mint: &Pubkey) -> Result<PumpSwapPool> {
+    if let Some(pool) = POOLS.read().unwrap().get(mint) {
+        return Ok(pool.clone());
+    }
+    let program = pump_swap_program();
+    let filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            POOL_BASE_MINT_OFFSET,
+            &mint.to_bytes(),
+        )),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            POOL_QUOTE_MINT_OFFSET,
+            &spl_token::native_mint::ID.to_bytes(),
+        )),
+    ];
+    let accounts = rpc_pool
+        .read(|client| {
+            let config = RpcProgramAccountsConfig {
+                filters: Some(filters.clone()),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            };
+            async move {
+                client
+                    .get_program_accounts_with_config(&program, config)
+                    .await
+            }
+        })
+        .await?;
+
+    let pools: Vec<PumpSwapPool> = accounts
+        .iter()
+        .filter_map(|(address, account)| PumpSwapPool::decode(*address, &account.data).ok())
+        .collect();
+    let canonical_creator = pool_authority_pda(mint);
+    let pool = pools
+        .iter()
+        .find(|pool| pool.creator == canonical_creator)
+        .or_else(|| pools.first())
+        .cloned()
+        .ok_or_else(|| anyhow!("NotFoundPool: no pump swap pool for {}", mint))?;
+    POOLS.write().unwrap().insert(*mint, pool.clone());
+    Ok(pool)
+}
+
+/// Current token balances of the pool's vaults.
+pub async fn get_reserves(rpc_pool: &RpcPool, pool: &PumpSwapPool) -> Result<PoolReserves> {
+    let vaults = [pool.pool_base_token_account, pool.pool_quote_token_account];
+    let vaults = &vaults;
+    let accounts = rpc_pool
+        .read(|client| async move { client.get_multiple_accounts(vaults).await })
+        .await?;
+    let balance = |index: usize| -> Result<u64> {
+        let account = accounts
+            .get(index)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| anyhow!("pool vault {} not found", vaults[index]))?;
+        Ok(StateWithExtensions::<TokenAccount>::unpack(&account.data)?
+            .base
+            .amount)
+    };
+    Ok(PoolReserves {
+        base_reserve: balance(0)?,
+        quote_reserve: balance(1)?,
+        ..PoolReserves::default()
+    })
+}
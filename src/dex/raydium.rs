@@ -1,40 +1,48 @@
 use crate::{
-    core::{
-        token::{get_account_info, get_associated_token_address, get_mint_info},
-        tx,
+    core::token::get_token_program,
+    dex::{
+        executor::{
+            slippage_bps, swap_amount, swap_logger, swap_token_accounts, wsol_swap_instructions,
+            DexExecutor, SwapBound, SwapQuote, BPS_DENOMINATOR,
+        },
+        raydium_clmm::{
+            self, ClmmPool, ClmmRoute, RaydiumClmm, CLMM_PROGRAM, CLMM_SWAP_DISCRIMINATOR,
+            CLMM_SWAP_V2_DISCRIMINATOR,
+        },
+        raydium_common,
+        raydium_cpmm::{
+            self, CpmmPool, CpmmRoute, RaydiumCpmm, CPMM_PROGRAM,
+            CPMM_SWAP_BASE_INPUT_DISCRIMINATOR, CPMM_SWAP_BASE_OUTPUT_DISCRIMINATOR,
+        },
     },
-    engine::swap::{SwapDirection, SwapInType},
+    engine::swap::SwapDirection,
+    services::rpc_pool::RpcPool,
     utils::{config::SwapConfig, logger::Logger},
 };
 use amm_cli::AmmSwapInfoResult;
 use anyhow::{anyhow, Context, Result};
-use bincode::Options;
 use bytemuck;
 use raydium_amm::state::{AmmInfo, Loadable};
 use serde::Deserialize;
-use serde::Serialize;
-use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use serde_json::Value;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
 use solana_sdk::{
-    instruction::Instruction, message::VersionedMessage, program_pack::Pack, pubkey::Pubkey,
-    signature::Keypair, signer::Signer, system_instruction, transaction::VersionedTransaction,
+    bs58, instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer,
 };
-use spl_associated_token_account::instruction::create_associated_token_account;
-use spl_token::{amount_to_ui_amount, state::Account, ui_amount_to_amount};
-use spl_token_client::token::TokenError;
-use std::{str::FromStr, sync::Arc, time::Duration};
+use std::{str::FromStr, sync::Arc};
 use tokio::time::Instant;
 
 pub const AMM_PROGRAM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
 pub const RAYDIUM_AUTHORITY_V4: &str = "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1";
-
-#[derive(Serialize)]
-struct SwapRequest {
-    quote_response: serde_json::Value, // You may deserialize it into a specific struct if known
-    user_public_key: String,
-    wrap_and_unwrap_sol: bool,
-    dynamic_compute_unit_limit: bool,
-    prioritization_fee_lamports: u64,
-}
+// swap_base_in, swap_base_out and their v2 forms without the market accounts
+const AMM_SWAP_INSTRUCTIONS: [u8; 4] = [9, 11, 16, 17];
+// pools per page of the Raydium API, and the most pages read for one pair
+const API_PAGE_SIZE: usize = 100;
+const API_MAX_PAGES: u32 = 10;
 
 #[derive(Debug, Deserialize)]
 pub struct PoolInfo {
@@ -46,6 +54,8 @@ pub struct PoolInfo {
 pub struct PoolData {
     // pub count: u32,
     pub data: Vec<Pool>,
+    #[serde(rename = "hasNextPage", default)]
+    pub has_next_page: bool,
 }
 
 impl PoolData {
@@ -63,8 +73,9 @@ pub struct Pool {
     pub mint_a: Mint,
     #[serde(rename = "mintB")]
     pub mint_b: Mint,
-    #[serde(rename = "marketId")]
-    pub market_id: String,
+    // AMM v4 only
+    #[serde(rename = "marketId", default)]
+    pub market_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -75,987 +86,407 @@ pub struct Mint {
     pub decimals: u8,
 }
 
+#[derive(Clone)]
 pub struct Raydium {
-    pub rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    pub rpc_pool: Arc<RpcPool>,
+    // only for `amm_cli`, which has no async API; used from `spawn_blocking`
     pub rpc_client: Option<Arc<solana_client::rpc_client::RpcClient>>,
     pub keypair: Arc<Keypair>,
     pub pool_id: Option<String>,
 }
 
+/// Program of a Raydium pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaydiumPoolKind {
+    AmmV4,
+    Cpmm,
+    Clmm,
+}
+
+impl RaydiumPoolKind {
+    pub fn from_program(program_id: &str) -> Option<Self> {
+        match program_id {
+            AMM_PROGRAM => Some(RaydiumPoolKind::AmmV4),
+            CPMM_PROGRAM => Some(RaydiumPoolKind::Cpmm),
+            CLMM_PROGRAM => Some(RaydiumPoolKind::Clmm),
+            _ => None,
+        }
+    }
+}
+
+/// Pool and coin of a target's Raydium swap.
+#[derive(Clone, Debug)]
+pub struct RaydiumTrade {
+    pub kind: RaydiumPoolKind,
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    /// WSOL held by the pool side of the swap before and after it.
+    pub sol_reserve_before: u64,
+    pub sol_reserve_after: u64,
+}
+
+impl RaydiumTrade {
+    /// Decodes a SOL-paired AMM v4, CPMM or CLMM swap from a
+    /// `transactionSubscribe` notification. Returns `None` when the
+    /// transaction has none.
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let transaction = &json["params"]["result"]["transaction"];
+        let meta = &transaction["meta"];
+
+        // the swap may be a top-level instruction or a CPI from a router
+        let outer = transaction["transaction"]["message"]["instructions"]
+            .as_array()
+            .into_iter()
+            .flatten();
+        let inner = meta["innerInstructions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|inner| inner["instructions"].as_array())
+            .flatten();
+        let (kind, pool) = outer.chain(inner).find_map(|ix| {
+            let kind = RaydiumPoolKind::from_program(ix["programId"].as_str()?)?;
+            let data = bs58::decode(ix["data"].as_str()?).into_vec().ok()?;
+            let is_swap = match kind {
+                RaydiumPoolKind::AmmV4 => AMM_SWAP_INSTRUCTIONS.contains(data.first()?),
+                RaydiumPoolKind::Cpmm => {
+                    let discriminator = data.get(..8)?;
+                    discriminator == CPMM_SWAP_BASE_INPUT_DISCRIMINATOR
+                        || discriminator == CPMM_SWAP_BASE_OUTPUT_DISCRIMINATOR
+                }
+                RaydiumPoolKind::Clmm => {
+                    let discriminator = data.get(..8)?;
+                    discriminator == CLMM_SWAP_DISCRIMINATOR
+                        || discriminator == CLMM_SWAP_V2_DISCRIMINATOR
+                }
+            };
+            if !is_swap {
+                return None;
+            }
+            // the pool follows the token program on AMM v4, the payer and
+            // amm config on CLMM, and the payer, authority and config on CPMM
+            let pool_index = match kind {
+                RaydiumPoolKind::AmmV4 => 1,
+                RaydiumPoolKind::Cpmm => 3,
+                RaydiumPoolKind::Clmm => 2,
+            };
+            let pool =
+                Pubkey::from_str(ix["accounts"].as_array()?.get(pool_index)?.as_str()?).ok()?;
+            Some((kind, pool))
+        })?;
+
+        // the coin is the only other mint a single-hop SOL swap moves
+        let native_mint = spl_token::native_mint::ID.to_string();
+        let mint = meta["postTokenBalances"]
+            .as_array()?
+            .iter()
+            .filter_map(|balance| balance["mint"].as_str())
+            .find(|mint| *mint != native_mint)?;
+
+        // the pool's wsol vault is the wsol account not owned by the signer
+        let signer = transaction["transaction"]["message"]["accountKeys"]
+            .as_array()?
+            .iter()
+            .find(|key| key["signer"].as_bool().unwrap_or(false))?["pubkey"]
+            .as_str()?;
+        let wsol_amount = |balances: &Value, account_index: u64| {
+            balances
+                .as_array()?
+                .iter()
+                .find(|b| b["accountIndex"].as_u64() == Some(account_index))?["uiTokenAmount"]
+                ["amount"]
+                .as_str()?
+                .parse::<u64>()
+                .ok()
+        };
+        let (sol_reserve_before, sol_reserve_after) = meta["postTokenBalances"]
+            .as_array()?
+            .iter()
+            .filter(|b| b["mint"] == native_mint.as_str() && b["owner"] != signer)
+            .find_map(|b| {
+                let account_index = b["accountIndex"].as_u64()?;
+                Some((
+                    wsol_amount(&meta["preTokenBalances"], account_index)?,
+                    wsol_amount(&meta["postTokenBalances"], account_index)?,
+                ))
+            })?;
+
+        Some(Self {
+            kind,
+            pool,
+            mint: Pubkey::from_str(mint).ok()?,
+            sol_reserve_before,
+            sol_reserve_after,
+        })
+    }
+}
+
+/// Accounts of a quoted swap on one of the Raydium programs.
+pub enum RaydiumRoute {
+    AmmV4(AmmSwapInfoResult),
+    Cpmm(CpmmRoute),
+    Clmm(ClmmRoute),
+}
+
 impl Raydium {
     pub fn new(
-        rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+        rpc_pool: Arc<RpcPool>,
         rpc_client: Arc<solana_client::rpc_client::RpcClient>,
         keypair: Arc<Keypair>,
     ) -> Self {
         Self {
-            rpc_nonblocking_client,
+            rpc_pool,
             keypair,
             rpc_client: Some(rpc_client),
             pool_id: None,
         }
     }
 
-    pub async fn swap_jupiter(
+    /// Runs `amm_cli::calculate_swap_info` on the blocking pool so pool and
+    /// market reads don't stall the runtime.
+    async fn calculate_swap_info(
         &self,
-        swap_config: SwapConfig,
-        mint: String,
-        start_time: Instant,
-        jito_url: String,
-        jito_tip_amount: f64,
-    ) -> Result<Vec<String>> {
-        let logger = Logger::new(format!(
-            "[SWAP IN JUPITER]({}:{:?}) => ",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed()
-        ));
-
-        let mut url = "".to_string();
-        let input_mint = "So11111111111111111111111111111111111111112";
-        let base_mint = &mint;
-        let wallet = self.keypair.as_ref();
-        if swap_config.swap_direction == SwapDirection::Buy {
-            let buy_amount = swap_config.amount_in * 1_000_000_000_f64;
-            url = format!(
-              "https://ultra-api.jup.ag/order?inputMint={}&outputMint={}&amount={}&slippageBps=10000",
-              input_mint,
-              base_mint,
-              buy_amount
-          );
-        } else {
-            let mint_pubkey = Pubkey::from_str(base_mint).unwrap();
-            let token_account = spl_associated_token_account::get_associated_token_address(
-                &wallet.pubkey(),
-                &mint_pubkey,
-            );
-            if let Ok(token_account_info) = get_account_info(
-                self.rpc_nonblocking_client.clone(),
-                mint_pubkey,
-                token_account,
+        amm_pool_id: Pubkey,
+        user_input_token: Pubkey,
+        amount_specified: u64,
+        slippage_bps: u64,
+        swap_base_in: bool,
+    ) -> Result<AmmSwapInfoResult> {
+        let client = self
+            .rpc_client
+            .clone()
+            .context("failed to get rpc client")?;
+        let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
+        let swap_info_result = tokio::task::spawn_blocking(move || {
+            amm_cli::calculate_swap_info(
+                &client,
+                amm_program,
+                amm_pool_id,
+                user_input_token,
+                amount_specified,
+                slippage_bps,
+                swap_base_in,
             )
-            .await
-            {
-                let in_mint = get_mint_info(
-                    self.rpc_nonblocking_client.clone(),
-                    self.keypair.clone(),
-                    mint_pubkey,
-                )
-                .await?;
-                let amount = match swap_config.in_type {
-                    SwapInType::Qty => {
-                        ui_amount_to_amount(swap_config.amount_in, in_mint.base.decimals)
-                    }
-                    SwapInType::Pct => {
-                        let amount_in_pct = swap_config.amount_in.min(1.0);
-                        if amount_in_pct == 1.0 {
-                            logger.log(format!(
-                                "Sell all. will be close ATA for mint {}",
-                                mint_pubkey
-                            ));
-                            token_account_info.base.amount
-                        } else {
-                            (((amount_in_pct * 100.0) as u64) * token_account_info.base.amount)
-                                / 100
-                        }
-                    }
-                };
-                url = format!(
-                  "https://ultra-api.jup.ag/order?inputMint={}&outputMint={}&amount={}&slippageBps=10000",
-                  base_mint,
-                  input_mint,
-                  amount
-              );
-            };
-        }
-        logger.log(format!("Requesting the URL: {}", url));
-        let client = reqwest::Client::new();
-        // Send the GET request
-        if let Ok(response) = reqwest::get(&url).await {
-            if response.status().is_success() {
-                logger.log("Response status is success!".to_string());
-                let json: serde_json::Value = response.json().await.unwrap_or_default();
-                let swap_request = SwapRequest {
-                    quote_response: json,
-                    user_public_key: wallet.pubkey().to_string(),
-                    wrap_and_unwrap_sol: true,
-                    dynamic_compute_unit_limit: true,
-                    prioritization_fee_lamports: 52000,
-                };
-                if let Ok(response) = client
-                    .post("https://quote-api.jup.ag/v6/swap")
-                    .header("Content-Type", "application/json")
-                    .json(&swap_request)
-                    .send()
-                    .await
-                {
-                    let res: serde_json::Value = response.json().await.unwrap();
-                    let tx = base64::decode(
-                        res["swapTransaction"]
-                            .as_str()
-                            .unwrap_or("default")
-                            .to_string(),
-                    )
-                    .unwrap_or_default();
-                    if let Ok(transaction) = bincode::options()
-                        .with_fixint_encoding()
-                        .reject_trailing_bytes()
-                        .deserialize::<VersionedTransaction>(&tx)
-                    {
-                        let signed_tx =
-                            VersionedTransaction::try_new(transaction.message.clone(), &[wallet])
-                                .unwrap_or_default();
-                        let recent_blockhash =
-                            VersionedMessage::recent_blockhash(&transaction.message);
-                        let txn = tx::jito_confirm(
-                            jito_url,
-                            jito_tip_amount,
-                            &self.rpc_client.clone().unwrap(),
-                            wallet,
-                            signed_tx,
-                            recent_blockhash,
-                            &logger,
-                        )
-                        .await;
-                        return txn;
-                    };
-                }
-            }
-        }
-        Err(anyhow::format_err!("Not tradable token."))
+        })
+        .await??;
+        Ok(swap_info_result)
     }
 
-    pub async fn swap_by_mint(
+    /// Swaps on the given AMM v4 pool.
+    pub async fn swap(
         &self,
-        mint_str: &str,
         swap_config: SwapConfig,
+        amm_pool_id: Pubkey,
+        pool_state: AmmInfo,
         start_time: Instant,
         jito_url: String,
         jito_tip_amount: f64,
     ) -> Result<Vec<String>> {
-        let logger = Logger::new(format!(
-            "[SWAP IN RAYDIUM BY MINT]({}:{:?}) => ",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed()
-        ));
-        let slippage_bps = swap_config.slippage * 100;
-        let owner = self.keypair.pubkey();
-        let program_id = spl_token::ID;
-        let native_mint = spl_token::native_mint::ID;
-        let (amm_pool_id, pool_state) = get_pool_state(
-            self.rpc_client.clone().unwrap(),
-            self.pool_id.as_deref(),
-            Some(mint_str),
-            &logger,
-        )
-        .await?;
-        let mint = Pubkey::from_str(mint_str)
-            .map_err(|e| logger.log(format!("Failed to parse mint pubkey: {}", e)))
-            .unwrap();
-
-        let (token_in, token_out, user_input_token, swap_base_in) = match (
-            swap_config.swap_direction.clone(),
-            pool_state.coin_vault_mint == native_mint,
-        ) {
-            (SwapDirection::Buy, true) => (native_mint, mint, pool_state.coin_vault, true),
-            (SwapDirection::Buy, false) => (native_mint, mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, true) => (mint, native_mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, false) => (mint, native_mint, pool_state.coin_vault, true),
-        };
-
-        // logger.log(format!(
-        //     "({}:{:?}): token_in:{}, token_out:{}, user_input_token:{}, swap_base_in:{}",
-        //     chrono::Utc::now().timestamp(),
-        //     start_time.elapsed(),
-        //     token_in,
-        //     token_out,
-        //     user_input_token,
-        //     swap_base_in
-        // ));
-
-        let in_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_in,
-            &owner,
-        );
-        let out_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_out,
-            &owner,
-        );
-
-        let mut create_instruction = None;
-        let mut close_instruction = None;
-
-        let (amount_specified, _amount_ui_pretty) = match swap_config.swap_direction {
-            SwapDirection::Buy => {
-                // Create base ATA if it doesn't exist.
-                match get_account_info(self.rpc_nonblocking_client.clone(), token_out, out_ata)
-                    .await
-                {
-                    Ok(_) => {
-                        // logger.log("Base ata exists. skipping creation..".to_string());
-                    }
-                    Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                        // logger.log(format!(
-                        //     "({}:{:?}): Base ATA for mint {} does not exist. will be create",
-                        //     chrono::Utc::now().timestamp(),
-                        //     start_time.elapsed(),
-                        //     token_out
-                        // ));
-                        create_instruction = Some(create_associated_token_account(
-                            &owner,
-                            &owner,
-                            &token_out,
-                            &program_id,
-                        ));
-                    }
-                    Err(_) => {
-                        // logger.log(format!("Error retrieving out ATA: {}", error));
-                    }
-                }
-
-                (
-                    ui_amount_to_amount(swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                    (swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                )
-            }
-            SwapDirection::Sell => {
-                let in_account =
-                    get_account_info(self.rpc_nonblocking_client.clone(), token_in, in_ata).await?;
-                let in_mint = get_mint_info(
-                    self.rpc_nonblocking_client.clone(),
-                    self.keypair.clone(),
-                    token_in,
-                )
-                .await?;
-
-                let amount = match swap_config.in_type {
-                    SwapInType::Qty => {
-                        ui_amount_to_amount(swap_config.amount_in, in_mint.base.decimals)
-                    }
-                    SwapInType::Pct => {
-                        let amount_in_pct = swap_config.amount_in.min(1.0);
-                        if amount_in_pct == 1.0 {
-                            // logger
-                            //     .log(format!("Sell all. will be close ATA for mint {}", token_in));
-                            close_instruction = Some(spl_token::instruction::close_account(
-                                &program_id,
-                                &in_ata,
-                                &owner,
-                                &owner,
-                                &[&owner],
-                            )?);
-                            in_account.base.amount
-                        } else {
-                            (((amount_in_pct * 100.0) as u64) * in_account.base.amount) / 100
-                        }
-                    }
-                };
-                (
-                    amount,
-                    (
-                        amount_to_ui_amount(amount, in_mint.base.decimals),
-                        in_mint.base.decimals,
-                    ),
-                )
-            }
-        };
-
-        let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
-        let client = self.rpc_client.clone().unwrap();
-        let swap_info_result = amm_cli::calculate_swap_info(
-            &client,
-            amm_program,
-            amm_pool_id,
-            user_input_token,
-            amount_specified,
-            slippage_bps,
-            swap_base_in,
-        )?;
-        let other_amount_threshold = swap_info_result.other_amount_threshold;
-
-        // logger.log(format!(
-        //     "[swap_info_result]({}): {:#?}",
-        //     chrono::Utc::now().timestamp(),
-        //     swap_info_result
-        // ));
-        // logger.log(format!(
-        //     "swap({}:{:?}): {}, value: {:?} -> {}",
-        //     chrono::Utc::now().timestamp(),
-        //     start_time.elapsed(),
-        //     token_in,
-        //     _amount_ui_pretty,
-        //     token_out
-        // ));
-
-        // build instructions
-        let mut instructions = vec![];
-        // sol <-> wsol support
-        let mut wsol_account = None;
-        if token_in == native_mint || token_out == native_mint {
-            // create wsol account
-            let seed = &format!("{}", Keypair::new().pubkey())[..32];
-            let wsol_pubkey = Pubkey::create_with_seed(&owner, seed, &spl_token::id())?;
-            wsol_account = Some(wsol_pubkey);
-
-            // LAMPORTS_PER_SOL / 100 // 0.01 SOL as rent
-            // get rent
-            let rent = self
-                .rpc_nonblocking_client
-                .get_minimum_balance_for_rent_exemption(Account::LEN)
-                .await?;
-            // if buy add amount_specified
-            let total_amount = if token_in == native_mint {
-                rent + amount_specified
-            } else {
-                rent
-            };
-            // create tmp wsol account
-            instructions.push(system_instruction::create_account_with_seed(
-                &owner,
-                &wsol_pubkey,
-                &owner,
-                seed,
-                total_amount,
-                Account::LEN as u64, // 165, // Token account size
-                &spl_token::id(),
-            ));
-
-            // initialize account
-            instructions.push(spl_token::instruction::initialize_account(
-                &spl_token::id(),
-                &wsol_pubkey,
-                &native_mint,
-                &owner,
-            )?);
-        }
-
-        if let Some(create_instruction) = create_instruction {
-            instructions.push(create_instruction);
-        }
-        if amount_specified > 0 {
-            let mut close_wsol_account_instruction = None;
-            // replace native mint with tmp wsol account
-            let mut final_in_ata = in_ata;
-            let mut final_out_ata = out_ata;
-
-            if let Some(wsol_account) = wsol_account {
-                match swap_config.swap_direction {
-                    SwapDirection::Buy => {
-                        final_in_ata = wsol_account;
-                    }
-                    SwapDirection::Sell => {
-                        final_out_ata = wsol_account;
-                    }
-                }
-                close_wsol_account_instruction = Some(spl_token::instruction::close_account(
-                    &program_id,
-                    &wsol_account,
-                    &owner,
-                    &owner,
-                    &[&owner],
-                )?);
-            }
-
-            // build swap instruction
-            let build_swap_instruction = amm_swap(
-                &amm_program,
-                swap_info_result,
-                &owner,
-                &final_in_ata,
-                &final_out_ata,
-                amount_specified,
-                other_amount_threshold,
-                swap_base_in,
-            )?;
-            // logger.log({
-            //     format!(
-            //         "({}:{:?}) => amount_specified: {}, other_amount_threshold: {}, wsol_account: {:?}",
-            //         chrono::Utc::now().timestamp(), start_time.elapsed(), amount_specified, other_amount_threshold, wsol_account
-            //     )
-            // });
-            if swap_config.swap_direction == SwapDirection::Buy
-                && start_time.elapsed() > Duration::from_millis(700)
-            {
-                return Err(anyhow!("Long RPC Connection with Pool State."));
-            }
-            instructions.push(build_swap_instruction);
-            // close wsol account
-            if let Some(close_wsol_account_instruction) = close_wsol_account_instruction {
-                instructions.push(close_wsol_account_instruction);
-            }
-        }
-        if let Some(close_instruction) = close_instruction {
-            instructions.push(close_instruction);
-        }
-        if instructions.is_empty() {
-            return Err(anyhow!("instructions is empty, no tx required"));
-        }
-        logger.log(format!("sending tx: {:?}", start_time.elapsed()));
-        tx::new_signed_and_send(
+        let logger = swap_logger(self.venue(), start_time);
+        let quote = self
+            .quote_pool(
+                RaydiumPool::AmmV4 {
+                    id: amm_pool_id,
+                    state: pool_state,
+                },
+                &swap_config,
+                &logger,
+            )
+            .await?;
+        self.execute_quote(
+            quote,
+            swap_config,
+            start_time,
             jito_url,
             jito_tip_amount,
-            &client,
-            &self.keypair,
-            instructions,
-            swap_config.use_jito,
             &logger,
         )
         .await
     }
 
-    pub async fn swap(
+    pub async fn quote_pool(
+        &self,
+        pool: RaydiumPool,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<RaydiumRoute>> {
+        match pool {
+            RaydiumPool::AmmV4 { id, state } => Ok(self
+                .quote_amm_v4(id, state, swap_config, logger)
+                .await?
+                .map_route(RaydiumRoute::AmmV4)),
+            RaydiumPool::Cpmm(pool) => Ok(self
+                .cpmm()
+                .quote_pool(pool, swap_config, logger)
+                .await?
+                .map_route(RaydiumRoute::Cpmm)),
+            RaydiumPool::Clmm(pool) => Ok(self
+                .clmm()
+                .quote_pool(pool, swap_config, logger)
+                .await?
+                .map_route(RaydiumRoute::Clmm)),
+        }
+    }
+
+    async fn quote_amm_v4(
         &self,
-        swap_config: SwapConfig,
         amm_pool_id: Pubkey,
         pool_state: AmmInfo,
-        start_time: Instant,
-        jito_url: String,
-        jito_tip_amount: f64,
-    ) -> Result<Vec<String>> {
-        let logger = Logger::new(format!(
-            "[SWAP IN RAYDIUM]({}:{:?}) => ",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed()
-        ));
-        let slippage_bps = swap_config.slippage * 100;
-        let owner = self.keypair.pubkey();
-        let program_id = spl_token::ID;
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<AmmSwapInfoResult>> {
+        let slippage_bps = slippage_bps(swap_config);
         let native_mint = spl_token::native_mint::ID;
-        let mint = if native_mint != pool_state.coin_vault_mint {
-            pool_state.coin_vault_mint
+        let sol_is_coin = pool_state.coin_vault_mint == native_mint;
+        let (mint, decimals) = if sol_is_coin {
+            (pool_state.pc_vault_mint, pool_state.pc_decimals)
         } else {
-            pool_state.pc_vault_mint
+            (pool_state.coin_vault_mint, pool_state.coin_decimals)
         };
-
-        let (token_in, token_out, user_input_token, swap_base_in) = match (
-            swap_config.swap_direction.clone(),
-            pool_state.coin_vault_mint == native_mint,
-        ) {
-            (SwapDirection::Buy, true) => (native_mint, mint, pool_state.coin_vault, true),
-            (SwapDirection::Buy, false) => (native_mint, mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, true) => (mint, native_mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, false) => (mint, native_mint, pool_state.coin_vault, true),
+        // amm_cli wants the pool vault of the input side
+        let user_input_token = match (&swap_config.swap_direction, sol_is_coin) {
+            (SwapDirection::Buy, true) | (SwapDirection::Sell, false) => pool_state.coin_vault,
+            (SwapDirection::Buy, false) | (SwapDirection::Sell, true) => pool_state.pc_vault,
         };
+        // the mint may be owned by Token-2022, wsol always belongs to SPL Token
+        let program_id = get_token_program(self.rpc_pool.clone(), mint).await?;
 
-        logger.log(format!(
-            "({}:{:?}): token_in:{}, token_out:{}, user_input_token:{}, swap_base_in:{}",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed(),
-            token_in,
-            token_out,
-            user_input_token,
-            swap_base_in
-        ));
-
-        let in_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_in,
-            &owner,
-        );
-        let out_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_out,
-            &owner,
-        );
-
-        let mut create_instruction = None;
-        let mut close_instruction = None;
-
-        let (amount_specified, _amount_ui_pretty) = match swap_config.swap_direction {
-            SwapDirection::Buy => {
-                // Create base ATA if it doesn't exist.
-                match get_account_info(self.rpc_nonblocking_client.clone(), token_out, out_ata)
-                    .await
-                {
-                    Ok(_) => {
-                        logger.log("Base ata exists. skipping creation..".to_string());
-                    }
-                    Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                        logger.log(format!(
-                            "({}:{:?}): Base ATA for mint {} does not exist. will be create",
-                            chrono::Utc::now().timestamp(),
-                            start_time.elapsed(),
-                            token_out
-                        ));
-                        create_instruction = Some(create_associated_token_account(
-                            &owner,
-                            &owner,
-                            &token_out,
-                            &program_id,
-                        ));
-                    }
-                    Err(error) => {
-                        logger.log(format!("Error retrieving out ATA: {}", error));
-                    }
-                }
-
-                (
-                    ui_amount_to_amount(swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                    (swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                )
-            }
-            SwapDirection::Sell => {
-                let in_account =
-                    get_account_info(self.rpc_nonblocking_client.clone(), token_in, in_ata).await?;
-                let in_mint = get_mint_info(
-                    self.rpc_nonblocking_client.clone(),
-                    self.keypair.clone(),
-                    token_in,
-                )
-                .await?;
-
-                let amount = match swap_config.in_type {
-                    SwapInType::Qty => {
-                        ui_amount_to_amount(swap_config.amount_in, in_mint.base.decimals)
-                    }
-                    SwapInType::Pct => {
-                        let amount_in_pct = swap_config.amount_in.min(1.0);
-                        if amount_in_pct == 1.0 {
-                            logger
-                                .log(format!("Sell all. will be close ATA for mint {}", token_in));
-                            close_instruction = Some(spl_token::instruction::close_account(
-                                &program_id,
-                                &in_ata,
-                                &owner,
-                                &owner,
-                                &[&owner],
-                            )?);
-                            in_account.base.amount
-                        } else {
-                            (((amount_in_pct * 100.0) as u64) * in_account.base.amount) / 100
-                        }
-                    }
-                };
-                (
-                    amount,
-                    (
-                        amount_to_ui_amount(amount, in_mint.base.decimals),
-                        in_mint.base.decimals,
-                    ),
-                )
-            }
-        };
-
-        let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
-        let client = self.rpc_client.clone().unwrap();
-        let swap_info_result = amm_cli::calculate_swap_info(
-            &client,
-            amm_program,
-            amm_pool_id,
-            user_input_token,
-            amount_specified,
-            slippage_bps,
-            swap_base_in,
-        )?;
+        let swap_amount = swap_amount(
+            &self.rpc_pool,
+            &self.keypair,
+            mint,
+            program_id,
+            Some(decimals as u8),
+            swap_config,
+        )
+        .await?;
+        let swap_info_result = self
+            .calculate_swap_info(
+                amm_pool_id,
+                user_input_token,
+                // the pool only receives what is left after the transfer fee
+                swap_amount.amount - swap_amount.transfer_fee,
+                slippage_bps,
+                true,
+            )
+            .await?;
         let other_amount_threshold = swap_info_result.other_amount_threshold;
-
-        // logger.log(format!(
-        //     "[swap_info_result]({}): {:#?}",
-        //     chrono::Utc::now().timestamp(),
-        //     swap_info_result
-        // ));
-        logger.log(format!(
-            "swap({}:{:?}): {}, value: {:?} -> {}",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed(),
-            token_in,
-            _amount_ui_pretty,
-            token_out
+        // amm_cli only reports the bound, which is the expected output less slippage
+        let amount_out = (other_amount_threshold as u128 * BPS_DENOMINATOR as u128)
+            .checked_div(BPS_DENOMINATOR.saturating_sub(slippage_bps) as u128)
+            .unwrap_or(other_amount_threshold as u128) as u64;
+        logger.debug(format!(
+            "amm v4 {}: amount_in: {}, other_amount_threshold: {}",
+            amm_pool_id, swap_amount.amount, other_amount_threshold
         ));
 
-        // build instructions
-        let mut instructions = vec![];
-        // sol <-> wsol support
-        let mut wsol_account = None;
-        if token_in == native_mint || token_out == native_mint {
-            // create wsol account
-            let seed = &format!("{}", Keypair::new().pubkey())[..32];
-            let wsol_pubkey = Pubkey::create_with_seed(&owner, seed, &spl_token::id())?;
-            wsol_account = Some(wsol_pubkey);
-
-            // LAMPORTS_PER_SOL / 100 // 0.01 SOL as rent
-            // get rent
-            let rent = self
-                .rpc_nonblocking_client
-                .get_minimum_balance_for_rent_exemption(Account::LEN)
-                .await?;
-            // if buy add amount_specified
-            let total_amount = if token_in == native_mint {
-                rent + amount_specified
-            } else {
-                rent
-            };
-            // create tmp wsol account
-            instructions.push(system_instruction::create_account_with_seed(
-                &owner,
-                &wsol_pubkey,
-                &owner,
-                seed,
-                total_amount,
-                Account::LEN as u64, // 165, // Token account size
-                &spl_token::id(),
-            ));
-
-            // initialize account
-            instructions.push(spl_token::instruction::initialize_account(
-                &spl_token::id(),
-                &wsol_pubkey,
-                &native_mint,
-                &owner,
-            )?);
-        }
-
-        if let Some(create_instruction) = create_instruction {
-            instructions.push(create_instruction);
-        }
-        if amount_specified > 0 {
-            let mut close_wsol_account_instruction = None;
-            // replace native mint with tmp wsol account
-            let mut final_in_ata = in_ata;
-            let mut final_out_ata = out_ata;
-
-            if let Some(wsol_account) = wsol_account {
-                match swap_config.swap_direction {
-                    SwapDirection::Buy => {
-                        final_in_ata = wsol_account;
-                    }
-                    SwapDirection::Sell => {
-                        final_out_ata = wsol_account;
-                    }
-                }
-                close_wsol_account_instruction = Some(spl_token::instruction::close_account(
-                    &program_id,
-                    &wsol_account,
-                    &owner,
-                    &owner,
-                    &[&owner],
-                )?);
-            }
-
-            // build swap instruction
-            let build_swap_instruction = amm_swap(
-                &amm_program,
-                swap_info_result,
-                &owner,
-                &final_in_ata,
-                &final_out_ata,
-                amount_specified,
-                other_amount_threshold,
-                swap_base_in,
-            )?;
-            logger.log({
-              format!(
-                  "({}:{:?}) => amount_specified: {}, other_amount_threshold: {}, wsol_account: {:?}",
-                  chrono::Utc::now().timestamp(), start_time.elapsed(), amount_specified, other_amount_threshold, wsol_account
-              )
-          });
-            instructions.push(build_swap_instruction);
-            // close wsol account
-            if let Some(close_wsol_account_instruction) = close_wsol_account_instruction {
-                instructions.push(close_wsol_account_instruction);
-            }
-        }
-        if let Some(close_instruction) = close_instruction {
-            instructions.push(close_instruction);
-        }
-        if instructions.is_empty() {
-            return Err(anyhow!("instructions is empty, no tx required"));
-        }
-        tx::new_signed_and_send(
-            jito_url,
-            jito_tip_amount,
-            &client,
-            &self.keypair,
-            instructions,
-            swap_config.use_jito,
-            &logger,
-        )
-        .await
+        Ok(SwapQuote {
+            mint,
+            token_program: program_id,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound: SwapBound::MinAmountOut(other_amount_threshold),
+            close_instruction: swap_amount.close_instruction,
+            route: swap_info_result,
+        })
     }
 
-    pub async fn swap_test(
-        &self,
-        mint_str: &str,
-        swap_config: SwapConfig,
-        jito_url: String,
-        jito_tip_amount: f64,
-    ) -> Result<Vec<String>> {
-        let logger = Logger::new(format!(
-            "[SWAP IN RAYDIUM]({}) => ",
-            chrono::Utc::now().timestamp(),
-        ));
-        let start_time = Instant::now();
-        let slippage_bps = swap_config.slippage * 100;
-        let owner = self.keypair.pubkey();
-        let program_id = spl_token::ID;
-        let native_mint = spl_token::native_mint::ID;
-        let mint = Pubkey::from_str(mint_str)
-            .map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
-        let (amm_pool_id, pool_state) = get_pool_state(
-            self.rpc_client.clone().unwrap(),
-            self.pool_id.as_deref(),
-            Some(mint_str),
-            &logger,
-        )
-        .await?;
-
-        let (token_in, token_out, user_input_token, swap_base_in) = match (
-            swap_config.swap_direction.clone(),
-            pool_state.coin_vault_mint == native_mint,
-        ) {
-            (SwapDirection::Buy, true) => (native_mint, mint, pool_state.coin_vault, true),
-            (SwapDirection::Buy, false) => (native_mint, mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, true) => (mint, native_mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, false) => (mint, native_mint, pool_state.coin_vault, true),
-        };
+    fn cpmm(&self) -> RaydiumCpmm {
+        RaydiumCpmm::new(self.rpc_pool.clone(), self.keypair.clone())
+    }
 
-        logger.log(format!(
-            "({}:{:?}): token_in:{}, token_out:{}, user_input_token:{}, swap_base_in:{}",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed(),
-            token_in,
-            token_out,
-            user_input_token,
-            swap_base_in
-        ));
+    fn clmm(&self) -> RaydiumClmm {
+        RaydiumClmm::new(self.rpc_pool.clone(), self.keypair.clone())
+    }
+}
 
-        let in_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_in,
-            &owner,
-        );
-        let out_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_out,
-            &owner,
-        );
+impl DexExecutor for Raydium {
+    type Route = RaydiumRoute;
+    type Trade = RaydiumTrade;
 
-        let mut create_instruction = None;
-        let mut close_instruction = None;
+    fn venue(&self) -> &'static str {
+        "RAYDIUM"
+    }
 
-        let (amount_specified, _amount_ui_pretty) = match swap_config.swap_direction {
-            SwapDirection::Buy => {
-                // Create base ATA if it doesn't exist.
-                match get_account_info(self.rpc_nonblocking_client.clone(), token_out, out_ata)
-                    .await
-                {
-                    Ok(_) => {
-                        logger.log("Base ata exists. skipping creation..".to_string());
-                    }
-                    Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                        logger.log(format!(
-                            "({}:{:?}): Base ATA for mint {} does not exist. will be create",
-                            chrono::Utc::now().timestamp(),
-                            start_time.elapsed(),
-                            token_out
-                        ));
-                        create_instruction = Some(create_associated_token_account(
-                            &owner,
-                            &owner,
-                            &token_out,
-                            &program_id,
-                        ));
-                    }
-                    Err(error) => {
-                        logger.log(format!("Error retrieving out ATA: {}", error));
-                    }
-                }
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
+    }
 
-                (
-                    ui_amount_to_amount(swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                    (swap_config.amount_in, spl_token::native_mint::DECIMALS),
-                )
-            }
-            SwapDirection::Sell => {
-                let in_account =
-                    get_account_info(self.rpc_nonblocking_client.clone(), token_in, in_ata).await?;
-                let in_mint = get_mint_info(
-                    self.rpc_nonblocking_client.clone(),
-                    self.keypair.clone(),
-                    token_in,
-                )
-                .await?;
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
 
-                let amount = match swap_config.in_type {
-                    SwapInType::Qty => {
-                        ui_amount_to_amount(swap_config.amount_in, in_mint.base.decimals)
-                    }
-                    SwapInType::Pct => {
-                        let amount_in_pct = swap_config.amount_in.min(1.0);
-                        if amount_in_pct == 1.0 {
-                            logger
-                                .log(format!("Sell all. will be close ATA for mint {}", token_in));
-                            close_instruction = Some(spl_token::instruction::close_account(
-                                &program_id,
-                                &in_ata,
-                                &owner,
-                                &owner,
-                                &[&owner],
-                            )?);
-                            in_account.base.amount
-                        } else {
-                            (((amount_in_pct * 100.0) as u64) * in_account.base.amount) / 100
-                        }
-                    }
-                };
-                (
-                    amount,
-                    (
-                        amount_to_ui_amount(amount, in_mint.base.decimals),
-                        in_mint.base.decimals,
-                    ),
-                )
+    /// Quotes on the pinned AMM v4 pool, or else on the deepest SOL pool of
+    /// any Raydium program.
+    async fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<RaydiumRoute>> {
+        let pool = match self.pool_id.as_deref() {
+            Some(pool_id) => {
+                let (id, state) =
+                    get_pool_state(self.rpc_pool.clone(), Some(pool_id), None, logger).await?;
+                RaydiumPool::AmmV4 { id, state }
             }
+            None => find_deepest_pool(self.rpc_pool.clone(), &mint, logger).await?,
         };
+        self.quote_pool(pool, swap_config, logger).await
+    }
 
-        let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
-        let client = self.rpc_client.clone().unwrap();
-        let swap_info_result = amm_cli::calculate_swap_info(
-            &client,
-            amm_program,
-            amm_pool_id,
-            user_input_token,
-            amount_specified,
-            slippage_bps,
-            swap_base_in,
-        )?;
-        let other_amount_threshold = swap_info_result.other_amount_threshold;
-
-        // logger.log(format!(
-        //     "[swap_info_result]({}): {:#?}",
-        //     chrono::Utc::now().timestamp(),
-        //     swap_info_result
-        // ));
-        logger.log(format!(
-            "swap({}:{:?}): {}, value: {:?} -> {}",
-            chrono::Utc::now().timestamp(),
-            start_time.elapsed(),
-            token_in,
-            _amount_ui_pretty,
-            token_out
-        ));
-
-        // build instructions
-        let mut instructions = vec![];
-        // sol <-> wsol support
-        let mut wsol_account = None;
-        if token_in == native_mint || token_out == native_mint {
-            // create wsol account
-            let seed = &format!("{}", Keypair::new().pubkey())[..32];
-            let wsol_pubkey = Pubkey::create_with_seed(&owner, seed, &spl_token::id())?;
-            wsol_account = Some(wsol_pubkey);
-
-            // LAMPORTS_PER_SOL / 100 // 0.01 SOL as rent
-            // get rent
-            let rent = self
-                .rpc_nonblocking_client
-                .get_minimum_balance_for_rent_exemption(Account::LEN)
-                .await?;
-            // if buy add amount_specified
-            let total_amount = if token_in == native_mint {
-                rent + amount_specified
-            } else {
-                rent
-            };
-            // create tmp wsol account
-            instructions.push(system_instruction::create_account_with_seed(
-                &owner,
-                &wsol_pubkey,
-                &owner,
-                seed,
-                total_amount,
-                Account::LEN as u64, // 165, // Token account size
-                &spl_token::id(),
-            ));
-
-            // initialize account
-            instructions.push(spl_token::instruction::initialize_account(
-                &spl_token::id(),
-                &wsol_pubkey,
-                &native_mint,
-                &owner,
-            )?);
-        }
-
-        if let Some(create_instruction) = create_instruction {
-            instructions.push(create_instruction);
+    /// Quotes on the pool `trade` swapped on, or as `quote` does without one.
+    async fn quote_trade(
+        &self,
+        mint: Pubkey,
+        trade: Option<RaydiumTrade>,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<RaydiumRoute>> {
+        match trade {
+            Some(trade) => {
+                let pool = RaydiumPool::load(&self.rpc_pool, trade.kind, trade.pool).await?;
+                self.quote_pool(pool, swap_config, logger).await
+            }
+            None => self.quote(mint, swap_config, logger).await,
         }
-        if amount_specified > 0 {
-            let mut close_wsol_account_instruction = None;
-            // replace native mint with tmp wsol account
-            let mut final_in_ata = in_ata;
-            let mut final_out_ata = out_ata;
+    }
 
-            if let Some(wsol_account) = wsol_account {
-                match swap_config.swap_direction {
-                    SwapDirection::Buy => {
-                        final_in_ata = wsol_account;
-                    }
-                    SwapDirection::Sell => {
-                        final_out_ata = wsol_account;
-                    }
-                }
-                close_wsol_account_instruction = Some(spl_token::instruction::close_account(
-                    &program_id,
-                    &wsol_account,
-                    &owner,
+    fn build_instructions(&self, quote: &SwapQuote<RaydiumRoute>) -> Result<Vec<Instruction>> {
+        match &quote.route {
+            RaydiumRoute::AmmV4(swap_info_result) => {
+                let owner = self.keypair.pubkey();
+                let SwapBound::MinAmountOut(other_amount_threshold) = quote.bound else {
+                    return Err(anyhow!("amm v4 swaps are exact input"));
+                };
+                let (user_source, user_destination) = swap_token_accounts(&owner, quote);
+                let swap_instruction = amm_swap(
+                    &Pubkey::from_str(AMM_PROGRAM)?,
+                    swap_info_result,
                     &owner,
-                    &[&owner],
-                )?);
-            }
-
-            // build swap instruction
-            let build_swap_instruction = amm_swap(
-                &amm_program,
-                swap_info_result,
-                &owner,
-                &final_in_ata,
-                &final_out_ata,
-                amount_specified,
-                other_amount_threshold,
-                swap_base_in,
-            )?;
-            logger.log({
-              format!(
-                  "({}:{:?}) => amount_specified: {}, other_amount_threshold: {}, wsol_account: {:?}",
-                  chrono::Utc::now().timestamp(), start_time.elapsed(), amount_specified, other_amount_threshold, wsol_account
-              )
-          });
-            instructions.push(build_swap_instruction);
-            // close wsol account
-            if let Some(close_wsol_account_instruction) = close_wsol_account_instruction {
-                instructions.push(close_wsol_account_instruction);
+                    &user_source,
+                    &user_destination,
+                    quote.amount_in,
+                    other_amount_threshold,
+                    true,
+                )?;
+                wsol_swap_instructions(&owner, quote, swap_instruction)
             }
+            RaydiumRoute::Cpmm(route) => self
+                .cpmm()
+                .build_instructions(&quote.with_route(route.clone())),
+            RaydiumRoute::Clmm(route) => self
+                .clmm()
+                .build_instructions(&quote.with_route(route.clone())),
         }
-        if let Some(close_instruction) = close_instruction {
-            instructions.push(close_instruction);
-        }
-        if instructions.is_empty() {
-            return Err(anyhow!("instructions is empty, no tx required"));
-        }
-        tx::new_signed_and_send(
-            jito_url,
-            jito_tip_amount,
-            &client,
-            &self.keypair,
-            instructions,
-            swap_config.use_jito,
-            &logger,
-        )
-        .await
     }
 }
 
 pub fn amm_swap(
     amm_program: &Pubkey,
-    result: AmmSwapInfoResult,
+    result: &AmmSwapInfoResult,
     user_owner: &Pubkey,
     user_source: &Pubkey,
     user_destination: &Pubkey,
@@ -1113,7 +544,7 @@ pub fn amm_swap(
 }
 
 pub async fn get_pool_state(
-    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    rpc_pool: Arc<RpcPool>,
     pool_id: Option<&str>,
     mint: Option<&str>,
     logger: &Logger,
@@ -1121,25 +552,30 @@ pub async fn get_pool_state(
     if let Some(pool_id) = pool_id {
         // logger.log(format!("[FIND POOL STATE BY pool_id]: {}", pool_id));
         let amm_pool_id = Pubkey::from_str(pool_id)?;
-        let pool_data = common::rpc::get_account(&rpc_client, &amm_pool_id)?
+        let pool_data = get_account_data(&rpc_pool, &amm_pool_id)
+            .await?
             .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
         let pool_state: &AmmInfo =
             bytemuck::from_bytes(&pool_data[0..core::mem::size_of::<AmmInfo>()]);
         Ok((amm_pool_id, *pool_state))
     } else if let Some(mint) = mint {
         // find pool by mint via rpc
-        if let Ok(pool_state) = get_pool_state_by_mint(rpc_client.clone(), mint, logger).await {
+        if let Ok(pool_state) = get_pool_state_by_mint(rpc_pool.clone(), mint, logger).await {
             return Ok(pool_state);
         }
         // find pool by mint via raydium api
-        let pool_data = get_pool_info(&spl_token::native_mint::ID.to_string(), mint).await;
-        if let Ok(pool_data) = pool_data {
-            let pool = pool_data
-                .get_pool()
+        let pools =
+            get_all_pool_info(&spl_token::native_mint::ID.to_string(), mint, "standard").await;
+        if let Ok(pools) = pools {
+            // standard pools include CPMM, which doesn't decode as `AmmInfo`
+            let pool = pools
+                .into_iter()
+                .find(|pool| pool.program_id == AMM_PROGRAM)
                 .ok_or(anyhow!("NotFoundPool: pool not found in raydium api"))?;
             let amm_pool_id = Pubkey::from_str(&pool.id)?;
             // logger.log(format!("[FIND POOL STATE BY raydium api]: {}", amm_pool_id));
-            let pool_data = common::rpc::get_account(&rpc_client, &amm_pool_id)?
+            let pool_data = get_account_data(&rpc_pool, &amm_pool_id)
+                .await?
                 .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
             let pool_state: &AmmInfo =
                 bytemuck::from_bytes(&pool_data[0..core::mem::size_of::<AmmInfo>()]);
@@ -1152,8 +588,20 @@ pub async fn get_pool_state(
     }
 }
 
+async fn get_account_data(rpc_pool: &RpcPool, pubkey: &Pubkey) -> Result<Option<Vec<u8>>> {
+    let account = rpc_pool
+        .read(|client| async move {
+            client
+                .get_account_with_commitment(pubkey, client.commitment())
+                .await
+        })
+        .await?
+        .value;
+    Ok(account.map(|account| account.data))
+}
+
 pub async fn get_pool_state_by_mint(
-    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    rpc_pool: Arc<RpcPool>,
     mint: &str,
     _logger: &Logger,
 ) -> Result<(Pubkey, AmmInfo)> {
@@ -1196,9 +644,23 @@ pub async fn get_pool_state_by_mint(
                 RpcFilterType::DataSize(pool_len),
             ]),
         };
-        let pools =
-            common::rpc::get_program_accounts_with_filters(&rpc_client, amm_program, filters)
-                .unwrap();
+        let pools = rpc_pool
+            .read(|client| {
+                let config = RpcProgramAccountsConfig {
+                    filters: filters.clone(),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                };
+                async move {
+                    client
+                        .get_program_accounts_with_config(&amm_program, config)
+                        .await
+                }
+            })
+            .await?;
         if !pools.is_empty() {
             found_pools = Some(pools);
             break;
@@ -1215,21 +677,138 @@ pub async fn get_pool_state_by_mint(
     }
 }
 
+/// A SOL pool of a mint on one of the Raydium programs.
+#[derive(Clone)]
+pub enum RaydiumPool {
+    AmmV4 { id: Pubkey, state: AmmInfo },
+    Cpmm(CpmmPool),
+    Clmm(ClmmPool),
+}
+
+impl RaydiumPool {
+    pub fn id(&self) -> Pubkey {
+        match self {
+            RaydiumPool::AmmV4 { id, .. } => *id,
+            RaydiumPool::Cpmm(pool) => pool.id,
+            RaydiumPool::Clmm(pool) => pool.id,
+        }
+    }
+
+    /// Vault holding the pool's SOL side.
+    pub fn sol_vault(&self) -> Pubkey {
+        match self {
+            RaydiumPool::AmmV4 { state, .. } => {
+                if state.coin_vault_mint == spl_token::native_mint::ID {
+                    state.coin_vault
+                } else {
+                    state.pc_vault
+                }
+            }
+            RaydiumPool::Cpmm(pool) => pool.sol_vault(),
+            RaydiumPool::Clmm(pool) => pool.sol_vault(),
+        }
+    }
+
+    /// Reads and decodes the pool `id` of the `kind` program.
+    pub async fn load(rpc_pool: &RpcPool, kind: RaydiumPoolKind, id: Pubkey) -> Result<Self> {
+        let data = get_account_data(rpc_pool, &id)
+            .await?
+            .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
+        match kind {
+            RaydiumPoolKind::AmmV4 => Ok(RaydiumPool::AmmV4 {
+                id,
+                state: *AmmInfo::load_from_bytes(&data)?,
+            }),
+            RaydiumPoolKind::Cpmm => Ok(RaydiumPool::Cpmm(CpmmPool::decode(id, &data)?)),
+            RaydiumPoolKind::Clmm => Ok(RaydiumPool::Clmm(ClmmPool::decode(id, &data)?)),
+        }
+    }
+}
+
+/// SOL pools of `mint` on the AMM v4, CPMM and CLMM programs. A program whose
+/// lookup fails contributes no pools; the Raydium API is asked when no
+/// program returns any.
+pub async fn find_pools(
+    rpc_pool: Arc<RpcPool>,
+    mint: &Pubkey,
+    logger: &Logger,
+) -> Vec<RaydiumPool> {
+    let mint_str = mint.to_string();
+    let (amm_v4, cpmm, clmm) = tokio::join!(
+        get_pool_state_by_mint(rpc_pool.clone(), &mint_str, logger),
+        raydium_cpmm::get_pools_by_mint(rpc_pool.clone(), mint),
+        raydium_clmm::get_pools_by_mint(rpc_pool.clone(), mint),
+    );
+    let mut pools = vec![];
+    if let Ok((id, state)) = amm_v4 {
+        pools.push(RaydiumPool::AmmV4 { id, state });
+    }
+    pools.extend(cpmm.unwrap_or_default().into_iter().map(RaydiumPool::Cpmm));
+    pools.extend(clmm.unwrap_or_default().into_iter().map(RaydiumPool::Clmm));
+
+    if pools.is_empty() {
+        // some RPC providers disable program account scans
+        if let Ok(api_pools) =
+            get_all_pool_info(&spl_token::native_mint::ID.to_string(), &mint_str, "all").await
+        {
+            for pool in &api_pools {
+                let Some(kind) = RaydiumPoolKind::from_program(&pool.program_id) else {
+                    logger.debug(format!(
+                        "skip pool {}: program {}",
+                        pool.id, pool.program_id
+                    ));
+                    continue;
+                };
+                let loaded = match Pubkey::from_str(&pool.id) {
+                    Ok(id) => RaydiumPool::load(&rpc_pool, kind, id).await,
+                    Err(e) => Err(e.into()),
+                };
+                match loaded {
+                    Ok(pool) => pools.push(pool),
+                    Err(e) => logger.debug(format!("skip pool {}: {}", pool.id, e)),
+                }
+            }
+        }
+    }
+    pools
+}
+
+/// The pool of `mint` holding the most SOL, so a copy moves the price least.
+pub async fn find_deepest_pool(
+    rpc_pool: Arc<RpcPool>,
+    mint: &Pubkey,
+    logger: &Logger,
+) -> Result<RaydiumPool> {
+    let pools = find_pools(rpc_pool.clone(), mint, logger).await;
+    let (pool, balance) = raydium_common::deepest_pool(&rpc_pool, pools, RaydiumPool::sol_vault)
+        .await?
+        .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
+    logger.debug(format!("deepest pool {}: {} lamports", pool.id(), balance));
+    Ok(pool)
+}
+
 // get pool info
-// https://api-v3.raydium.io/pools/info/mint?mint1=So11111111111111111111111111111111111111112&mint2=EzM2d8JVpzfhV7km3tUsR1U1S4xwkrPnWkM4QFeTpump&poolType=standard&poolSortField=default&sortType=desc&pageSize=10&page=1
-pub async fn get_pool_info(mint1: &str, mint2: &str) -> Result<PoolData> {
+// https://api-v3.raydium.io/pools/info/mint?mint1=So11111111111111111111111111111111111111112&mint2=EzM2d8JVpzfhV7km3tUsR1U1S4xwkrPnWkM4QFeTpump&poolType=standard&poolSortField=default&sortType=desc&pageSize=100&page=1
+pub async fn get_pool_info(
+    mint1: &str,
+    mint2: &str,
+    pool_type: &str,
+    page: u32,
+) -> Result<PoolData> {
     let client = reqwest::Client::new();
+    let page_size = API_PAGE_SIZE.to_string();
+    let page = page.to_string();
 
     let result = client
         .get("https://api-v3.raydium.io/pools/info/mint")
         .query(&[
             ("mint1", mint1),
             ("mint2", mint2),
-            ("poolType", "standard"),
+            ("poolType", pool_type),
             ("poolSortField", "default"),
             ("sortType", "desc"),
-            ("pageSize", "1"),
-            ("page", "1"),
+            ("pageSize", page_size.as_str()),
+            ("page", page.as_str()),
         ])
         .send()
         .await?
@@ -1238,3 +817,17 @@ pub async fn get_pool_info(mint1: &str, mint2: &str) -> Result<PoolData> {
         .context("Failed to parse pool info JSON")?;
     Ok(result.data)
 }
+
+/// Every pool of the pair the Raydium API lists, reading page after page.
+pub async fn get_all_pool_info(mint1: &str, mint2: &str, pool_type: &str) -> Result<Vec<Pool>> {
+    let mut pools = vec![];
+    for page in 1..=API_MAX_PAGES {
+        let pool_data = get_pool_info(mint1, mint2, pool_type, page).await?;
+        let last_page = !pool_data.has_next_page || pool_data.data.len() < API_PAGE_SIZE;
+        pools.extend(pool_data.data);
+        if last_page {
+            break;
+        }
+    }
+    Ok(pools)
+}
@@ -0,0 +1,663 @@
+use std::{cmp::Reverse, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use borsh::BorshDeserialize as _;
+use borsh_derive::BorshDeserialize;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+};
+
+use crate::{
+    core::token,
+    dex::{
+        executor::{
+            min_amount_with_slippage, slippage_bps, swap_amount, swap_token_accounts,
+            wsol_swap_instructions, DexExecutor, SwapBound, SwapQuote,
+        },
+        raydium_common::{self, FEE_RATE_DENOMINATOR},
+    },
+    engine::swap::SwapDirection,
+    services::rpc_pool::RpcPool,
+    utils::{config::SwapConfig, logger::Logger},
+};
+
+pub const CLMM_PROGRAM: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+pub const MEMO_PROGRAM: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+pub const CLMM_SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+pub const CLMM_SWAP_V2_DISCRIMINATOR: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+pub const CLMM_POOL_LEN: u64 = 1544;
+// offsets of the mints in the pool account, for program account filters
+const POOL_MINT_0_OFFSET: usize = 73;
+const POOL_MINT_1_OFFSET: usize = 105;
+pub const TICK_ARRAY_SIZE: i32 = 60;
+// discriminator, pool id and start index precede the ticks of a tick array
+const TICK_ARRAY_HEADER_LEN: usize = 44;
+const TICK_STATE_LEN: usize = 168;
+// tick array starts read for a quote, starting with the current one; the
+// swap is only sent with the ones its quote reaches
+const SCANNED_TICK_ARRAYS: i32 = 8;
+
+// On-chain layout of a CLMM pool, up to the current tick.
+#[derive(Debug, BorshDeserialize)]
+struct PoolStateAccount {
+    _discriminator: u64,
+    _bump: u8,
+    amm_config: [u8; 32],
+    _owner: [u8; 32],
+    token_mint_0: [u8; 32],
+    token_mint_1: [u8; 32],
+    token_vault_0: [u8; 32],
+    token_vault_1: [u8; 32],
+    observation_key: [u8; 32],
+    mint_decimals_0: u8,
+    mint_decimals_1: u8,
+    tick_spacing: u16,
+    liquidity: u128,
+    sqrt_price_x64: u128,
+    tick_current: i32,
+}
+
+/// A Raydium concentrated-liquidity pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClmmPool {
+    pub id: Pubkey,
+    pub amm_config: Pubkey,
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation_key: Pubkey,
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    pub tick_spacing: u16,
+    pub liquidity: u128,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+}
+
+impl ClmmPool {
+    pub fn decode(id: Pubkey, data: &[u8]) -> Result<Self> {
+        let account = PoolStateAccount::deserialize(&mut &data[..])
+            .map_err(|e| anyhow!("failed to deserialize clmm pool {}: {}", id, e))?;
+        Ok(Self {
+            id,
+            amm_config: Pubkey::new_from_array(account.amm_config),
+            token_mint_0: Pubkey::new_from_array(account.token_mint_0),
+            token_mint_1: Pubkey::new_from_array(account.token_mint_1),
+            token_vault_0: Pubkey::new_from_array(account.token_vault_0),
+            token_vault_1: Pubkey::new_from_array(account.token_vault_1),
+            observation_key: Pubkey::new_from_array(account.observation_key),
+            mint_decimals_0: account.mint_decimals_0,
+            mint_decimals_1: account.mint_decimals_1,
+            tick_spacing: account.tick_spacing,
+            liquidity: account.liquidity,
+            sqrt_price_x64: account.sqrt_price_x64,
+            tick_current: account.tick_current,
+        })
+    }
+
+    /// Vault holding the pool's SOL side.
+    pub fn sol_vault(&self) -> Pubkey {
+        if self.token_mint_0 == spl_token::native_mint::ID {
+            self.token_vault_0
+        } else {
+            self.token_vault_1
+        }
+    }
+
+    /// Walks a swap of `amount_in` through the initialized ticks of
+    /// `tick_arrays`, which run from the current array in the swap's price
+    /// direction, with the trade fee taken from the input. Fails when the
+    /// swap would move the price past the last loaded tick, where the
+    /// liquidity is unknown.
+    pub fn swap(
+        &self,
+        amount_in: u64,
+        zero_for_one: bool,
+        trade_fee_rate: u64,
+        tick_arrays: &[TickArray],
+    ) -> Result<ClmmSwap> {
+        if amount_in == 0 {
+            return Ok(ClmmSwap {
+                amount_out: 0,
+                tick_arrays: tick_arrays.len().min(1),
+            });
+        }
+        let mut remaining = amount_in as f64 * (FEE_RATE_DENOMINATOR - trade_fee_rate) as f64
+            / FEE_RATE_DENOMINATOR as f64;
+        let mut liquidity = self.liquidity as f64;
+        let mut sqrt_price = self.sqrt_price_x64 as f64 / 2f64.powi(64);
+        let mut amount_out = 0.0;
+
+        // ticks below or at the current one are crossed going down, the
+        // ones above going up
+        let mut ticks: Vec<(usize, ClmmTick)> = tick_arrays
+            .iter()
+            .enumerate()
+            .flat_map(|(index, array)| array.ticks.iter().map(move |tick| (index, *tick)))
+            .filter(|(_, tick)| {
+                if zero_for_one {
+                    tick.tick <= self.tick_current
+                } else {
+                    tick.tick > self.tick_current
+                }
+            })
+            .collect();
+        if zero_for_one {
+            ticks.sort_by_key(|(_, tick)| Reverse(tick.tick));
+        } else {
+            ticks.sort_by_key(|(_, tick)| tick.tick);
+        }
+
+        for (index, tick) in ticks {
+            let target = tick_sqrt_price(tick.tick);
+            let (step_in, step_out) = if zero_for_one {
+                (
+                    liquidity * (1.0 / target - 1.0 / sqrt_price),
+                    liquidity * (sqrt_price - target),
+                )
+            } else {
+                (
+                    liquidity * (target - sqrt_price),
+                    liquidity * (1.0 / sqrt_price - 1.0 / target),
+                )
+            };
+            if remaining < step_in {
+                // the swap ends before reaching the tick
+                amount_out += if zero_for_one {
+                    let next = liquidity * sqrt_price / (liquidity + remaining * sqrt_price);
+                    liquidity * (sqrt_price - next)
+                } else {
+                    let next = sqrt_price + remaining / liquidity;
+                    liquidity * (1.0 / sqrt_price - 1.0 / next)
+                };
+                return Ok(ClmmSwap {
+                    amount_out: amount_out.max(0.0) as u64,
+                    tick_arrays: index + 1,
+                });
+            }
+            remaining -= step_in;
+            amount_out += step_out;
+            sqrt_price = target;
+            liquidity += if zero_for_one {
+                -(tick.liquidity_net as f64)
+            } else {
+                tick.liquidity_net as f64
+            };
+        }
+        Err(anyhow!(
+            "swap of {} exceeds the liquidity of the {} tick arrays loaded for clmm pool {}",
+            amount_in,
+            tick_arrays.len(),
+            self.id
+        ))
+    }
+
+    /// Start tick of the tick array holding `tick_current`.
+    pub fn current_tick_array_start(&self) -> i32 {
+        let ticks_per_array = self.tick_spacing as i32 * TICK_ARRAY_SIZE;
+        self.tick_current.div_euclid(ticks_per_array) * ticks_per_array
+    }
+}
+
+/// Square root of the price at `tick`, in the units of `sqrt_price_x64 / 2^64`.
+fn tick_sqrt_price(tick: i32) -> f64 {
+    1.0001f64.powf(tick as f64 / 2.0)
+}
+
+/// An initialized tick; crossing it upwards adds `liquidity_net` to the
+/// liquidity in range, crossing it downwards removes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClmmTick {
+    pub tick: i32,
+    pub liquidity_net: i128,
+}
+
+/// An initialized tick array and its initialized ticks, in ascending order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickArray {
+    pub address: Pubkey,
+    pub start_index: i32,
+    pub ticks: Vec<ClmmTick>,
+}
+
+impl TickArray {
+    pub fn decode(address: Pubkey, data: &[u8]) -> Result<Self> {
+        let invalid = || anyhow!("invalid clmm tick array {}", address);
+        let start_index = i32::from_le_bytes(data.get(40..44).ok_or_else(invalid)?.try_into()?);
+        let mut ticks = vec![];
+        for index in 0..TICK_ARRAY_SIZE as usize {
+            let offset = TICK_ARRAY_HEADER_LEN + index * TICK_STATE_LEN;
+            let tick = data
+                .get(offset..offset + TICK_STATE_LEN)
+                .ok_or_else(invalid)?;
+            // tick, liquidity_net and liquidity_gross lead the tick state
+            let liquidity_gross = u128::from_le_bytes(tick[20..36].try_into()?);
+            if liquidity_gross == 0 {
+                continue;
+            }
+            ticks.push(ClmmTick {
+                tick: i32::from_le_bytes(tick[0..4].try_into()?),
+                liquidity_net: i128::from_le_bytes(tick[4..20].try_into()?),
+            });
+        }
+        Ok(Self {
+            address,
+            start_index,
+            ticks,
+        })
+    }
+}
+
+/// Outcome of a swap walked through the loaded tick arrays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClmmSwap {
+    pub amount_out: u64,
+    /// How many of the loaded tick arrays the swap reaches, in order.
+    pub tick_arrays: usize,
+}
+
+#[derive(Clone)]
+pub struct RaydiumClmm {
+    pub rpc_pool: Arc<RpcPool>,
+    pub keypair: Arc<Keypair>,
+}
+
+/// Pool of a quoted CLMM swap, its direction and the tick arrays it crosses.
+#[derive(Debug, Clone)]
+pub struct ClmmRoute {
+    pub pool: ClmmPool,
+    pub zero_for_one: bool,
+    pub tick_arrays: Vec<Pubkey>,
+}
+
+impl RaydiumClmm {
+    pub fn new(rpc_pool: Arc<RpcPool>, keypair: Arc<Keypair>) -> Self {
+        Self { rpc_pool, keypair }
+    }
+
+    pub async fn quote_pool(
+        &self,
+        pool: ClmmPool,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<ClmmRoute>> {
+        let slippage_bps = slippage_bps(swap_config);
+        let sol_is_0 = pool.token_mint_0 == spl_token::native_mint::ID;
+        let (mint, mint_decimals) = if sol_is_0 {
+            (pool.token_mint_1, pool.mint_decimals_1)
+        } else {
+            (pool.token_mint_0, pool.mint_decimals_0)
+        };
+        let zero_for_one = match swap_config.swap_direction {
+            SwapDirection::Buy => sol_is_0,
+            SwapDirection::Sell => !sol_is_0,
+        };
+
+        let (program_id, trade_fee_rate, tick_arrays) = tokio::try_join!(
+            async {
+                token::get_token_program(self.rpc_pool.clone(), mint)
+                    .await
+                    .map_err(anyhow::Error::from)
+            },
+            get_trade_fee_rate(&self.rpc_pool, &pool.amm_config),
+            get_tick_arrays(&self.rpc_pool, &pool, zero_for_one),
+        )?;
+
+        let swap_amount = swap_amount(
+            &self.rpc_pool,
+            &self.keypair,
+            mint,
+            program_id,
+            Some(mint_decimals),
+            swap_config,
+        )
+        .await?;
+        // the pool only receives what is left after the transfer fee
+        let swap = pool.swap(
+            swap_amount.amount - swap_amount.transfer_fee,
+            zero_for_one,
+            trade_fee_rate,
+            &tick_arrays,
+        )?;
+        let amount_out = swap.amount_out;
+        let minimum_amount_out = min_amount_with_slippage(amount_out, slippage_bps);
+        // pass one more array than the quote reaches, in case the price moves
+        // further before the swap lands
+        let tick_arrays: Vec<Pubkey> = tick_arrays
+            .iter()
+            .take(swap.tick_arrays + 1)
+            .map(|tick_array| tick_array.address)
+            .collect();
+        logger.debug(format!(
+            "amount_in: {}, minimum_amount_out: {}, tick arrays: {:?}",
+            swap_amount.amount, minimum_amount_out, tick_arrays
+        ));
+
+        Ok(SwapQuote {
+            mint,
+            token_program: program_id,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound: SwapBound::MinAmountOut(minimum_amount_out),
+            close_instruction: swap_amount.close_instruction,
+            route: ClmmRoute {
+                pool,
+                zero_for_one,
+                tick_arrays,
+            },
+        })
+    }
+}
+
+impl DexExecutor for RaydiumClmm {
+    type Route = ClmmRoute;
+    type Trade = ();
+
+    fn venue(&self) -> &'static str {
+        "RAYDIUM CLMM"
+    }
+
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
+    }
+
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
+
+    async fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<ClmmRoute>> {
+        let pools = get_pools_by_mint(self.rpc_pool.clone(), &mint).await?;
+        let (pool, _) = raydium_common::deepest_pool(&self.rpc_pool, pools, ClmmPool::sol_vault)
+            .await?
+            .ok_or_else(|| anyhow!("NotFoundPool: clmm pool not found"))?;
+        self.quote_pool(pool, swap_config, logger).await
+    }
+
+    fn build_instructions(&self, quote: &SwapQuote<ClmmRoute>) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let ClmmRoute {
+            pool,
+            zero_for_one,
+            tick_arrays,
+        } = &quote.route;
+        let (mints, vaults) = if *zero_for_one {
+            (
+                (pool.token_mint_0, pool.token_mint_1),
+                (pool.token_vault_0, pool.token_vault_1),
+            )
+        } else {
+            (
+                (pool.token_mint_1, pool.token_mint_0),
+                (pool.token_vault_1, pool.token_vault_0),
+            )
+        };
+        let SwapBound::MinAmountOut(minimum_amount_out) = quote.bound else {
+            return Err(anyhow!("clmm swaps are exact input"));
+        };
+        let swap_instruction = swap_v2(
+            pool,
+            &owner,
+            swap_token_accounts(&owner, quote),
+            vaults,
+            mints,
+            tick_arrays,
+            quote.amount_in,
+            minimum_amount_out,
+        )?;
+        wsol_swap_instructions(&owner, quote, swap_instruction)
+    }
+}
+
+fn clmm_program() -> Pubkey {
+    Pubkey::from_str(CLMM_PROGRAM).unwrap()
+}
+
+pub fn tick_array_pda(pool: &Pubkey, start_index: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"tick_array", pool.as_ref(), &start_index.to_be_bytes()],
+        &clmm_program(),
+    )
+    .0
+}
+
+pub fn tick_array_bitmap_extension_pda(pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"pool_tick_array_bitmap_extension", pool.as_ref()],
+        &clmm_program(),
+    )
+    .0
+}
+
+/// Initialized tick arrays the swap may walk through, starting with the
+/// current one and moving in the price direction of the swap.
+pub async fn get_tick_arrays(
+    rpc_pool: &RpcPool,
+    pool: &ClmmPool,
+    zero_for_one: bool,
+) -> Result<Vec<TickArray>> {
+    let ticks_per_array = pool.tick_spacing as i32 * TICK_ARRAY_SIZE;
+    let step = if zero_for_one {
+        -ticks_per_array
+    } else {
+        ticks_per_array
+    };
+    let start = pool.current_tick_array_start();
+    let candidates: Vec<Pubkey> = (0..SCANNED_TICK_ARRAYS)
+        .map(|i| tick_array_pda(&pool.id, start + i * step))
+        .collect();
+    let candidates = &candidates;
+    let accounts = rpc_pool
+        .read(|client| async move { client.get_multiple_accounts(candidates).await })
+        .await?;
+    let tick_arrays = candidates
+        .iter()
+        .zip(accounts)
+        .filter_map(|(address, account)| Some(TickArray::decode(*address, &account?.data)))
+        .collect::<Result<Vec<_>>>()?;
+    if tick_arrays.is_empty() {
+        return Err(anyhow!(
+            "clmm pool {} has no initialized tick arrays",
+            pool.id
+        ));
+    }
+    Ok(tick_arrays)
+}
+
+/// Swaps exactly `amount_in` for at least `minimum_amount_out`; the pairs are
+/// (input, output).
+#[allow(clippy::too_many_arguments)]
+pub fn swap_v2(
+    pool: &ClmmPool,
+    payer: &Pubkey,
+    token_accounts: (Pubkey, Pubkey),
+    vaults: (Pubkey, Pubkey),
+    mints: (Pubkey, Pubkey),
+    tick_arrays: &[Pubkey],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction> {
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(pool.amm_config, false),
+        AccountMeta::new(pool.id, false),
+        AccountMeta::new(token_accounts.0, false),
+        AccountMeta::new(token_accounts.1, false),
+        AccountMeta::new(vaults.0, false),
+        AccountMeta::new(vaults.1, false),
+        AccountMeta::new(pool.observation_key, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(spl_token_2022::ID, false),
+        AccountMeta::new_readonly(Pubkey::from_str(MEMO_PROGRAM)?, false),
+        AccountMeta::new_readonly(mints.0, false),
+        AccountMeta::new_readonly(mints.1, false),
+        AccountMeta::new(tick_array_bitmap_extension_pda(&pool.id), false),
+    ];
+    accounts.extend(
+        tick_arrays
+            .iter()
+            .map(|tick_array| AccountMeta::new(*tick_array, false)),
+    );
+
+    let mut data = CLMM_SWAP_V2_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    // no price limit
+    data.extend_from_slice(&0u128.to_le_bytes());
+    // is_base_input
+    data.push(1);
+    Ok(Instruction::new_with_bytes(clmm_program(), &data, accounts))
+}
+
+/// Trade fee rate of an amm config, read once per config.
+pub async fn get_trade_fee_rate(rpc_pool: &RpcPool, amm_config: &Pubkey) -> Result<u64> {
+    // discriminator, bump, index, owner and protocol_fee_rate precede the rate
+    raydium_common::get_trade_fee_rate(rpc_pool, amm_config, |data| {
+        data.get(47..51)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as u64)
+    })
+    .await
+}
+
+/// CLMM pools pairing `mint` with SOL, in either order.
+pub async fn get_pools_by_mint(rpc_pool: Arc<RpcPool>, mint: &Pubkey) -> Result<Vec<ClmmPool>> {
+    let accounts = raydium_common::get_program_pools(
+        &rpc_pool,
+        clmm_program(),
+        CLMM_POOL_LEN,
+        (POOL_MINT_0_OFFSET, POOL_MINT_1_OFFSET),
+        mint,
+    )
+    .await?;
+    Ok(accounts
+        .iter()
+        .filter_map(|(id, account)| ClmmPool::decode(*id, &account.data).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+    // the program's bounds on sqrt_price_x64, at ticks -443636 and 443636
+    const MIN_SQRT_PRICE_X64: u128 = 4_295_048_016;
+    const MAX_SQRT_PRICE_X64: u128 = 79_226_673_521_066_979_257_578_248_091;
+
+    fn pool(tick_current: i32) -> ClmmPool {
+        ClmmPool {
+            id: Pubkey::new_unique(),
+            amm_config: Pubkey::new_unique(),
+            token_mint_0: spl_token::native_mint::ID,
+            token_mint_1: Pubkey::new_unique(),
+            token_vault_0: Pubkey::new_unique(),
+            token_vault_1: Pubkey::new_unique(),
+            observation_key: Pubkey::new_unique(),
+            mint_decimals_0: 9,
+            mint_decimals_1: 6,
+            tick_spacing: 10,
+            liquidity: LIQUIDITY,
+            sqrt_price_x64: (tick_sqrt_price(tick_current) * 2f64.powi(64)) as u128,
+            tick_current,
+        }
+    }
+
+    fn tick_array(start_index: i32, ticks: &[(i32, i128)]) -> TickArray {
+        TickArray {
+            address: tick_array_pda(&Pubkey::default(), start_index),
+            start_index,
+            ticks: ticks
+                .iter()
+                .map(|&(tick, liquidity_net)| ClmmTick {
+                    tick,
+                    liquidity_net,
+                })
+                .collect(),
+        }
+    }
+
+    // going up, liquidity doubles at tick 300 and is all gone at 900, in
+    // the next array
+    fn tick_arrays() -> Vec<TickArray> {
+        vec![
+            tick_array(0, &[(300, LIQUIDITY as i128)]),
+            tick_array(600, &[(900, -2 * LIQUIDITY as i128)]),
+        ]
+    }
+
+    fn relative_error(value: f64, expected: f64) -> f64 {
+        (value / expected - 1.0).abs()
+    }
+
+    #[test]
+    fn sqrt_prices_follow_the_tick_math() {
+        assert_eq!(tick_sqrt_price(0), 1.0);
+        assert!(relative_error(tick_sqrt_price(10).powi(2), 1.0001f64.powi(10)) < 1e-12);
+        assert!(relative_error(tick_sqrt_price(-10) * tick_sqrt_price(10), 1.0) < 1e-12);
+        let x64 = 2f64.powi(64);
+        assert!(relative_error(tick_sqrt_price(443_636) * x64, MAX_SQRT_PRICE_X64 as f64) < 1e-9);
+        assert!(relative_error(tick_sqrt_price(-443_636) * x64, MIN_SQRT_PRICE_X64 as f64) < 1e-9);
+    }
+
+    #[test]
+    fn tick_arrays_start_at_multiples_of_their_span() {
+        // 10 ticks apart, 600 ticks to an array
+        assert_eq!(pool(0).current_tick_array_start(), 0);
+        assert_eq!(pool(599).current_tick_array_start(), 0);
+        assert_eq!(pool(600).current_tick_array_start(), 600);
+        assert_eq!(pool(-1).current_tick_array_start(), -600);
+        assert_eq!(pool(-600).current_tick_array_start(), -600);
+    }
+
+    #[test]
+    fn swaps_within_one_tick_array_are_constant_product() {
+        // at price 1 both virtual reserves equal the liquidity, so the 0.25%
+        // fee leaves 997_500_000 in for 10^12 * 997_500_000 / (10^12 + 997_500_000)
+        let swap = pool(0)
+            .swap(1_000_000_000, false, 2_500, &tick_arrays())
+            .unwrap();
+        assert_eq!(swap.amount_out, 996_505_985);
+        assert_eq!(swap.tick_arrays, 1);
+
+        // selling token 0 from tick 500 towards the tick at 300
+        let swap = pool(500)
+            .swap(500_000_000, true, 0, &tick_arrays())
+            .unwrap();
+        assert_eq!(swap.amount_out, 525_364_902);
+        assert_eq!(swap.tick_arrays, 1);
+    }
+
+    #[test]
+    fn swaps_cross_ticks_into_the_next_tick_array() {
+        // 15_112_303_331 of token 1 take the price to tick 300 for
+        // 10^12 * (1 - 1.0001^-150) of token 0; the remaining
+        // 14_887_696_668 swap against twice the liquidity
+        let swap = pool(0)
+            .swap(30_000_000_000, false, 0, &tick_arrays())
+            .unwrap();
+        assert_eq!(swap.amount_out, 29_229_867_706);
+        assert_eq!(swap.tick_arrays, 2);
+
+        // past tick 900 there is no liquidity left
+        assert!(pool(0)
+            .swap(100_000_000_000, false, 0, &tick_arrays())
+            .is_err());
+        assert_eq!(
+            pool(0).swap(0, false, 0, &tick_arrays()).unwrap(),
+            ClmmSwap {
+                amount_out: 0,
+                tick_arrays: 1,
+            }
+        );
+    }
+}
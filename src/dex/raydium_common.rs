@@ -0,0 +1,116 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+
+use crate::services::rpc_pool::RpcPool;
+
+// trade fees of the CPMM and CLMM programs are expressed in millionths
+pub const FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+
+lazy_static! {
+    // amm config -> trade fee rate, which is set per fee tier; CPMM and CLMM
+    // configs are distinct accounts, so one map serves both programs
+    static ref TRADE_FEE_RATES: RwLock<HashMap<Pubkey, u64>> = RwLock::new(HashMap::new());
+}
+
+/// Trade fee rate of an amm config, read once per config and pulled out of
+/// the account data with `decode`.
+pub async fn get_trade_fee_rate(
+    rpc_pool: &RpcPool,
+    amm_config: &Pubkey,
+    decode: impl Fn(&[u8]) -> Option<u64>,
+) -> Result<u64> {
+    if let Some(rate) = TRADE_FEE_RATES.read().unwrap().get(amm_config) {
+        return Ok(*rate);
+    }
+    let data = rpc_pool
+        .read(|client| async move { client.get_account_data(amm_config).await })
+        .await?;
+    let rate = decode(&data).ok_or_else(|| anyhow!("invalid amm config {}", amm_config))?;
+    TRADE_FEE_RATES.write().unwrap().insert(*amm_config, rate);
+    Ok(rate)
+}
+
+/// Token balances of `vaults`, in order.
+pub async fn get_vault_balances(rpc_pool: &RpcPool, vaults: &[Pubkey]) -> Result<Vec<u64>> {
+    let accounts = rpc_pool
+        .read(|client| async move { client.get_multiple_accounts(vaults).await })
+        .await?;
+    vaults
+        .iter()
+        .zip(accounts)
+        .map(|(vault, account)| {
+            let account = account.ok_or_else(|| anyhow!("vault {} not found", vault))?;
+            Ok(StateWithExtensions::<TokenAccount>::unpack(&account.data)?
+                .base
+                .amount)
+        })
+        .collect()
+}
+
+/// Pool accounts of `program` pairing `mint` with SOL, in either order.
+pub async fn get_program_pools(
+    rpc_pool: &RpcPool,
+    program: Pubkey,
+    data_size: u64,
+    mint_offsets: (usize, usize),
+    mint: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>> {
+    let native_mint = spl_token::native_mint::ID;
+    let mut pools = vec![];
+    for (mint_0, mint_1) in [(native_mint, *mint), (*mint, native_mint)] {
+        let filters = vec![
+            RpcFilterType::DataSize(data_size),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                mint_offsets.0,
+                &mint_0.to_bytes(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                mint_offsets.1,
+                &mint_1.to_bytes(),
+            )),
+        ];
+        let accounts = rpc_pool
+            .read(|client| {
+                let config = RpcProgramAccountsConfig {
+                    filters: Some(filters.clone()),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                };
+                async move {
+                    client
+                        .get_program_accounts_with_config(&program, config)
+                        .await
+                }
+            })
+            .await?;
+        pools.extend(accounts);
+    }
+    Ok(pools)
+}
+
+/// The pool holding the most SOL, with its SOL balance; `None` when there
+/// are no pools.
+pub async fn deepest_pool<P>(
+    rpc_pool: &RpcPool,
+    pools: Vec<P>,
+    sol_vault: impl Fn(&P) -> Pubkey,
+) -> Result<Option<(P, u64)>> {
+    let vaults: Vec<Pubkey> = pools.iter().map(sol_vault).collect();
+    let balances = get_vault_balances(rpc_pool, &vaults).await?;
+    Ok(pools
+        .into_iter()
+        .zip(balances)
+        .max_by_key(|(_, balance)| *balance))
+}
@@ -0,0 +1,411 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use borsh::BorshDeserialize as _;
+use borsh_derive::BorshDeserialize;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+};
+
+use crate::{
+    dex::{
+        executor::{
+            min_amount_with_slippage, slippage_bps, swap_amount, swap_token_accounts,
+            wsol_swap_instructions, DexExecutor, SwapBound, SwapQuote,
+        },
+        raydium_common::{self, get_vault_balances, FEE_RATE_DENOMINATOR},
+    },
+    engine::swap::SwapDirection,
+    services::rpc_pool::RpcPool,
+    utils::{config::SwapConfig, logger::Logger},
+};
+
+pub const CPMM_PROGRAM: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+pub const CPMM_SWAP_BASE_INPUT_DISCRIMINATOR: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+pub const CPMM_SWAP_BASE_OUTPUT_DISCRIMINATOR: [u8; 8] = [55, 217, 98, 86, 163, 74, 180, 173];
+pub const CPMM_POOL_LEN: u64 = 637;
+// offsets of the mints in the pool account, for program account filters
+const POOL_TOKEN_0_MINT_OFFSET: usize = 168;
+const POOL_TOKEN_1_MINT_OFFSET: usize = 200;
+
+// On-chain layout of a CPMM pool, up to the accrued fees.
+#[derive(Debug, BorshDeserialize)]
+struct PoolStateAccount {
+    _discriminator: u64,
+    amm_config: [u8; 32],
+    _pool_creator: [u8; 32],
+    token_0_vault: [u8; 32],
+    token_1_vault: [u8; 32],
+    _lp_mint: [u8; 32],
+    token_0_mint: [u8; 32],
+    token_1_mint: [u8; 32],
+    token_0_program: [u8; 32],
+    token_1_program: [u8; 32],
+    observation_key: [u8; 32],
+    _auth_bump: u8,
+    _status: u8,
+    _lp_mint_decimals: u8,
+    mint_0_decimals: u8,
+    mint_1_decimals: u8,
+    _lp_supply: u64,
+    protocol_fees_token_0: u64,
+    protocol_fees_token_1: u64,
+    fund_fees_token_0: u64,
+    fund_fees_token_1: u64,
+}
+
+/// A Raydium CPMM pool; fees the pool owes the protocol and fund are kept
+/// to net them out of the vault balances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpmmPool {
+    pub id: Pubkey,
+    pub amm_config: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_1_mint: Pubkey,
+    pub token_0_program: Pubkey,
+    pub token_1_program: Pubkey,
+    pub observation_key: Pubkey,
+    pub mint_0_decimals: u8,
+    pub mint_1_decimals: u8,
+    pub owed_fees_token_0: u64,
+    pub owed_fees_token_1: u64,
+}
+
+impl CpmmPool {
+    pub fn decode(id: Pubkey, data: &[u8]) -> Result<Self> {
+        let account = PoolStateAccount::deserialize(&mut &data[..])
+            .map_err(|e| anyhow!("failed to deserialize cpmm pool {}: {}", id, e))?;
+        Ok(Self {
+            id,
+            amm_config: Pubkey::new_from_array(account.amm_config),
+            token_0_vault: Pubkey::new_from_array(account.token_0_vault),
+            token_1_vault: Pubkey::new_from_array(account.token_1_vault),
+            token_0_mint: Pubkey::new_from_array(account.token_0_mint),
+            token_1_mint: Pubkey::new_from_array(account.token_1_mint),
+            token_0_program: Pubkey::new_from_array(account.token_0_program),
+            token_1_program: Pubkey::new_from_array(account.token_1_program),
+            observation_key: Pubkey::new_from_array(account.observation_key),
+            mint_0_decimals: account.mint_0_decimals,
+            mint_1_decimals: account.mint_1_decimals,
+            owed_fees_token_0: account.protocol_fees_token_0 + account.fund_fees_token_0,
+            owed_fees_token_1: account.protocol_fees_token_1 + account.fund_fees_token_1,
+        })
+    }
+
+    /// Vault holding the pool's SOL side.
+    pub fn sol_vault(&self) -> Pubkey {
+        if self.token_0_mint == spl_token::native_mint::ID {
+            self.token_0_vault
+        } else {
+            self.token_1_vault
+        }
+    }
+
+    /// Token 0 when `is_0`, otherwise token 1.
+    pub fn side(&self, is_0: bool) -> CpmmSide {
+        if is_0 {
+            CpmmSide {
+                mint: self.token_0_mint,
+                vault: self.token_0_vault,
+                program: self.token_0_program,
+                decimals: self.mint_0_decimals,
+                owed_fees: self.owed_fees_token_0,
+            }
+        } else {
+            CpmmSide {
+                mint: self.token_1_mint,
+                vault: self.token_1_vault,
+                program: self.token_1_program,
+                decimals: self.mint_1_decimals,
+                owed_fees: self.owed_fees_token_1,
+            }
+        }
+    }
+}
+
+/// One token of a CPMM pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpmmSide {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub program: Pubkey,
+    pub decimals: u8,
+    pub owed_fees: u64,
+}
+
+/// Quote math of a CPMM pool with the input side first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpmmQuote {
+    pub input_reserve: u64,
+    pub output_reserve: u64,
+    pub trade_fee_rate: u64,
+}
+
+impl CpmmQuote {
+    /// Output for `amount_in`, with the trade fee taken from the input.
+    pub fn amount_out(&self, amount_in: u64) -> u64 {
+        if amount_in == 0 || self.output_reserve == 0 {
+            return 0;
+        }
+        let fee = (amount_in as u128 * self.trade_fee_rate as u128)
+            .div_ceil(FEE_RATE_DENOMINATOR as u128);
+        let amount_in = amount_in as u128 - fee;
+        (self.output_reserve as u128 * amount_in / (self.input_reserve as u128 + amount_in)) as u64
+    }
+}
+
+#[derive(Clone)]
+pub struct RaydiumCpmm {
+    pub rpc_pool: Arc<RpcPool>,
+    pub keypair: Arc<Keypair>,
+}
+
+/// Pool of a quoted CPMM swap and the side it is paid in.
+#[derive(Debug, Clone)]
+pub struct CpmmRoute {
+    pub pool: CpmmPool,
+    pub input_is_0: bool,
+}
+
+impl RaydiumCpmm {
+    pub fn new(rpc_pool: Arc<RpcPool>, keypair: Arc<Keypair>) -> Self {
+        Self { rpc_pool, keypair }
+    }
+
+    pub async fn quote_pool(
+        &self,
+        pool: CpmmPool,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<CpmmRoute>> {
+        let slippage_bps = slippage_bps(swap_config);
+        let native_mint = spl_token::native_mint::ID;
+
+        // orient the pool so that index 0 is the input side
+        let sol_is_0 = pool.token_0_mint == native_mint;
+        let input_is_0 = match swap_config.swap_direction {
+            SwapDirection::Buy => sol_is_0,
+            SwapDirection::Sell => !sol_is_0,
+        };
+        let input = pool.side(input_is_0);
+        let output = pool.side(!input_is_0);
+        let coin = pool.side(!sol_is_0);
+
+        let (vault_balances, trade_fee_rate) = tokio::try_join!(
+            get_vault_balances(&self.rpc_pool, &[input.vault, output.vault]),
+            get_trade_fee_rate(&self.rpc_pool, &pool.amm_config),
+        )?;
+        let quote = CpmmQuote {
+            input_reserve: vault_balances[0].saturating_sub(input.owed_fees),
+            output_reserve: vault_balances[1].saturating_sub(output.owed_fees),
+            trade_fee_rate,
+        };
+
+        let swap_amount = swap_amount(
+            &self.rpc_pool,
+            &self.keypair,
+            coin.mint,
+            coin.program,
+            Some(coin.decimals),
+            swap_config,
+        )
+        .await?;
+        // the pool only receives what is left after the transfer fee
+        let amount_out = quote.amount_out(swap_amount.amount - swap_amount.transfer_fee);
+        let minimum_amount_out = min_amount_with_slippage(amount_out, slippage_bps);
+        logger.debug(format!(
+            "amount_in: {}, minimum_amount_out: {}",
+            swap_amount.amount, minimum_amount_out
+        ));
+
+        Ok(SwapQuote {
+            mint: coin.mint,
+            token_program: coin.program,
+            direction: swap_config.swap_direction.clone(),
+            amount_in: swap_amount.amount,
+            transfer_fee: swap_amount.transfer_fee,
+            amount_out,
+            bound: SwapBound::MinAmountOut(minimum_amount_out),
+            close_instruction: swap_amount.close_instruction,
+            route: CpmmRoute { pool, input_is_0 },
+        })
+    }
+}
+
+impl DexExecutor for RaydiumCpmm {
+    type Route = CpmmRoute;
+    type Trade = ();
+
+    fn venue(&self) -> &'static str {
+        "RAYDIUM CPMM"
+    }
+
+    fn rpc_pool(&self) -> &Arc<RpcPool> {
+        &self.rpc_pool
+    }
+
+    fn keypair(&self) -> &Arc<Keypair> {
+        &self.keypair
+    }
+
+    async fn quote(
+        &self,
+        mint: Pubkey,
+        swap_config: &SwapConfig,
+        logger: &Logger,
+    ) -> Result<SwapQuote<CpmmRoute>> {
+        let pools = get_pools_by_mint(self.rpc_pool.clone(), &mint).await?;
+        let (pool, _) = raydium_common::deepest_pool(&self.rpc_pool, pools, CpmmPool::sol_vault)
+            .await?
+            .ok_or_else(|| anyhow!("NotFoundPool: cpmm pool not found"))?;
+        self.quote_pool(pool, swap_config, logger).await
+    }
+
+    fn build_instructions(&self, quote: &SwapQuote<CpmmRoute>) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let CpmmRoute { pool, input_is_0 } = &quote.route;
+        let input = pool.side(*input_is_0);
+        let output = pool.side(!*input_is_0);
+        let SwapBound::MinAmountOut(minimum_amount_out) = quote.bound else {
+            return Err(anyhow!("cpmm swaps are exact input"));
+        };
+        let swap_instruction = swap_base_input(
+            pool,
+            &owner,
+            swap_token_accounts(&owner, quote),
+            (input.vault, output.vault),
+            (input.program, output.program),
+            (input.mint, output.mint),
+            quote.amount_in,
+            minimum_amount_out,
+        )?;
+        wsol_swap_instructions(&owner, quote, swap_instruction)
+    }
+}
+
+fn cpmm_program() -> Pubkey {
+    Pubkey::from_str(CPMM_PROGRAM).unwrap()
+}
+
+pub fn authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_and_lp_mint_auth_seed"], &cpmm_program()).0
+}
+
+/// Swaps exactly `amount_in` for at least `minimum_amount_out`; the pairs are
+/// (input, output).
+#[allow(clippy::too_many_arguments)]
+pub fn swap_base_input(
+    pool: &CpmmPool,
+    payer: &Pubkey,
+    token_accounts: (Pubkey, Pubkey),
+    vaults: (Pubkey, Pubkey),
+    token_programs: (Pubkey, Pubkey),
+    mints: (Pubkey, Pubkey),
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction> {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(authority_pda(), false),
+        AccountMeta::new_readonly(pool.amm_config, false),
+        AccountMeta::new(pool.id, false),
+        AccountMeta::new(token_accounts.0, false),
+        AccountMeta::new(token_accounts.1, false),
+        AccountMeta::new(vaults.0, false),
+        AccountMeta::new(vaults.1, false),
+        AccountMeta::new_readonly(token_programs.0, false),
+        AccountMeta::new_readonly(token_programs.1, false),
+        AccountMeta::new_readonly(mints.0, false),
+        AccountMeta::new_readonly(mints.1, false),
+        AccountMeta::new(pool.observation_key, false),
+    ];
+    let mut data = CPMM_SWAP_BASE_INPUT_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    Ok(Instruction::new_with_bytes(cpmm_program(), &data, accounts))
+}
+
+/// Trade fee rate of an amm config, read once per config.
+pub async fn get_trade_fee_rate(rpc_pool: &RpcPool, amm_config: &Pubkey) -> Result<u64> {
+    // discriminator, bump, disable_create_pool and index precede the rate
+    raydium_common::get_trade_fee_rate(rpc_pool, amm_config, |data| {
+        data.get(12..20)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    })
+    .await
+}
+
+/// CPMM pools pairing `mint` with SOL, in either order.
+pub async fn get_pools_by_mint(rpc_pool: Arc<RpcPool>, mint: &Pubkey) -> Result<Vec<CpmmPool>> {
+    let accounts = raydium_common::get_program_pools(
+        &rpc_pool,
+        cpmm_program(),
+        CPMM_POOL_LEN,
+        (POOL_TOKEN_0_MINT_OFFSET, POOL_TOKEN_1_MINT_OFFSET),
+        mint,
+    )
+    .await?;
+    Ok(accounts
+        .iter()
+        .filter_map(|(id, account)| CpmmPool::decode(*id, &account.data).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 100 SOL against 10^9 coins of 6 decimals, at the 0.25% fee tier
+    const SOL_RESERVE: u64 = 100_000_000_000;
+    const COIN_RESERVE: u64 = 1_000_000_000_000_000;
+    const TRADE_FEE_RATE: u64 = 2_500;
+
+    fn quote(input_reserve: u64, output_reserve: u64) -> CpmmQuote {
+        CpmmQuote {
+            input_reserve,
+            output_reserve,
+            trade_fee_rate: TRADE_FEE_RATE,
+        }
+    }
+
+    #[test]
+    fn buys_and_sells_are_constant_product_after_the_fee() {
+        // 1 SOL less a 2_500_000 lamport fee:
+        // 10^15 * 997_500_000 / (10^11 + 997_500_000)
+        assert_eq!(
+            quote(SOL_RESERVE, COIN_RESERVE).amount_out(1_000_000_000),
+            9_876_482_091_140
+        );
+        // 10^8 coins less a 2.5 * 10^11 unit fee:
+        // 10^11 * 99_750_000_000_000 / (10^15 + 99_750_000_000_000)
+        assert_eq!(
+            quote(COIN_RESERVE, SOL_RESERVE).amount_out(100_000_000_000_000),
+            9_070_243_237
+        );
+    }
+
+    #[test]
+    fn quotes_round_in_the_pools_favour() {
+        // the fee rounds up, so a one unit swap is all fee
+        assert_eq!(quote(SOL_RESERVE, COIN_RESERVE).amount_out(1), 0);
+        assert_eq!(quote(SOL_RESERVE, COIN_RESERVE).amount_out(0), 0);
+        assert_eq!(quote(SOL_RESERVE, 0).amount_out(1_000_000_000), 0);
+
+        // the product of the reserves never shrinks
+        let mut amount_in = 1;
+        while amount_in < SOL_RESERVE * 10 {
+            let amount_out = quote(SOL_RESERVE, COIN_RESERVE).amount_out(amount_in);
+            assert!(amount_out < COIN_RESERVE);
+            assert!(
+                (SOL_RESERVE + amount_in) as u128 * (COIN_RESERVE - amount_out) as u128
+                    >= SOL_RESERVE as u128 * COIN_RESERVE as u128
+            );
+            amount_in = amount_in * 3 + 1;
+        }
+    }
+}
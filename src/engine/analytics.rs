@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+
+use spl_token::{amount_to_ui_amount, native_mint::DECIMALS};
+
+use crate::engine::{backtest::Fixture, monitor::TradeInfoFromToken};
+
+const SLOT_SECONDS: f64 = 0.4;
+/// Buys into the first SOL of a curve are snipes.
+const SNIPE_CURVE_LAMPORTS: u64 = 1_000_000_000;
+const SNIPER_SHARE: f64 = 0.5;
+const BUNDLER_SHARE: f64 = 0.3;
+/// Closed positions needed before a win rate means much.
+const CONFIDENT_CLOSED_POSITIONS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletProfile {
+    Trader,
+    /// Mostly buys at the very start of pump.fun curves.
+    Sniper,
+    /// Often buys a coin several times in one slot.
+    Bundler,
+}
+
+/// How a wallet trades, from its own history.
+#[derive(Debug, Clone)]
+pub struct WalletStats {
+    pub trades: usize,
+    pub buys: usize,
+    pub sells: usize,
+    pub coins: usize,
+    pub closed_positions: usize,
+    pub wins: usize,
+    pub realized_pnl_lamports: i64,
+    /// Lamports spent on buys.
+    pub invested_lamports: u64,
+    pub avg_hold_secs: Option<f64>,
+    pub trades_per_day: f64,
+    pub median_buy_lamports: u64,
+    /// Share of pump.fun buys into the first SOL of the curve.
+    pub snipe_share: f64,
+    /// Share of buys that share a slot with another buy of the same coin.
+    pub same_slot_share: f64,
+    pub profile: WalletProfile,
+}
+
+#[derive(Default)]
+struct Position {
+    amount: f64,
+    cost_lamports: f64,
+    pnl_lamports: f64,
+    opened_at: f64,
+}
+
+/// Seconds on the chain clock: block time when known, slot time otherwise.
+fn time_of(trade: &TradeInfoFromToken, use_block_time: bool) -> f64 {
+    match (use_block_time, trade.block_time) {
+        (true, Some(block_time)) => block_time as f64,
+        _ => trade.slot as f64 * SLOT_SECONDS,
+    }
+}
+
+impl WalletStats {
+    pub fn win_rate(&self) -> f64 {
+        if self.closed_positions == 0 {
+            return 0.0;
+        }
+        self.wins as f64 / self.closed_positions as f64
+    }
+
+    /// 0-100, weighing win rate, return on what was spent and sample size.
+    /// Snipers and bundlers are halved; their entries can't be copied in time.
+    pub fn score(&self) -> u8 {
+        let roi = if self.invested_lamports == 0 {
+            0.0
+        } else {
+            self.realized_pnl_lamports as f64 / self.invested_lamports as f64
+        };
+        let confidence =
+            (self.closed_positions as f64 / CONFIDENT_CLOSED_POSITIONS as f64).min(1.0);
+        let score =
+            50.0 * self.win_rate() + 30.0 * (roi.clamp(-1.0, 1.0) + 1.0) / 2.0 + 20.0 * confidence;
+        let score = match self.profile {
+            WalletProfile::Trader => score,
+            WalletProfile::Sniper | WalletProfile::Bundler => score / 2.0,
+        };
+        score.round() as u8
+    }
+
+    pub fn summary(&self) -> String {
+        let hold = match self.avg_hold_secs {
+            Some(secs) if secs >= 3600.0 => format!("{:.1} h", secs / 3600.0),
+            Some(secs) if secs >= 60.0 => format!("{:.1} min", secs / 60.0),
+            Some(secs) => format!("{:.0} s", secs),
+            None => "n/a".to_string(),
+        };
+        format!(
+            "Trades: {} ({} buys, {} sells) over {} coins\nWin rate: {:.0}% of {} closed positions\nRealized PnL: {} SOL\nAverage hold: {}\nTrades per day: {:.1}\nTypical buy: {} SOL\nSnipes: {:.0}%, same-slot buys: {:.0}%\nProfile: {:?}\nScore: {}/100",
+            self.trades,
+            self.buys,
+            self.sells,
+            self.coins,
+            self.win_rate() * 100.0,
+            self.closed_positions,
+            self.realized_pnl_lamports as f64 / 10f64.powi(DECIMALS as i32),
+            hold,
+            self.trades_per_day,
+            amount_to_ui_amount(self.median_buy_lamports, DECIMALS),
+            self.snipe_share * 100.0,
+            self.same_slot_share * 100.0,
+            self.profile,
+            self.score()
+        )
+    }
+}
+
+/// Works out `fixture.target`'s trading from its history. `initial_virtual_sol_reserves`
+/// comes from the pump.fun Global account and dates curve entries.
+pub fn analyze(fixture: &Fixture, initial_virtual_sol_reserves: u64) -> WalletStats {
+    let trades: Vec<TradeInfoFromToken> = fixture
+        .transactions
+        .iter()
+        .filter_map(|json| TradeInfoFromToken::from_json(json.clone()).ok())
+        .filter(|trade| trade.target == fixture.target && !trade.mint.is_empty())
+        .filter(|trade| trade.token_amount() > 0.0)
+        .collect();
+    let use_block_time = trades.iter().all(|trade| trade.block_time.is_some());
+
+    let mut positions: HashMap<&str, Position> = HashMap::new();
+    let mut coins = HashSet::new();
+    let mut buy_slots: HashMap<(&str, u64), usize> = HashMap::new();
+    let mut buy_sizes = vec![];
+    let mut snipes = 0;
+    let mut pump_buys = 0;
+    let mut hold_secs = vec![];
+    let mut closed_positions = 0;
+    let mut wins = 0;
+    let mut realized_pnl_lamports = 0.0;
+    let mut invested_lamports = 0;
+
+    for trade in &trades {
+        coins.insert(trade.mint.as_str());
+        let now = time_of(trade, use_block_time);
+        let sol_lamports = trade.sol_lamports();
+        if trade.is_buy() {
+            buy_sizes.push(sol_lamports);
+            invested_lamports += sol_lamports;
+            *buy_slots
+                .entry((trade.mint.as_str(), trade.slot))
+                .or_default() += 1;
+            if let Some(pump_trade) = &trade.pump_trade {
+                pump_buys += 1;
+                let curve_sol_before = pump_trade
+                    .virtual_sol_reserves
+                    .saturating_sub(sol_lamports)
+                    .saturating_sub(initial_virtual_sol_reserves);
+                if curve_sol_before < SNIPE_CURVE_LAMPORTS {
+                    snipes += 1;
+                }
+            }
+            let position = positions.entry(trade.mint.as_str()).or_default();
+            if position.amount == 0.0 {
+                position.opened_at = now;
+            }
+            position.amount += trade.token_amount();
+            position.cost_lamports += sol_lamports as f64;
+        } else {
+            // sells of coins bought before the history starts can't be priced
+            let Some(position) = positions
+                .get_mut(trade.mint.as_str())
+                .filter(|position| position.amount > 0.0)
+            else {
+                continue;
+            };
+            let sold = trade.token_amount().min(position.amount);
+            let proceeds = sol_lamports as f64 * sold / trade.token_amount();
+            let cost = position.cost_lamports * sold / position.amount;
+            let pnl = proceeds - cost;
+            position.pnl_lamports += pnl;
+            position.cost_lamports -= cost;
+            position.amount -= sold;
+            realized_pnl_lamports += pnl;
+            // dust left after a sell-all still closes the position
+            if position.amount <= sold * 1e-6 {
+                closed_positions += 1;
+                if position.pnl_lamports > 0.0 {
+                    wins += 1;
+                }
+                hold_secs.push(now - position.opened_at);
+                positions.remove(trade.mint.as_str());
+            }
+        }
+    }
+
+    let buys = buy_sizes.len();
+    let same_slot_buys: usize = buy_slots.values().filter(|count| **count > 1).sum();
+    let share = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+    let snipe_share = share(snipes, pump_buys);
+    let same_slot_share = share(same_slot_buys, buys);
+    let profile = if same_slot_share >= BUNDLER_SHARE {
+        WalletProfile::Bundler
+    } else if snipe_share >= SNIPER_SHARE {
+        WalletProfile::Sniper
+    } else {
+        WalletProfile::Trader
+    };
+
+    let days = match (trades.first(), trades.last()) {
+        (Some(first), Some(last)) => {
+            (time_of(last, use_block_time) - time_of(first, use_block_time)) / 86_400.0
+        }
+        _ => 0.0,
+    };
+    buy_sizes.sort_unstable();
+
+    WalletStats {
+        trades: trades.len(),
+        buys,
+        sells: trades.len() - buys,
+        coins: coins.len(),
+        closed_positions,
+        wins,
+        realized_pnl_lamports: realized_pnl_lamports as i64,
+        invested_lamports,
+        avg_hold_secs: (!hold_secs.is_empty())
+            .then(|| hold_secs.iter().sum::<f64>() / hold_secs.len() as f64),
+        // a day at least, so a burst of trades doesn't read as thousands a day
+        trades_per_day: trades.len() as f64 / days.max(1.0),
+        median_buy_lamports: buy_sizes.get(buys / 2).copied().unwrap_or(0),
+        snipe_share,
+        same_slot_share,
+        profile,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const TARGET: &str = "QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF";
+    const OTHER_WALLET: &str = "YMN9Qj5jPNp7j14VPcML1B6xGgcPWVZUGLFU3Mnyfaf";
+    const CURVE: &str = "CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8";
+    const COIN_A: &str = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR";
+    const COIN_B: &str = "2VDW9dFE1ZXz4zWAbaBDQFynNVdRpQ73HyfSHMzBSL6Z";
+    const COIN_C: &str = "GgBaCs3NCBuZN12kCJgAW63ydqohFkHEdfdEXBPzLHq";
+    const COIN_D: &str = "LbUiWL3xVV8hTFYBVdbTNrpDo41NKS6o3LHHuDzjfcY";
+    const SOL: u64 = 1_000_000_000;
+
+    /// `wallet` buying `tokens` of `mint` for `lamports` paid into its curve,
+    /// or selling them for `lamports` out of it when `tokens` is negative.
+    fn trade(
+        wallet: &str,
+        slot: u64,
+        block_time: i64,
+        mint: &str,
+        tokens: f64,
+        lamports: u64,
+    ) -> Value {
+        let (pre_tokens, post_tokens) = if tokens > 0.0 {
+            (0.0, tokens)
+        } else {
+            (-tokens, 0.0)
+        };
+        let curve_lamports = 50 * SOL;
+        let (pre_lamports, post_lamports) = if tokens > 0.0 {
+            (curve_lamports, curve_lamports + lamports)
+        } else {
+            (curve_lamports, curve_lamports - lamports)
+        };
+        let balances = |amount: f64| {
+            json!([
+                { "owner": wallet, "mint": mint, "uiTokenAmount": { "uiAmount": amount } },
+                { "owner": CURVE, "mint": mint, "uiTokenAmount": { "uiAmount": 1e9 } },
+            ])
+        };
+        json!({
+            "params": { "result": {
+                "slot": slot,
+                "blockTime": block_time,
+                "signature": format!("sig-{}-{}", slot, mint),
+                "transaction": {
+                    "transaction": { "message": { "accountKeys": [
+                        { "pubkey": wallet, "signer": true },
+                        { "pubkey": CURVE, "signer": false },
+                    ] } },
+                    "meta": {
+                        "preTokenBalances": balances(pre_tokens),
+                        "postTokenBalances": balances(post_tokens),
+                        "preBalances": [10 * SOL, pre_lamports],
+                        "postBalances": [10 * SOL, post_lamports],
+                    },
+                },
+            } },
+        })
+    }
+
+    fn history(transactions: Vec<Value>) -> Fixture {
+        Fixture {
+            target: TARGET.to_string(),
+            transactions,
+        }
+    }
+
+    #[test]
+    fn analyzes_closed_and_open_positions() {
+        let fixture = history(vec![
+            // A: bought for 1 SOL, sold for 1.5 SOL 300 s later
+            trade(TARGET, 100, 1_000, COIN_A, 1_000.0, SOL),
+            trade(TARGET, 850, 1_300, COIN_A, -1_000.0, 3 * SOL / 2),
+            // someone else's trade in the same history is not the target's
+            trade(OTHER_WALLET, 900, 1_400, COIN_A, 5_000.0, 4 * SOL),
+            // B: bought for 2 SOL, sold in two halves for 0.5 and 0.7 SOL
+            trade(TARGET, 2_600, 2_000, COIN_B, 2_000.0, 2 * SOL),
+            trade(TARGET, 2_850, 2_100, COIN_B, -1_000.0, SOL / 2),
+            trade(TARGET, 3_850, 2_500, COIN_B, -1_000.0, 7 * SOL / 10),
+            // C: held from before the history, its sell can't be priced
+            trade(TARGET, 5_100, 3_000, COIN_C, -500.0, SOL / 10),
+            // D: still held a day after the first trade
+            trade(TARGET, 216_100, 87_400, COIN_D, 100.0, SOL / 4),
+        ]);
+        let stats = analyze(&fixture, 30 * SOL);
+
+        assert_eq!(stats.trades, 7);
+        assert_eq!(stats.buys, 3);
+        assert_eq!(stats.sells, 4);
+        assert_eq!(stats.coins, 4);
+        assert_eq!(stats.closed_positions, 2);
+        assert_eq!(stats.wins, 1);
+        assert_eq!(stats.win_rate(), 0.5);
+        // +0.5 on A, -0.5 and -0.3 on B
+        assert_eq!(stats.realized_pnl_lamports, -3 * SOL as i64 / 10);
+        assert_eq!(stats.invested_lamports, 13 * SOL / 4);
+        assert_eq!(stats.avg_hold_secs, Some(400.0));
+        assert_eq!(stats.trades_per_day, 7.0);
+        assert_eq!(stats.median_buy_lamports, SOL);
+        assert_eq!(stats.snipe_share, 0.0);
+        assert_eq!(stats.same_slot_share, 0.0);
+        assert_eq!(stats.profile, WalletProfile::Trader);
+        // 50 * 0.5 + 30 * (1 - 0.3 / 3.25) / 2 + 20 * 2 / 20
+        assert_eq!(stats.score(), 41);
+    }
+
+    #[test]
+    fn buys_only_history_has_nothing_closed() {
+        let fixture = history(vec![
+            trade(TARGET, 100, 1_000, COIN_A, 1_000.0, SOL),
+            trade(TARGET, 100, 1_000, COIN_A, 1_000.0, SOL),
+            trade(TARGET, 200, 1_040, COIN_B, 1_000.0, 3 * SOL),
+        ]);
+        let stats = analyze(&fixture, 30 * SOL);
+
+        assert_eq!(stats.trades, 3);
+        assert_eq!(stats.buys, 3);
+        assert_eq!(stats.sells, 0);
+        assert_eq!(stats.coins, 2);
+        assert_eq!(stats.closed_positions, 0);
+        assert_eq!(stats.win_rate(), 0.0);
+        assert_eq!(stats.realized_pnl_lamports, 0);
+        assert_eq!(stats.invested_lamports, 5 * SOL);
+        assert_eq!(stats.avg_hold_secs, None);
+        // less than a day of trading counts as one
+        assert_eq!(stats.trades_per_day, 3.0);
+        assert_eq!(stats.median_buy_lamports, SOL);
+        // two of the three buys share a slot
+        assert_eq!(stats.same_slot_share, 2.0 / 3.0);
+        assert_eq!(stats.profile, WalletProfile::Bundler);
+        // (30 * 1 / 2) halved for a bundler
+        assert_eq!(stats.score(), 8);
+    }
+
+    #[test]
+    fn empty_history_scores_neutral_return_only() {
+        let stats = analyze(&history(vec![]), 30 * SOL);
+
+        assert_eq!(stats.trades, 0);
+        assert_eq!(stats.buys, 0);
+        assert_eq!(stats.coins, 0);
+        assert_eq!(stats.win_rate(), 0.0);
+        assert_eq!(stats.avg_hold_secs, None);
+        assert_eq!(stats.trades_per_day, 0.0);
+        assert_eq!(stats.median_buy_lamports, 0);
+        assert_eq!(stats.profile, WalletProfile::Trader);
+        assert_eq!(stats.score(), 15);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{collections::HashMap, fs, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_token::{amount_to_ui_amount, native_mint::DECIMALS, ui_amount_to_amount};
+
+use crate::{
+    core::paper::PaperAccount,
+    dex::{
+        bonding_curve::BondingCurve,
+        executor::{max_amount_with_slippage, min_amount_with_slippage},
+        pump_fun::PumpTrade,
+    },
+    engine::{
+        monitor::{copy_swap_config, TradeInfoFromToken},
+        swap::SwapDirection,
+    },
+    utils::logger::Logger,
+};
+
+/// Most signatures `getSignaturesForAddress` returns per page.
+const SIGNATURES_PAGE: usize = 1000;
+const FETCH_CONCURRENCY: usize = 8;
+
+/// A target's transactions cached to disk, each in the `transactionSubscribe`
+/// notification shape the monitor parses, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub target: String,
+    pub transactions: Vec<Value>,
+}
+
+impl Fixture {
+    pub fn load(path: &str) -> Result<Self> {
+        let data = fs::read(path).map_err(|e| anyhow!("failed to read {}: {}", path, e))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+            .map_err(|e| anyhow!("failed to write {}: {}", path, e))
+    }
+}
+
+/// Wraps a `getTransaction` result the way `transactionSubscribe` delivers it.
+fn to_notification(signature: &str, transaction: Value) -> Value {
+    json!({
+        "params": {
+            "result": {
+                "slot": transaction["slot"],
+                "blockTime": transaction["blockTime"],
+                "signature": signature,
+                "transaction": {
+                    "transaction": transaction["transaction"],
+                    "meta": transaction["meta"],
+                }
+            }
+        }
+    })
+}
+
+/// Pulls up to `limit` of `target`'s latest successful transactions.
+pub async fn fetch_fixture(
+    client: &RpcClient,
+    target: &Pubkey,
+    limit: usize,
+    logger: &Logger,
+) -> Result<Fixture> {
+    let mut signatures = vec![];
+    let mut before = None;
+    while signatures.len() < limit {
+        let page = client
+            .get_signatures_for_address_with_config(
+                target,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURES_PAGE.min(limit - signatures.len())),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(Signature::from_str(&last.signature)?);
+        signatures.extend(
+            page.into_iter()
+                .filter(|status| status.err.is_none())
+                .map(|status| status.signature),
+        );
+    }
+    logger.log(format!("fetching {} transactions", signatures.len()));
+
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let mut transactions: Vec<(u64, Value)> = stream::iter(signatures)
+        .map(|signature| async move {
+            let transaction = client
+                .get_transaction_with_config(&Signature::from_str(&signature)?, config)
+                .await?;
+            let notification = to_notification(&signature, serde_json::to_value(transaction)?);
+            Ok::<_, anyhow::Error>((
+                notification["params"]["result"]["slot"]
+                    .as_u64()
+                    .unwrap_or(0),
+                notification,
+            ))
+        })
+        .buffered(FETCH_CONCURRENCY)
+        .filter_map(|result| async move {
+            result
+                .map_err(|e| logger.error(format!("failed to fetch transaction: {}", e)))
+                .ok()
+        })
+        .collect()
+        .await;
+    transactions.sort_by_key(|(slot, _)| *slot);
+
+    Ok(Fixture {
+        target: target.to_string(),
+        transactions: transactions
+            .into_iter()
+            .map(|(_, transaction)| transaction)
+            .collect(),
+    })
+}
+
+/// Execution assumptions of a replay.
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    pub starting_sol: f64,
+    /// Percent of the target's size copied, as `TOKEN_PERCENTAGE`.
+    pub token_percent: f64,
+    /// Slippage tolerance in percent, as `SLIPPAGE`.
+    pub slippage: u64,
+    /// Slots between the target's trade and the copy landing; the copy fills
+    /// against the curve as the target's latest trade within them left it.
+    pub latency_slots: u64,
+    /// Adverse price movement on top of the replayed curve, in bps.
+    pub price_impact_bps: u64,
+    /// pump.fun fee charged by the curve.
+    pub fee_basis_points: u64,
+    /// Creator fee charged on top by curves with a creator.
+    pub creator_fee_basis_points: u64,
+    /// Jito tip paid per copy.
+    pub tip_sol: f64,
+}
+
+/// Outcome of replaying a fixture.
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub transactions: usize,
+    pub buys: usize,
+    pub sells: usize,
+    pub slippage_failures: usize,
+    pub skipped: usize,
+    pub account: PaperAccount,
+    /// What the open positions would sell for on their last replayed curves.
+    pub open_value_lamports: u64,
+}
+
+impl BacktestReport {
+    pub fn equity_lamports(&self) -> u64 {
+        self.account.sol_lamports + self.open_value_lamports
+    }
+
+    pub fn summary(&self) -> String {
+        let starting = self.account.starting_lamports;
+        let equity = self.equity_lamports();
+        let return_pct = if starting == 0 {
+            0.0
+        } else {
+            (equity as f64 - starting as f64) / starting as f64 * 100.0
+        };
+        format!(
+            "Transactions: {}\nCopied: {} buys, {} sells\nFailed on slippage: {}\nSkipped: {}\n{}\nOpen positions worth: {} SOL\nEquity: {} SOL ({:+.2}%)",
+            self.transactions,
+            self.buys,
+            self.sells,
+            self.slippage_failures,
+            self.skipped,
+            self.account.summary(),
+            amount_to_ui_amount(self.open_value_lamports, DECIMALS),
+            amount_to_ui_amount(equity, DECIMALS),
+            return_pct
+        )
+    }
+}
+
+fn curve(trade: &PumpTrade, config: &BacktestConfig) -> BondingCurve {
+    BondingCurve {
+        virtual_sol_reserves: trade.virtual_sol_reserves,
+        virtual_token_reserves: trade.virtual_token_reserves,
+        real_token_reserves: trade.real_token_reserves,
+        fee_basis_points: config.fee_basis_points,
+        creator_fee_basis_points: if trade.creator.is_some() {
+            config.creator_fee_basis_points
+        } else {
+            0
+        },
+    }
+}
+
+/// Replays the target's pump.fun trades through the monitor's parsing and
+/// sizing, filling copies against the curves the trades left behind.
+pub fn replay(fixture: &Fixture, config: &BacktestConfig) -> BacktestReport {
+    let trades: Vec<(TradeInfoFromToken, PumpTrade)> = fixture
+        .transactions
+        .iter()
+        .filter_map(|json| TradeInfoFromToken::from_json(json.clone()).ok())
+        .filter(|trade_info| trade_info.target == fixture.target)
+        .filter(|trade_info| {
+            trade_info.pump_swap_trade.is_none()
+                && trade_info.meteora_trade.is_none()
+                && trade_info.raydium_trade.is_none()
+        })
+        .filter_map(|trade_info| {
+            let pump_trade = trade_info.pump_trade.clone()?;
+            Some((trade_info, pump_trade))
+        })
+        .collect();
+
+    let mut report = BacktestReport {
+        transactions: fixture.transactions.len(),
+        buys: 0,
+        sells: 0,
+        slippage_failures: 0,
+        skipped: fixture.transactions.len() - trades.len(),
+        account: PaperAccount::new(ui_amount_to_amount(config.starting_sol, DECIMALS)),
+        open_value_lamports: 0,
+    };
+    let tip_lamports = ui_amount_to_amount(config.tip_sol, DECIMALS);
+    let slippage_bps = config.slippage * 100;
+    let mut last_curves: HashMap<String, BondingCurve> = HashMap::new();
+
+    for (i, (trade_info, pump_trade)) in trades.iter().enumerate() {
+        // the curve the bot quotes on, and the one its copy lands on
+        let seen = curve(pump_trade, config);
+        let landed = trades[i..]
+            .iter()
+            .take_while(|(later, _)| later.slot <= trade_info.slot + config.latency_slots)
+            .filter(|(later, _)| later.mint == trade_info.mint)
+            .last()
+            .map(|(_, later)| curve(later, config))
+            .unwrap_or(seen);
+        last_curves.insert(trade_info.mint.clone(), landed);
+
+        let swap_config = copy_swap_config(
+            trade_info,
+            config.token_percent,
+            config.slippage,
+            false,
+            None,
+            None,
+        );
+        let fill = match swap_config.swap_direction {
+            SwapDirection::Buy => {
+                let lamports = ui_amount_to_amount(swap_config.amount_in, DECIMALS);
+                // pump.fun buys are exact-out, bounded by the most SOL they may cost
+                let token_amount = seen.buy_quote(lamports);
+                let max_sol_cost = max_amount_with_slippage(lamports, slippage_bps);
+                match landed.buy_cost(token_amount) {
+                    Some(cost) if token_amount > 0 => {
+                        let cost = max_amount_with_slippage(cost, config.price_impact_bps);
+                        (cost <= max_sol_cost).then_some((cost, token_amount))
+                    }
+                    _ => None,
+                }
+            }
+            SwapDirection::Sell => {
+                let token_amount =
+                    ui_amount_to_amount(swap_config.amount_in, pump_trade.token_decimals);
+                let min_sol_output =
+                    min_amount_with_slippage(seen.sell_quote(token_amount), slippage_bps);
+                let sol_output = min_amount_with_slippage(
+                    landed.sell_quote(token_amount),
+                    config.price_impact_bps,
+                );
+                (sol_output >= min_sol_output && sol_output > 0)
+                    .then_some((token_amount, sol_output))
+            }
+        };
+        let Some((amount_in, amount_out)) = fill else {
+            report.slippage_failures += 1;
+            continue;
+        };
+        match report.account.apply(
+            &trade_info.mint,
+            &swap_config.swap_direction,
+            amount_in,
+            amount_out,
+            tip_lamports,
+        ) {
+            Ok(_) => match swap_config.swap_direction {
+                SwapDirection::Buy => report.buys += 1,
+                SwapDirection::Sell => report.sells += 1,
+            },
+            // an unaffordable buy, or a sell of a coin the copy never bought
+            Err(_) => report.skipped += 1,
+        }
+    }
+
+    report.open_value_lamports = report
+        .account
+        .positions
+        .iter()
+        .filter_map(|(mint, position)| Some(last_curves.get(mint)?.sell_quote(position.amount)))
+        .sum();
+    report
+}
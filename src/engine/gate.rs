@@ -0,0 +1,258 @@
+use std::{fmt, future::Future, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status::TransactionStatus;
+
+use crate::services::rpc_pool::RpcPool;
+
+/// How long a signal waits for its target's transaction to confirm; one that
+/// isn't confirmed by then has most likely been dropped with its fork.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(15);
+const POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// When a target's trade is copied, set per chat under `copy_gate`. The
+/// subscription delivers trades at processed, before they are final.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyGate {
+    /// As soon as the trade is seen; fastest, but dropped trades get copied too.
+    #[default]
+    Processed,
+    /// Once the target's transaction is confirmed.
+    Confirmed,
+    /// After a fixed delay in milliseconds, if the target's transaction is
+    /// still processed and succeeded by then.
+    Delay(u64),
+}
+
+impl FromStr for CopyGate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let gate = parts.next().unwrap_or_default().to_lowercase();
+        match (gate.as_str(), parts.next()) {
+            ("processed", None) => Ok(Self::Processed),
+            ("confirmed", None) => Ok(Self::Confirmed),
+            ("delay", Some(ms)) => ms
+                .parse()
+                .map(Self::Delay)
+                .map_err(|_| anyhow!("invalid delay {}, give it in milliseconds", ms)),
+            _ => Err(anyhow!(
+                "unknown copy gate {}, use processed, confirmed or delay <ms>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CopyGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Processed => write!(f, "processed"),
+            Self::Confirmed => write!(f, "confirmed"),
+            Self::Delay(ms) => write!(f, "delay {} ms", ms),
+        }
+    }
+}
+
+impl CopyGate {
+    pub fn for_chat(user_data: &Value) -> Self {
+        user_data
+            .get("copy_gate")
+            .and_then(|gate| serde_json::from_value(gate.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Holds a signal until the target's transaction reaches the gate. Fails
+    /// when it failed, was dropped or didn't confirm in time, and the copy
+    /// should be cancelled.
+    pub async fn wait(&self, rpc_pool: &RpcPool, signature: &str) -> Result<()> {
+        if *self == Self::Processed {
+            return Ok(());
+        }
+        let signature = Signature::from_str(signature)?;
+        self.wait_for(signature, || async move {
+            Ok(rpc_pool
+                .read(|client| async move { client.get_signature_statuses(&[signature]).await })
+                .await?
+                .value
+                .into_iter()
+                .next()
+                .flatten())
+        })
+        .await
+    }
+
+    /// `wait` on the statuses `status` reads for `signature`.
+    async fn wait_for<F>(&self, signature: Signature, mut status: impl FnMut() -> F) -> Result<()>
+    where
+        F: Future<Output = Result<Option<TransactionStatus>>>,
+    {
+        let commitment = match self {
+            Self::Processed => return Ok(()),
+            Self::Confirmed => CommitmentConfig::confirmed(),
+            Self::Delay(ms) => {
+                tokio::time::sleep(Duration::from_millis(*ms)).await;
+                CommitmentConfig::processed()
+            }
+        };
+        let deadline = tokio::time::Instant::now() + CONFIRM_TIMEOUT;
+        loop {
+            match status().await? {
+                Some(status) if status.err.is_some() => {
+                    return Err(anyhow!(
+                        "target transaction {} failed: {:?}",
+                        signature,
+                        status.err
+                    ))
+                }
+                Some(status) if status.satisfies_commitment(commitment) => return Ok(()),
+                // the delay already gave it its time to land
+                None if matches!(self, Self::Delay(_)) => {
+                    return Err(anyhow!(
+                        "target transaction {} was dropped before the delay ran out",
+                        signature
+                    ))
+                }
+                _ => {}
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "target transaction {} did not reach {} in {:?}",
+                    signature,
+                    self,
+                    CONFIRM_TIMEOUT
+                ));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::transaction::TransactionError;
+    use solana_transaction_status::TransactionConfirmationStatus;
+    use tokio::time::Instant;
+
+    use super::*;
+
+    fn status(confirmation_status: TransactionConfirmationStatus) -> Option<TransactionStatus> {
+        Some(TransactionStatus {
+            slot: 1,
+            confirmations: None,
+            status: Ok(()),
+            err: None,
+            confirmation_status: Some(confirmation_status),
+        })
+    }
+
+    fn failed() -> Option<TransactionStatus> {
+        let err = TransactionError::InstructionError(
+            2,
+            solana_sdk::instruction::InstructionError::Custom(6002),
+        );
+        Some(TransactionStatus {
+            slot: 1,
+            confirmations: None,
+            status: Err(err.clone()),
+            err: Some(err),
+            confirmation_status: Some(TransactionConfirmationStatus::Processed),
+        })
+    }
+
+    /// Waits `gate` out against `statuses`, read one per poll with the last
+    /// repeated, and returns the outcome, the polls and the time it took.
+    async fn wait(
+        gate: CopyGate,
+        statuses: Vec<Option<TransactionStatus>>,
+    ) -> (Result<()>, usize, Duration) {
+        let start = Instant::now();
+        let mut polls = 0;
+        let result = gate
+            .wait_for(Signature::default(), || {
+                let status = statuses[polls.min(statuses.len() - 1)].clone();
+                polls += 1;
+                async move { Ok(status) }
+            })
+            .await;
+        (result, polls, start.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn processed_copies_right_away() {
+        let (result, polls, elapsed) = wait(CopyGate::Processed, vec![None]).await;
+        assert!(result.is_ok());
+        assert_eq!(polls, 0);
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn confirmed_waits_for_confirmation() {
+        let statuses = vec![
+            None,
+            status(TransactionConfirmationStatus::Processed),
+            status(TransactionConfirmationStatus::Confirmed),
+        ];
+        let (result, polls, elapsed) = wait(CopyGate::Confirmed, statuses).await;
+        assert!(result.is_ok());
+        assert_eq!(polls, 3);
+        assert_eq!(elapsed, POLL_INTERVAL * 2);
+
+        let statuses = vec![None, failed()];
+        let (result, polls, _) = wait(CopyGate::Confirmed, statuses).await;
+        assert!(result.unwrap_err().to_string().contains("failed"));
+        assert_eq!(polls, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_copies_what_landed_by_then() {
+        let statuses = vec![status(TransactionConfirmationStatus::Processed)];
+        let (result, polls, elapsed) = wait(CopyGate::Delay(1_500), statuses).await;
+        assert!(result.is_ok());
+        assert_eq!(polls, 1);
+        assert_eq!(elapsed, Duration::from_millis(1_500));
+
+        let (result, polls, elapsed) = wait(CopyGate::Delay(1_500), vec![None]).await;
+        assert!(result.unwrap_err().to_string().contains("dropped"));
+        assert_eq!(polls, 1);
+        assert_eq!(elapsed, Duration::from_millis(1_500));
+
+        let (result, _, _) = wait(CopyGate::Delay(1_500), vec![failed()]).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn confirmed_gives_up_after_the_timeout() {
+        let statuses = vec![status(TransactionConfirmationStatus::Processed)];
+        let (result, polls, elapsed) = wait(CopyGate::Confirmed, statuses).await;
+        assert!(result.unwrap_err().to_string().contains("did not reach"));
+        assert!(elapsed >= CONFIRM_TIMEOUT);
+        assert!(elapsed < CONFIRM_TIMEOUT + POLL_INTERVAL);
+        // one poll every 400ms from the start, the last one past the timeout
+        assert_eq!(polls, 39);
+    }
+
+    #[test]
+    fn gates_parse_from_commands() {
+        assert_eq!(
+            "processed".parse::<CopyGate>().unwrap(),
+            CopyGate::Processed
+        );
+        assert_eq!(
+            "Confirmed".parse::<CopyGate>().unwrap(),
+            CopyGate::Confirmed
+        );
+        assert_eq!(
+            "delay 800".parse::<CopyGate>().unwrap(),
+            CopyGate::Delay(800)
+        );
+        assert!("delay".parse::<CopyGate>().is_err());
+        assert!("delay soon".parse::<CopyGate>().is_err());
+        assert!("finalized".parse::<CopyGate>().is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{
+    core::fee::env_or,
+    engine::monitor::TradeInfoFromToken,
+    error::{ClientError, ClientResult},
+};
+
+/// How long a signature is remembered; repeated notifications arrive within seconds.
+const SIGNATURE_TTL: Duration = Duration::from_secs(120);
+/// Target trades kept to spot a buy and sell of the same coin in one slot.
+const RECENT_TARGET_TRADES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct SignalGuardConfig {
+    /// Least time between two copied buys of the same coin.
+    pub mint_cooldown: Duration,
+    /// Most copied buys of the same coin, 0 for no limit.
+    pub max_buys_per_mint: usize,
+}
+
+impl SignalGuardConfig {
+    pub fn from_env() -> Self {
+        Self {
+            mint_cooldown: Duration::from_millis(env_or("COPY_MINT_COOLDOWN_MS", 1_000)),
+            max_buys_per_mint: env_or("MAX_BUYS_PER_MINT", 3),
+        }
+    }
+}
+
+/// Filters the copy signals of one monitor: repeated notifications, wash
+/// trading by the target and bursts of buys of the same coin.
+pub struct SignalGuard {
+    config: SignalGuardConfig,
+    seen_signatures: HashMap<String, Instant>,
+    // (mint, slot, is_buy) of the target's latest trades
+    target_trades: VecDeque<(String, u64, bool)>,
+    last_buy: HashMap<String, Instant>,
+    // copied buys of each coin since its position was last closed
+    buys: HashMap<String, usize>,
+    // coins with a copy of a buy let through but not yet settled
+    pending_buys: HashSet<String>,
+}
+
+impl SignalGuard {
+    pub fn new(config: SignalGuardConfig) -> Self {
+        Self {
+            config,
+            seen_signatures: HashMap::new(),
+            target_trades: VecDeque::new(),
+            last_buy: HashMap::new(),
+            buys: HashMap::new(),
+            pending_buys: HashSet::new(),
+        }
+    }
+
+    /// Fails on a signature already seen.
+    pub fn check_signature(&mut self, signature: &str) -> ClientResult<()> {
+        let now = Instant::now();
+        self.seen_signatures
+            .retain(|_, seen_at| now.duration_since(*seen_at) < SIGNATURE_TTL);
+        if self
+            .seen_signatures
+            .insert(signature.to_string(), now)
+            .is_some()
+        {
+            return Err(ClientError::Duplicate(format!(
+                "signature {} was already processed",
+                signature
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fails when the target bought and sold the coin in the same slot, in one
+    /// transaction or two; such round trips are wash trades, not signals. A
+    /// sell of a coin still held, `held` by the chat's records or with a copied
+    /// buy in flight or unsold, always passes so the copied entry is exited.
+    pub fn check_wash(&mut self, trade_info: &TradeInfoFromToken, held: bool) -> ClientResult<()> {
+        let is_buy = trade_info.is_buy();
+        let round_trip = self.target_trades.iter().any(|(mint, slot, was_buy)| {
+            *mint == trade_info.mint && *slot == trade_info.slot && *was_buy != is_buy
+        });
+        let held = held || self.holds(&trade_info.mint);
+        let washed = trade_info.token_amount() == 0.0 || (round_trip && (is_buy || !held));
+        self.target_trades
+            .push_back((trade_info.mint.clone(), trade_info.slot, is_buy));
+        if self.target_trades.len() > RECENT_TARGET_TRADES {
+            self.target_trades.pop_front();
+        }
+        if washed {
+            return Err(ClientError::Duplicate(format!(
+                "target bought and sold {} in slot {}",
+                trade_info.mint, trade_info.slot
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether a copied buy of the coin is in flight or hasn't been sold off.
+    pub fn holds(&self, mint: &str) -> bool {
+        self.pending_buys.contains(mint) || self.buys.get(mint).is_some_and(|buys| *buys > 0)
+    }
+
+    /// Fails on a buy of a coin within its cooldown, past its buy limit or
+    /// while another copied buy of it is in flight; a buy let through is held
+    /// until `settle_buy`. Sells always pass so positions can be exited.
+    pub fn check_copy(&mut self, trade_info: &TradeInfoFromToken) -> ClientResult<()> {
+        if !trade_info.is_buy() {
+            return Ok(());
+        }
+        if self.pending_buys.contains(&trade_info.mint) {
+            return Err(ClientError::Duplicate(format!(
+                "a copied buy of {} is still in flight",
+                trade_info.mint
+            )));
+        }
+        let now = Instant::now();
+        if let Some(last_buy) = self.last_buy.get(&trade_info.mint) {
+            if now.duration_since(*last_buy) < self.config.mint_cooldown {
+                return Err(ClientError::Duplicate(format!(
+                    "{} was bought {:?} ago, cooldown is {:?}",
+                    trade_info.mint,
+                    now.duration_since(*last_buy),
+                    self.config.mint_cooldown
+                )));
+            }
+        }
+        let buys = self.buys.get(&trade_info.mint).copied().unwrap_or(0);
+        if self.config.max_buys_per_mint > 0 && buys >= self.config.max_buys_per_mint {
+            return Err(ClientError::Duplicate(format!(
+                "{} was already bought {} times",
+                trade_info.mint, buys
+            )));
+        }
+        self.pending_buys.insert(trade_info.mint.clone());
+        Ok(())
+    }
+
+    /// Settles a buy let through by `check_copy`; only a buy that was copied
+    /// counts towards the coin's cooldown and limit.
+    pub fn settle_buy(&mut self, mint: &str, copied: bool) {
+        self.pending_buys.remove(mint);
+        if copied {
+            *self.buys.entry(mint.to_string()).or_default() += 1;
+            self.last_buy.insert(mint.to_string(), Instant::now());
+        }
+    }
+
+    /// Starts the coin's buy count over once its position is sold off.
+    pub fn close_position(&mut self, mint: &str) {
+        self.buys.remove(mint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const TARGET: &str = "QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF";
+    const MINT: &str = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR";
+
+    fn guard(mint_cooldown: Duration, max_buys_per_mint: usize) -> SignalGuard {
+        SignalGuard::new(SignalGuardConfig {
+            mint_cooldown,
+            max_buys_per_mint,
+        })
+    }
+
+    /// The target's trade in a `transactionSubscribe` notification: a buy when
+    /// its coin balance grows, a sell when it shrinks.
+    fn trade(slot: u64, pre_amount: f64, post_amount: f64) -> TradeInfoFromToken {
+        let balance = |amount: f64| {
+            json!([{
+                "owner": TARGET,
+                "mint": MINT,
+                "uiTokenAmount": { "uiAmount": amount },
+            }])
+        };
+        TradeInfoFromToken::from_json(json!({
+            "params": { "result": {
+                "slot": slot,
+                "signature": format!("sig-{}-{}", slot, post_amount),
+                "transaction": {
+                    "transaction": { "message": { "accountKeys": [
+                        { "pubkey": TARGET, "signer": true },
+                    ] } },
+                    "meta": {
+                        "preTokenBalances": balance(pre_amount),
+                        "postTokenBalances": balance(post_amount),
+                    },
+                },
+            } },
+        }))
+        .unwrap()
+    }
+
+    fn buy(slot: u64) -> TradeInfoFromToken {
+        trade(slot, 0.0, 1_000.0)
+    }
+
+    fn sell(slot: u64) -> TradeInfoFromToken {
+        trade(slot, 1_000.0, 0.0)
+    }
+
+    #[test]
+    fn repeated_signatures_are_dropped() {
+        let mut guard = guard(Duration::ZERO, 0);
+        assert!(guard.check_signature("a").is_ok());
+        assert!(guard.check_signature("b").is_ok());
+        assert!(guard.check_signature("a").is_err());
+        assert!(guard.check_signature("b").is_err());
+    }
+
+    #[test]
+    fn buys_of_a_coin_wait_out_its_cooldown() {
+        let mut guard = guard(Duration::from_millis(50), 0);
+        assert!(guard.check_copy(&buy(1)).is_ok());
+        guard.settle_buy(MINT, true);
+        assert!(guard.check_copy(&buy(2)).is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(guard.check_copy(&buy(3)).is_ok());
+    }
+
+    #[test]
+    fn buys_of_a_coin_stop_at_the_cap_until_it_is_sold_off() {
+        let mut guard = guard(Duration::ZERO, 2);
+        for slot in 1..=2 {
+            assert!(guard.check_copy(&buy(slot)).is_ok());
+            guard.settle_buy(MINT, true);
+        }
+        assert!(guard.check_copy(&buy(3)).is_err());
+        // sells pass whatever the count
+        assert!(guard.check_copy(&sell(3)).is_ok());
+
+        guard.close_position(MINT);
+        assert!(guard.check_copy(&buy(4)).is_ok());
+    }
+
+    #[test]
+    fn buys_count_only_once_they_are_sent() {
+        let mut guard = guard(Duration::from_secs(60), 1);
+        assert!(guard.check_copy(&buy(1)).is_ok());
+        // the first copy is in flight
+        assert!(guard.check_copy(&buy(2)).is_err());
+
+        // a failed copy neither starts the cooldown nor counts to the cap
+        guard.settle_buy(MINT, false);
+        assert!(!guard.holds(MINT));
+        assert!(guard.check_copy(&buy(3)).is_ok());
+
+        guard.settle_buy(MINT, true);
+        assert!(guard.holds(MINT));
+        assert!(guard.check_copy(&buy(4)).is_err());
+    }
+
+    #[test]
+    fn same_slot_round_trips_are_washes() {
+        let mut guard = guard(Duration::ZERO, 0);
+        assert!(guard.check_wash(&buy(7), false).is_ok());
+        assert!(guard.check_wash(&sell(7), false).is_err());
+        assert!(guard.check_wash(&buy(7), false).is_err());
+        // a trade that moved no coins is no signal either
+        assert!(guard
+            .check_wash(&trade(8, 1_000.0, 1_000.0), false)
+            .is_err());
+
+        // the next slot is a fresh trade
+        assert!(guard.check_wash(&sell(9), false).is_ok());
+    }
+
+    #[test]
+    fn same_slot_sells_pass_when_the_coin_is_held() {
+        let mut guard = guard(Duration::ZERO, 0);
+        assert!(guard.check_wash(&buy(7), false).is_ok());
+        assert!(guard.check_wash(&sell(7), true).is_ok());
+
+        // a copied buy still in flight holds the coin too
+        assert!(guard.check_copy(&buy(8)).is_ok());
+        assert!(guard.check_wash(&buy(8), false).is_ok());
+        assert!(guard.check_wash(&sell(8), false).is_ok());
+        // buys in a slot the target already sold in never pass
+        assert!(guard.check_wash(&buy(8), true).is_err());
+    }
+}
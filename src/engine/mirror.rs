@@ -0,0 +1,209 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::utils::file::update_info;
+
+/// Which of a target's trades are copied, set per target under `mirror_modes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorMode {
+    #[default]
+    Both,
+    /// Ride the target's entries, exit on your own.
+    Buys,
+    /// Mirror exits on whatever you hold.
+    Sells,
+    /// Mirror exits only on positions opened by copying this target.
+    OwnExits,
+}
+
+impl FromStr for MirrorMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "both" => Ok(Self::Both),
+            "buys" | "buy" => Ok(Self::Buys),
+            "sells" | "sell" => Ok(Self::Sells),
+            "own_exits" | "own" => Ok(Self::OwnExits),
+            _ => Err(anyhow!(
+                "unknown mirror mode {}, use both, buys, sells or own_exits",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MirrorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            Self::Both => "both",
+            Self::Buys => "buys",
+            Self::Sells => "sells",
+            Self::OwnExits => "own_exits",
+        };
+        write!(f, "{}", mode)
+    }
+}
+
+impl MirrorMode {
+    pub fn for_target(user_data: &Value, target: &str) -> Self {
+        user_data["mirror_modes"]
+            .get(target)
+            .and_then(|mode| serde_json::from_value(mode.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether to copy a buy or sell; `opened_by_target` tells whether the
+    /// coin was bought by copying the same target.
+    pub fn allows(&self, is_buy: bool, opened_by_target: bool) -> bool {
+        match self {
+            Self::Both => true,
+            Self::Buys => is_buy,
+            Self::Sells => !is_buy,
+            Self::OwnExits => !is_buy && opened_by_target,
+        }
+    }
+}
+
+/// The target whose copied buy opened the position in `mint`.
+pub fn opened_by<'a>(user_data: &'a Value, mint: &str) -> Option<&'a str> {
+    user_data["copied_positions"].get(mint)?.as_str()
+}
+
+/// Remembers that copying `target` opened the position in `mint`, unless a
+/// copied buy already opened it.
+pub async fn record_copied_position(chat_id: &str, mint: &str, target: &str) -> Result<()> {
+    update_info(None, |info| Ok(record(info, chat_id, mint, target)?))
+        .await
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Forgets who opened the position in `mint` once it is sold off, so the next
+/// copied buy opens it afresh.
+pub async fn clear_copied_position(chat_id: &str, mint: &str) -> Result<()> {
+    update_info(None, |info| {
+        clear(info, chat_id, mint);
+        Ok(())
+    })
+    .await
+    .map_err(|e| anyhow!("{}", e))
+}
+
+fn record(info: &mut Value, chat_id: &str, mint: &str, target: &str) -> Result<()> {
+    let user_data = info
+        .get_mut(chat_id)
+        .and_then(|user_data| user_data.as_object_mut())
+        .ok_or_else(|| anyhow!("no settings for chat {}", chat_id))?;
+    let positions = user_data
+        .entry("copied_positions")
+        .or_insert_with(|| json!({}));
+    // the first copied buy opened it; later ones only add to it
+    if positions.get(mint).is_none() {
+        positions[mint] = json!(target);
+    }
+    Ok(())
+}
+
+fn clear(info: &mut Value, chat_id: &str, mint: &str) {
+    if let Some(positions) = info
+        .get_mut(chat_id)
+        .and_then(|user_data| user_data.get_mut("copied_positions"))
+        .and_then(|positions| positions.as_object_mut())
+    {
+        positions.remove(mint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAT: &str = "42";
+    const MINT: &str = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR";
+    const TARGET: &str = "QWmroo4YnnMqYW3cnxWkFdaTxGD3P7vMSzwMHGbUzwF";
+    const OTHER_TARGET: &str = "YMN9Qj5jPNp7j14VPcML1B6xGgcPWVZUGLFU3Mnyfaf";
+
+    #[test]
+    fn modes_allow_their_side_of_the_trade() {
+        use MirrorMode::*;
+        // (mode, is_buy, opened_by_target, allowed)
+        let cases = [
+            (Both, true, true, true),
+            (Both, true, false, true),
+            (Both, false, true, true),
+            (Both, false, false, true),
+            (Buys, true, true, true),
+            (Buys, true, false, true),
+            (Buys, false, true, false),
+            (Buys, false, false, false),
+            (Sells, true, true, false),
+            (Sells, true, false, false),
+            (Sells, false, true, true),
+            (Sells, false, false, true),
+            (OwnExits, true, true, false),
+            (OwnExits, true, false, false),
+            (OwnExits, false, true, true),
+            (OwnExits, false, false, false),
+        ];
+        for (mode, is_buy, opened_by_target, allowed) in cases {
+            assert_eq!(
+                mode.allows(is_buy, opened_by_target),
+                allowed,
+                "{} buy={} opened_by_target={}",
+                mode,
+                is_buy,
+                opened_by_target
+            );
+        }
+    }
+
+    #[test]
+    fn modes_parse_from_commands_and_settings() {
+        for (s, mode) in [
+            ("both", MirrorMode::Both),
+            ("BUYS", MirrorMode::Buys),
+            ("buy", MirrorMode::Buys),
+            ("sells", MirrorMode::Sells),
+            ("sell", MirrorMode::Sells),
+            ("own_exits", MirrorMode::OwnExits),
+            ("own", MirrorMode::OwnExits),
+        ] {
+            assert_eq!(s.parse::<MirrorMode>().unwrap(), mode);
+            // what is shown parses back
+            assert_eq!(mode.to_string().parse::<MirrorMode>().unwrap(), mode);
+        }
+        assert!("mirror".parse::<MirrorMode>().is_err());
+
+        let user_data = json!({ "mirror_modes": { TARGET: "own_exits" } });
+        assert_eq!(
+            MirrorMode::for_target(&user_data, TARGET),
+            MirrorMode::OwnExits
+        );
+        assert_eq!(
+            MirrorMode::for_target(&user_data, OTHER_TARGET),
+            MirrorMode::Both
+        );
+    }
+
+    #[test]
+    fn copied_positions_last_from_the_first_buy_until_sold_off() {
+        let mut info = json!({ CHAT: {} });
+        record(&mut info, CHAT, MINT, TARGET).unwrap();
+        assert_eq!(opened_by(&info[CHAT], MINT), Some(TARGET));
+
+        // adding to the position doesn't change who opened it
+        record(&mut info, CHAT, MINT, OTHER_TARGET).unwrap();
+        assert_eq!(opened_by(&info[CHAT], MINT), Some(TARGET));
+
+        clear(&mut info, CHAT, MINT);
+        assert_eq!(opened_by(&info[CHAT], MINT), None);
+        record(&mut info, CHAT, MINT, OTHER_TARGET).unwrap();
+        assert_eq!(opened_by(&info[CHAT], MINT), Some(OTHER_TARGET));
+
+        assert!(record(&mut info, "7", MINT, TARGET).is_err());
+    }
+}
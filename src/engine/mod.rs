@@ -1,2 +1,7 @@
+pub mod analytics;
+pub mod backtest;
+pub mod gate;
+pub mod guard;
+pub mod mirror;
 pub mod monitor;
 pub mod swap;
@@ -1,35 +1,68 @@
-use std::sync::Arc;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    dex::pump_fun::Pump,
+    core::{
+        cache,
+        ledger::{self, Fill},
+        paper, token,
+    },
+    dex::{
+        executor::{DexExecutor, DryRun},
+        meteora::{Meteora, MeteoraTrade},
+        pump_fun::{get_pda, Pump, PumpTrade, PUMP_PROGRAM},
+        pump_swap::{PumpSwap, PumpSwapTrade},
+        raydium::{Raydium, RaydiumTrade},
+    },
+    services::rpc_pool::RpcPool,
     telegram::send_msg,
     utils::{
         config::{Config, SwapConfig, SUBSCRIPTION_MSG},
-        file::{read_info, write_info},
+        file::{read_info, update_info},
     },
 };
 use anyhow::Result;
 use chrono::Utc;
 use colored::Colorize;
-use futures_util::{stream::StreamExt, SinkExt};
-use serde_json::{json, to_string, Value};
-use solana_sdk::signature::Keypair;
+use futures_util::{
+    stream::{SplitSink, SplitStream, StreamExt},
+    SinkExt,
+};
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
 use spl_token::amount_to_ui_amount;
+use spl_token_client::token::TokenError;
 use teloxide::{types::ChatId, Bot};
-use tokio::time::Instant;
-use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+use tokio::{net::TcpStream, time::Instant};
+use tokio_tungstenite::{
+    connect_async, tungstenite::Message as WsMessage, MaybeTlsStream, WebSocketStream,
+};
 use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient};
 
-use super::swap::{SwapDirection, SwapInType};
+use super::{
+    gate::CopyGate,
+    guard::{SignalGuard, SignalGuardConfig},
+    mirror::{self, MirrorMode},
+    swap::{SwapDirection, SwapInType},
+};
 
 #[derive(Clone, Debug)]
 pub struct TradeInfoFromToken {
     pub slot: u64,
+    /// Only set on transactions fetched with `getTransaction`.
+    pub block_time: Option<i64>,
     pub signature: String,
     pub target: String,
     pub mint: String,
     pub token_amount_list: TokenAmountList,
     pub sol_amount_list: SolAmountList,
+    pub pump_trade: Option<PumpTrade>,
+    pub pump_swap_trade: Option<PumpSwapTrade>,
+    pub meteora_trade: Option<MeteoraTrade>,
+    pub raydium_trade: Option<RaydiumTrade>,
 }
 
 #[derive(Clone, Debug)]
@@ -47,6 +80,7 @@ pub struct SolAmountList {
 impl TradeInfoFromToken {
     pub fn from_json(json: Value) -> Result<Self> {
         let slot = json["params"]["result"]["slot"].as_u64().unwrap_or(0);
+        let block_time = json["params"]["result"]["blockTime"].as_i64();
         let signature = json["params"]["result"]["signature"]
             .as_str()
             .unwrap_or("")
@@ -81,13 +115,27 @@ impl TradeInfoFromToken {
             }
         }
 
+        // the target's and the pool's wsol balances can't be told from the coin above
+        let pump_swap_trade = PumpSwapTrade::from_json(&json);
+        if let Some(trade) = &pump_swap_trade {
+            mint = trade.pool.base_mint.to_string();
+        }
+        let meteora_trade = MeteoraTrade::from_json(&json);
+        if let Some(trade) = &meteora_trade {
+            mint = trade.mint.to_string();
+        }
+        let raydium_trade = RaydiumTrade::from_json(&json);
+        if let Some(trade) = &raydium_trade {
+            mint = trade.mint.to_string();
+        }
+
         let token_post_amount = json["params"]["result"]["transaction"]["meta"]
             ["postTokenBalances"]
             .as_array()
             .and_then(|balances| {
                 balances
                     .iter()
-                    .find(|b| b["owner"] == target)
+                    .find(|b| b["owner"] == target && b["mint"] == mint)
                     .and_then(|b| b["uiTokenAmount"]["uiAmount"].as_f64())
             })
             .unwrap_or(0.0);
@@ -97,7 +145,7 @@ impl TradeInfoFromToken {
             .and_then(|balances| {
                 balances
                     .iter()
-                    .find(|b| b["owner"] == target)
+                    .find(|b| b["owner"] == target && b["mint"] == mint)
                     .and_then(|b| b["uiTokenAmount"]["uiAmount"].as_f64())
             })
             .unwrap_or(0.0);
@@ -123,8 +171,23 @@ impl TradeInfoFromToken {
             .and_then(|b| b.as_u64())
             .unwrap_or(0);
 
+        // PumpSwap, Meteora and Raydium pools hold their SOL in wsol vaults
+        // rather than as lamports
+        let (sol_pre_amount, sol_post_amount) =
+            match (&pump_swap_trade, &meteora_trade, &raydium_trade) {
+                (Some(trade), _, _) => (trade.quote_reserve_before, trade.reserves.quote_reserve),
+                (None, Some(trade), _) => (trade.sol_reserve_before, trade.sol_reserve_after),
+                (None, None, Some(trade)) => (trade.sol_reserve_before, trade.sol_reserve_after),
+                (None, None, None) => (sol_pre_amount, sol_post_amount),
+            };
+
+        let pump_trade = Pubkey::from_str(&mint)
+            .ok()
+            .and_then(|mint| PumpTrade::from_json(&json, &mint));
+
         Ok(Self {
             slot,
+            block_time,
             signature,
             target,
             mint,
@@ -136,41 +199,328 @@ impl TradeInfoFromToken {
                 sol_pre_amount,
                 sol_post_amount,
             },
+            pump_trade,
+            pump_swap_trade,
+            meteora_trade,
+            raydium_trade,
         })
     }
+
+    /// Whether the target bought; its coin balance grew.
+    pub fn is_buy(&self) -> bool {
+        self.token_amount_list.token_pre_amount < self.token_amount_list.token_post_amount
+    }
+
+    /// Lamports that moved in or out of the curve or pool.
+    pub fn sol_lamports(&self) -> u64 {
+        self.sol_amount_list
+            .sol_post_amount
+            .abs_diff(self.sol_amount_list.sol_pre_amount)
+    }
+
+    /// Coins the target bought or sold, in UI units.
+    pub fn token_amount(&self) -> f64 {
+        (self.token_amount_list.token_post_amount - self.token_amount_list.token_pre_amount).abs()
+    }
+}
+
+/// The venue a target's trade went through, with the executor that copies it
+/// there and what the trade tells about the pool or curve.
+enum CopyVenue {
+    PumpFun(Pump, Option<PumpTrade>),
+    PumpSwap(PumpSwap, PumpSwapTrade),
+    Meteora(Meteora, MeteoraTrade),
+    Raydium(Raydium, RaydiumTrade),
+}
+
+impl CopyVenue {
+    /// Copies on the AMM the trade names, or on pump.fun when it names none.
+    fn for_trade(
+        trade_info: &TradeInfoFromToken,
+        rpc_pool: &Arc<RpcPool>,
+        rpc_client: &Arc<solana_client::rpc_client::RpcClient>,
+        wallet: &Arc<Keypair>,
+    ) -> Self {
+        if let Some(trade) = &trade_info.pump_swap_trade {
+            Self::PumpSwap(
+                PumpSwap::new(rpc_pool.clone(), wallet.clone()),
+                trade.clone(),
+            )
+        } else if let Some(trade) = &trade_info.meteora_trade {
+            Self::Meteora(
+                Meteora::new(rpc_pool.clone(), wallet.clone()),
+                trade.clone(),
+            )
+        } else if let Some(trade) = &trade_info.raydium_trade {
+            Self::Raydium(
+                Raydium::new(rpc_pool.clone(), rpc_client.clone(), wallet.clone()),
+                trade.clone(),
+            )
+        } else {
+            Self::PumpFun(
+                Pump::new(rpc_pool.clone(), wallet.clone()),
+                trade_info.pump_trade.clone(),
+            )
+        }
+    }
+
+    async fn swap(
+        self,
+        mint_str: &str,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_url: String,
+        jito_tip_amount: f64,
+    ) -> Result<Vec<String>> {
+        match self {
+            Self::PumpFun(pump, trade) => {
+                pump.swap_by_trade(
+                    mint_str,
+                    trade,
+                    swap_config,
+                    start_time,
+                    jito_url,
+                    jito_tip_amount,
+                )
+                .await
+            }
+            Self::PumpSwap(pump_swap, trade) => {
+                pump_swap
+                    .swap_by_trade(
+                        mint_str,
+                        Some(trade),
+                        swap_config,
+                        start_time,
+                        jito_url,
+                        jito_tip_amount,
+                    )
+                    .await
+            }
+            Self::Meteora(meteora, trade) => {
+                meteora
+                    .swap_by_trade(
+                        mint_str,
+                        Some(trade),
+                        swap_config,
+                        start_time,
+                        jito_url,
+                        jito_tip_amount,
+                    )
+                    .await
+            }
+            Self::Raydium(raydium, trade) => {
+                raydium
+                    .swap_by_trade(
+                        mint_str,
+                        Some(trade),
+                        swap_config,
+                        start_time,
+                        jito_url,
+                        jito_tip_amount,
+                    )
+                    .await
+            }
+        }
+    }
+
+    async fn dry_run(
+        self,
+        mint_str: &str,
+        swap_config: SwapConfig,
+        start_time: Instant,
+        jito_tip_amount: f64,
+    ) -> Result<DryRun> {
+        match self {
+            Self::PumpFun(pump, trade) => {
+                pump.dry_run_by_trade(mint_str, trade, swap_config, start_time, jito_tip_amount)
+                    .await
+            }
+            Self::PumpSwap(pump_swap, trade) => {
+                pump_swap
+                    .dry_run_by_trade(
+                        mint_str,
+                        Some(trade),
+                        swap_config,
+                        start_time,
+                        jito_tip_amount,
+                    )
+                    .await
+            }
+            Self::Meteora(meteora, trade) => {
+                meteora
+                    .dry_run_by_trade(
+                        mint_str,
+                        Some(trade),
+                        swap_config,
+                        start_time,
+                        jito_tip_amount,
+                    )
+                    .await
+            }
+            Self::Raydium(raydium, trade) => {
+                raydium
+                    .dry_run_by_trade(
+                        mint_str,
+                        Some(trade),
+                        swap_config,
+                        start_time,
+                        jito_tip_amount,
+                    )
+                    .await
+            }
+        }
+    }
+}
+
+/// Sizes the copy of a target's trade: `token_percent` of the SOL it spent on
+/// buys, `token_percent` of the coins it sold on sells.
+pub fn copy_swap_config(
+    trade_info: &TradeInfoFromToken,
+    token_percent: f64,
+    slippage: u64,
+    use_bundle: bool,
+    lookup_table: Option<Pubkey>,
+    nonce_account: Option<Pubkey>,
+) -> SwapConfig {
+    if trade_info.is_buy() {
+        let sol_amount = amount_to_ui_amount(trade_info.sol_lamports(), 9);
+        let amount_in = sol_amount * token_percent / 100.0;
+        SwapConfig {
+            swap_direction: SwapDirection::Buy,
+            in_type: SwapInType::Qty,
+            amount_in,
+            slippage,
+            use_jito: true,
+            use_bundle,
+            lookup_table,
+            nonce_account,
+        }
+    } else {
+        let amount_in = trade_info.token_amount() * token_percent / 100.0;
+        SwapConfig {
+            swap_direction: SwapDirection::Sell,
+            in_type: SwapInType::Qty,
+            amount_in,
+            slippage,
+            use_jito: true,
+            use_bundle,
+            lookup_table,
+            nonce_account,
+        }
+    }
+}
+
+/// Holds a copy until the target's transaction passes the chat's gate, and
+/// tells the chat when the copy is cancelled instead.
+async fn pass_gate(
+    gate: CopyGate,
+    rpc_pool: &RpcPool,
+    signature: &str,
+    mint: &str,
+    bot: Bot,
+    chat_id: ChatId,
+    prefix: String,
+) -> bool {
+    if gate == CopyGate::Processed {
+        return true;
+    }
+    let start_time = Instant::now();
+    match gate.wait(rpc_pool, signature).await {
+        Ok(()) => {
+            println!(
+                "[GATE]({}): {} after {:?}",
+                mint,
+                gate,
+                start_time.elapsed()
+            );
+            true
+        }
+        Err(e) => {
+            let message = format!("Cancel {}: {}", mint, e).yellow().to_string();
+            if let Err(e) = send_msg(bot, chat_id, prefix, message).await {
+                println!("Error: {}", e);
+            }
+            false
+        }
+    }
+}
+
+/// Whether `owner` holds none of `mint` once its sell `signature` is
+/// confirmed, i.e. whether the sell closed the position.
+async fn position_closed(
+    rpc_pool: Arc<RpcPool>,
+    owner: Pubkey,
+    mint: &str,
+    signature: &str,
+) -> Result<bool> {
+    CopyGate::Confirmed.wait(&rpc_pool, signature).await?;
+    let mint = Pubkey::from_str(mint)?;
+    let token_program = token::get_token_program(rpc_pool.clone(), mint).await?;
+    let ata = get_associated_token_address_with_program_id(&owner, &mint, &token_program);
+    match token::get_account_info(rpc_pool, mint, ata).await {
+        Ok(account) => Ok(account.base.amount == 0),
+        // selling everything closes the coin account
+        Err(TokenError::AccountNotFound) => Ok(true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Connects to the first subscription endpoint that accepts, in the pool's
+/// order, and subscribes to the target transactions on it.
+async fn subscribe(
+    rpc_pool: &RpcPool,
+) -> Result<(
+    String,
+    SplitSink<WsStream, WsMessage>,
+    SplitStream<WsStream>,
+)> {
+    for url in rpc_pool.subscription_urls() {
+        match connect_async(&url).await {
+            Ok((ws_stream, _)) => {
+                let (mut write, read) = ws_stream.split();
+                if let Err(e) = write
+                    .send(WsMessage::Text(SUBSCRIPTION_MSG.to_string().into()))
+                    .await
+                {
+                    println!("Failed to send subscription message: {}", e);
+                    rpc_pool.mark_subscription_failed(&url);
+                    continue;
+                }
+                return Ok((url, write, read));
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to connect to WebSocket '{}': {}", url, e)
+                    .red()
+                    .to_string();
+                println!("{}", error_msg);
+                rpc_pool.mark_subscription_failed(&url);
+            }
+        }
+    }
+    Err(anyhow::anyhow!(
+        "WebSocket connection failed on every subscription endpoint"
+    ))
 }
 
 pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
     let config_guard = Config::get().await;
     let Config {
-        rpc_wss,
         token_percent,
         slippage,
         jito_url,
         jito_tip_amount,
+        jito_bundle,
+        rpc_pool,
         rpc_client,
-        rpc_nonblocking_client,
+        ..
     } = &*config_guard;
+    cache::start_blockhash_refresher(rpc_pool.clone()).await;
     println!("================================");
 
-    // WebSocket setup
-    let (ws_stream, _) = match connect_async(&*rpc_wss).await {
-        Ok(result) => result,
-        Err(e) => {
-            let error_msg = format!("Failed to connect to WebSocket '{}': {}", rpc_wss, e)
-                .red()
-                .to_string();
-            println!("{}", error_msg);
-            return Err(anyhow::anyhow!("WebSocket connection failed: {}", e));
-        }
-    };
-    let (mut write, mut read) = ws_stream.split();
-    if let Err(e) = write
-        .send(WsMessage::Text(SUBSCRIPTION_MSG.to_string().into()))
-        .await
-    {
-        println!("Failed to send subscription message: {}", e);
-    }
+    // WebSocket setup, trying the subscription endpoints in order
+    let (mut rpc_wss, mut _write, mut read) = subscribe(rpc_pool).await?;
 
     let prefix = "[PUMPFUN-MONITOR] => ".blue().bold().to_string();
     if let Err(e) = send_msg(
@@ -184,8 +534,27 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
         println!("Error: {}", e);
     }
 
-    // Subscription loop
-    while let Some(msg) = read.next().await {
+    // shared with the copy tasks, which settle the buys they were let through
+    let guard = Arc::new(Mutex::new(SignalGuard::new(SignalGuardConfig::from_env())));
+
+    // Subscription loop, moving to the next endpoint when the stream ends
+    loop {
+        let msg = match read.next().await {
+            Some(Ok(msg)) => msg,
+            ended => {
+                let reason = match ended {
+                    Some(Err(e)) => e.to_string(),
+                    _ => "closed".to_string(),
+                };
+                println!(
+                    "Subscription on {} ended ({}), resubscribing",
+                    rpc_wss, reason
+                );
+                rpc_pool.mark_subscription_failed(&rpc_wss);
+                (rpc_wss, _write, read) = subscribe(rpc_pool).await?;
+                continue;
+            }
+        };
         // Read info from data.json
         let mut info: Value = match read_info(None).await {
             Ok(info) => info,
@@ -225,9 +594,26 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
             if let Some(private_key) = user_data.get("private_key").and_then(|v| v.as_str()) {
                 let user_wallet = Keypair::from_base58_string(private_key);
                 let wallet = Arc::new(user_wallet);
+                let owner = wallet.pubkey();
 
-                let msg = msg?;
-                let swapx = Pump::new(rpc_nonblocking_client.clone(), rpc_client.clone(), wallet);
+                let lookup_table = user_data
+                    .get("lookup_table")
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| Pubkey::from_str(v).ok());
+                let nonce_account = user_data
+                    .get("nonce_account")
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| Pubkey::from_str(v).ok());
+                let dry_run = user_data
+                    .get("dry_run")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let paper_trading = user_data
+                    .get("paper")
+                    .and_then(|v| v.get("enabled"))
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let copy_gate = CopyGate::for_chat(user_data);
 
                 if let WsMessage::Text(text) = msg {
                     let start_time = Instant::now();
@@ -250,6 +636,17 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
                             continue;
                         }
                     };
+                    // a lagging endpoint delivers signals too late to copy
+                    if let Some(slot) = json["params"]["result"]["slot"].as_u64() {
+                        if rpc_pool.is_slot_lagging(slot) {
+                            println!("Subscription on {} is lagging at slot {}", rpc_wss, slot);
+                            rpc_pool.mark_subscription_failed(&rpc_wss);
+                            if rpc_pool.subscription_urls().len() > 1 {
+                                (rpc_wss, _write, read) = subscribe(rpc_pool).await?;
+                            }
+                            continue;
+                        }
+                    }
 
                     if json["params"]["result"]["transaction"]["transaction"]["message"]
                         ["accountKeys"]
@@ -277,6 +674,91 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
                         };
 
                         if targetlist.contains(&trade_info.target) {
+                            // repeated notifications of the same transaction
+                            let seen = guard.lock().unwrap().check_signature(&trade_info.signature);
+                            if let Err(e) = seen {
+                                println!("Skip {}: {}", trade_info.mint, e);
+                                continue;
+                            }
+                            // ClientError isn't Send, keep only its message across the await
+                            let held = mirror::opened_by(user_data, &trade_info.mint).is_some();
+                            let washed = guard
+                                .lock()
+                                .unwrap()
+                                .check_wash(&trade_info, held)
+                                .err()
+                                .map(|e| e.to_string());
+                            if let Some(reason) = washed {
+                                if let Err(e) = send_msg(
+                                    bot.clone(),
+                                    chat_id,
+                                    prefix.clone(),
+                                    format!("Skip {}: {}", trade_info.mint, reason)
+                                        .yellow()
+                                        .to_string(),
+                                )
+                                .await
+                                {
+                                    println!("Error: {}", e);
+                                }
+                                continue;
+                            }
+
+                            let mirror_mode = MirrorMode::for_target(user_data, &trade_info.target);
+                            let opened_by_target = mirror::opened_by(user_data, &trade_info.mint)
+                                == Some(trade_info.target.as_str());
+                            if !mirror_mode.allows(trade_info.is_buy(), opened_by_target) {
+                                println!(
+                                    "Skip {}: mirror mode {} of {}",
+                                    trade_info.mint, mirror_mode, trade_info.target
+                                );
+                                continue;
+                            }
+                            let refused = guard
+                                .lock()
+                                .unwrap()
+                                .check_copy(&trade_info)
+                                .err()
+                                .map(|e| e.to_string());
+                            if let Some(reason) = refused {
+                                if let Err(e) = send_msg(
+                                    bot.clone(),
+                                    chat_id,
+                                    prefix.clone(),
+                                    format!("Skip {}: {}", trade_info.mint, reason)
+                                        .yellow()
+                                        .to_string(),
+                                )
+                                .await
+                                {
+                                    println!("Error: {}", e);
+                                }
+                                continue;
+                            }
+
+                            // keep the curve warm for this and the next copies of the mint
+                            let bonding_curve = trade_info
+                                .pump_trade
+                                .as_ref()
+                                .map(|trade| trade.bonding_curve)
+                                .or_else(|| {
+                                    let mint = Pubkey::from_str(&trade_info.mint).ok()?;
+                                    get_pda(&mint, &Pubkey::from_str(PUMP_PROGRAM).ok()?).ok()
+                                })
+                                // migrated coins trade on PumpSwap, Meteora or Raydium and have no curve to watch
+                                .filter(|_| {
+                                    trade_info.pump_swap_trade.is_none()
+                                        && trade_info.meteora_trade.is_none()
+                                        && trade_info.raydium_trade.is_none()
+                                });
+                            if let Some(bonding_curve) = bonding_curve {
+                                cache::watch_account(
+                                    &rpc_wss,
+                                    bonding_curve,
+                                    cache::TARGET_WATCH_TTL,
+                                );
+                            }
+
                             if let Err(e) = send_msg(
                                 bot.clone(),
                                 chat_id,
@@ -309,32 +791,14 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
                                 println!("Error: {}", e);
                             }
 
-                            let token_pre_amount = trade_info.token_amount_list.token_pre_amount;
-                            let token_post_amount = trade_info.token_amount_list.token_post_amount;
-                            let swap_config = if token_pre_amount < token_post_amount {
-                                let sol_amount_lamports =
-                                    trade_info.sol_amount_list.sol_post_amount
-                                        - trade_info.sol_amount_list.sol_pre_amount;
-                                let sol_amount = amount_to_ui_amount(sol_amount_lamports, 9);
-                                let amount_in = sol_amount * token_percent / 100.0;
-                                SwapConfig {
-                                    swap_direction: SwapDirection::Buy,
-                                    in_type: SwapInType::Qty,
-                                    amount_in,
-                                    slippage: *slippage,
-                                    use_jito: true,
-                                }
-                            } else {
-                                let token_amount = token_pre_amount - token_post_amount;
-                                let amount_in = token_amount * token_percent / 100.0;
-                                SwapConfig {
-                                    swap_direction: SwapDirection::Sell,
-                                    in_type: SwapInType::Qty,
-                                    amount_in,
-                                    slippage: *slippage,
-                                    use_jito: true,
-                                }
-                            };
+                            let swap_config = copy_swap_config(
+                                &trade_info,
+                                *token_percent,
+                                *slippage,
+                                *jito_bundle,
+                                lookup_table,
+                                nonce_account,
+                            );
 
                             if let Err(e) = send_msg(
                                 bot.clone(),
@@ -353,26 +817,195 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
 
                             let bot_clone = bot.clone();
                             let prefix_clone = prefix.clone();
-                            let swapx_clone = swapx.clone();
+                            let venue =
+                                CopyVenue::for_trade(&trade_info, rpc_pool, rpc_client, &wallet);
                             let swap_config_clone = swap_config.clone();
+                            let swap_direction = swap_config.swap_direction.clone();
                             let jito_url_clone = jito_url.clone();
                             let jito_tip_amount_clone = jito_tip_amount.clone();
                             let mint_str = trade_info.mint.clone();
                             let chat_id_str = chat_id.to_string();
+                            let rpc_wss_clone = rpc_wss.clone();
+                            let target = trade_info.target.clone();
+                            let rpc_pool_clone = rpc_pool.clone();
+                            // a buy let through is settled once it is copied, failed or cancelled
+                            let settle_buy = {
+                                let guard = guard.clone();
+                                let is_buy = trade_info.is_buy();
+                                let mint = trade_info.mint.clone();
+                                move |copied: bool| {
+                                    if is_buy {
+                                        guard.lock().unwrap().settle_buy(&mint, copied);
+                                    }
+                                }
+                            };
+                            let guard_clone = guard.clone();
+                            // paper copies are dry runs filled against the virtual account
+                            if dry_run || paper_trading {
+                                tokio::spawn(async move {
+                                    if !pass_gate(
+                                        copy_gate,
+                                        &rpc_pool_clone,
+                                        &sig,
+                                        &mint_str,
+                                        bot_clone.clone(),
+                                        chat_id,
+                                        prefix_clone.clone(),
+                                    )
+                                    .await
+                                    {
+                                        settle_buy(false);
+                                        return;
+                                    }
+                                    let result = venue
+                                        .dry_run(
+                                            &mint_str,
+                                            swap_config_clone,
+                                            start_time,
+                                            jito_tip_amount_clone,
+                                        )
+                                        .await;
+                                    let message = match (result, paper_trading) {
+                                        (Ok(dry_run), true) => {
+                                            match paper::fill(&chat_id_str, &dry_run).await {
+                                                Ok((fill, account)) => {
+                                                    settle_buy(true);
+                                                    let recorded = match dry_run.direction {
+                                                        SwapDirection::Buy => {
+                                                            mirror::record_copied_position(
+                                                                &chat_id_str,
+                                                                &mint_str,
+                                                                &target,
+                                                            )
+                                                            .await
+                                                        }
+                                                        // the position is gone once all of it is sold
+                                                        SwapDirection::Sell
+                                                            if !account
+                                                                .positions
+                                                                .contains_key(&mint_str) =>
+                                                        {
+                                                            guard_clone
+                                                                .lock()
+                                                                .unwrap()
+                                                                .close_position(&mint_str);
+                                                            mirror::clear_copied_position(
+                                                                &chat_id_str,
+                                                                &mint_str,
+                                                            )
+                                                            .await
+                                                        }
+                                                        SwapDirection::Sell => Ok(()),
+                                                    };
+                                                    if let Err(e) = recorded {
+                                                        println!(
+                                                            "Failed to record position: {}",
+                                                            e
+                                                        );
+                                                    }
+                                                    let ledger_fill = Fill::paper(
+                                                        &chat_id_str,
+                                                        &target,
+                                                        &sig,
+                                                        &dry_run,
+                                                        &fill,
+                                                    );
+                                                    if let Err(e) =
+                                                        ledger::record(&ledger_fill).await
+                                                    {
+                                                        println!("Failed to record fill: {}", e);
+                                                    }
+                                                    format!(
+                                                        "\n\t * [PAPER-COPIED] => TARGET_TX: (https://solscan.io/tx/{}) \n\t * [POOL] => ({}) \n\t * [FILL] => {:?} {} for {} on {}, fees {} lamports, PnL {} lamports \n\t * [COPIED] => {} :: ({:?}). \n\t [PAPER] => {}",
+                                                        sig, mint_str, dry_run.direction, fill.amount_in, fill.amount_out, dry_run.venue, fill.fee_lamports, fill.pnl_lamports, Utc::now(), start_time.elapsed(), account.summary().replace('\n', ", ")
+                                                    )
+                                                    .green()
+                                                    .to_string()
+                                                }
+                                                Err(e) => {
+                                                    settle_buy(false);
+                                                    format!("Skip paper {}: {}", mint_str, e)
+                                                        .red()
+                                                        .italic()
+                                                        .to_string()
+                                                }
+                                            }
+                                        }
+                                        (Ok(dry_run), false) => {
+                                            settle_buy(true);
+                                            let fill = Fill::dry_run(
+                                                &chat_id_str,
+                                                &target,
+                                                &sig,
+                                                &dry_run,
+                                            );
+                                            if let Err(e) = ledger::record(&fill).await {
+                                                println!("Failed to record fill: {}", e);
+                                            }
+                                            format!(
+                                                "\n\t * [DRY-RUN] => {} \n\t * [TARGET] => (https://solscan.io/tx/{}) \n\t * [QUOTED] => {} :: ({:?}).",
+                                                dry_run.summary(), sig, Utc::now(), start_time.elapsed()
+                                            )
+                                            .yellow()
+                                            .to_string()
+                                        }
+                                        (Err(e), _) => {
+                                            settle_buy(false);
+                                            format!("Skip {}: {}", mint_str, e)
+                                                .red()
+                                                .italic()
+                                                .to_string()
+                                        }
+                                    };
+                                    if let Err(e) =
+                                        send_msg(bot_clone, chat_id, prefix_clone, message).await
+                                    {
+                                        println!("Error sending dry-run message: {}", e);
+                                    }
+                                });
+                                continue;
+                            }
                             tokio::spawn(async move {
-                                match swapx_clone
-                                    .swap_by_mint(
+                                if !pass_gate(
+                                    copy_gate,
+                                    &rpc_pool_clone,
+                                    &sig,
+                                    &mint_str,
+                                    bot_clone.clone(),
+                                    chat_id,
+                                    prefix_clone.clone(),
+                                )
+                                .await
+                                {
+                                    settle_buy(false);
+                                    return;
+                                }
+                                let result = venue
+                                    .swap(
                                         &mint_str,
                                         swap_config_clone,
                                         start_time,
                                         jito_url_clone,
                                         jito_tip_amount_clone,
                                     )
-                                    .await
-                                {
+                                    .await;
+                                match result {
                                     Ok(res) => {
-                                        // Update usage for this chat ID
-                                        if let Some(user_data) = info.get_mut(&chat_id_str) {
+                                        settle_buy(true);
+                                        if let (SwapDirection::Buy, Some(bonding_curve)) =
+                                            (&swap_direction, bonding_curve)
+                                        {
+                                            cache::watch_account(
+                                                &rpc_wss_clone,
+                                                bonding_curve,
+                                                cache::HELD_WATCH_TTL,
+                                            );
+                                        }
+
+                                        // Update usage for this chat ID on the current data,
+                                        // not the copy read when the signal came in
+                                        let usage = update_info(None, |info| {
+                                            let user_data = &mut info[&chat_id_str];
                                             let usage = user_data
                                                 .get("usage")
                                                 .and_then(|v| v.as_u64())
@@ -380,28 +1013,33 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
                                                 + 1;
                                             if let Some(obj) = user_data.as_object_mut() {
                                                 obj.insert("usage".to_string(), json!(usage));
+                                            } else {
+                                                // If chat_id doesn't exist, create a new entry
+                                                *user_data = json!({ "usage": usage });
                                             }
-                                        } else {
-                                            // If chat_id doesn't exist, create a new entry
-                                            info[&chat_id_str] = json!({ "usage": 1 });
-                                        }
+                                            Ok(usage)
+                                        })
+                                        .await;
+                                        let usage = match usage {
+                                            Ok(usage) => usage,
+                                            Err(e) => {
+                                                println!("Failed to write info: {}", e);
+                                                return;
+                                            }
+                                        };
 
-                                        // Write updated info back
-                                        if let Err(e) =
-                                            write_info(to_string(&info).unwrap_or_default(), None)
-                                                .await
-                                        {
-                                            println!("Failed to write info: {}", e);
-                                            return;
+                                        if swap_direction == SwapDirection::Buy {
+                                            if let Err(e) = mirror::record_copied_position(
+                                                &chat_id_str,
+                                                &mint_str,
+                                                &target,
+                                            )
+                                            .await
+                                            {
+                                                println!("Failed to record position: {}", e);
+                                            }
                                         }
 
-                                        // Get usage for display (after update)
-                                        let usage = info
-                                            .get(&chat_id_str)
-                                            .and_then(|v| v.get("usage"))
-                                            .and_then(|v| v.as_u64())
-                                            .unwrap_or(1);
-
                                         let message = format!(
                                     "\n\t * [SUCCESSFUL-COPIED] => TX_HASH: (https://solscan.io/tx/{}) \n\t * [POOL] => ({}) \n\t * [COPIED] => {} :: ({:?}). \n\t [USAGE] => {}",
                                     &res[0], mint_str, Utc::now(), start_time.elapsed(), usage
@@ -413,8 +1051,38 @@ pub async fn copytrader_pumpfun(bot: Bot, chat_id: ChatId) -> Result<()> {
                                         {
                                             println!("Error sending success message: {}", e);
                                         }
+
+                                        // the position is gone once the wallet holds none of the coin
+                                        if swap_direction == SwapDirection::Sell {
+                                            let closed = position_closed(
+                                                rpc_pool_clone,
+                                                owner,
+                                                &mint_str,
+                                                &res[0],
+                                            )
+                                            .await;
+                                            let cleared = match closed {
+                                                Ok(true) => {
+                                                    guard_clone
+                                                        .lock()
+                                                        .unwrap()
+                                                        .close_position(&mint_str);
+                                                    mirror::clear_copied_position(
+                                                        &chat_id_str,
+                                                        &mint_str,
+                                                    )
+                                                    .await
+                                                }
+                                                Ok(false) => Ok(()),
+                                                Err(e) => Err(e),
+                                            };
+                                            if let Err(e) = cleared {
+                                                println!("Failed to record position: {}", e);
+                                            }
+                                        }
                                     }
                                     Err(e) => {
+                                        settle_buy(false);
                                         let message = format!("Skip {}: {}", mint_str, e)
                                             .red()
                                             .italic()
@@ -1,7 +1,7 @@
 use clap::ValueEnum;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(ValueEnum, Debug, Clone, Deserialize, PartialEq)]
+#[derive(ValueEnum, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum SwapDirection {
     #[serde(rename = "buy")]
     Buy,
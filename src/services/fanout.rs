@@ -0,0 +1,290 @@
+use std::{
+    env,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{signature::Signature, transaction::VersionedTransaction};
+use tokio::{
+    sync::{mpsc, OnceCell},
+    time::Instant,
+};
+
+use crate::{error::ClientError, services::jito::JitoClient, utils::logger::Logger};
+
+static JITO_FANOUT: OnceCell<Arc<JitoFanout>> = OnceCell::const_new();
+
+pub const RATE_LIMIT_BACKOFF: Duration = Duration::from_millis(500);
+pub const MAX_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(30);
+// weight of the newest sample in the latency moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+#[derive(Debug, Default, Clone)]
+pub struct EndpointStats {
+    pub sent: u64,
+    pub accepted: u64,
+    pub errors: u64,
+    pub rate_limited: u64,
+    pub latency_ms: f64,
+    consecutive_rate_limits: u32,
+    backoff_until: Option<Instant>,
+}
+
+impl EndpointStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.sent == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.sent as f64
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "sent {}, accepted {}, errors {:.1}% ({} rate limited), latency {:.0} ms{}",
+            self.sent,
+            self.accepted,
+            self.error_rate() * 100.0,
+            self.rate_limited,
+            self.latency_ms,
+            if self.is_backing_off() {
+                ", backing off"
+            } else {
+                ""
+            }
+        )
+    }
+
+    pub fn is_backing_off(&self) -> bool {
+        self.backoff_until
+            .map(|until| Instant::now() < until)
+            .unwrap_or(false)
+    }
+
+    fn record_success(&mut self, elapsed: Duration) {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        self.sent += 1;
+        self.accepted += 1;
+        self.latency_ms = if self.accepted == 1 {
+            elapsed_ms
+        } else {
+            LATENCY_EWMA_ALPHA * elapsed_ms + (1.0 - LATENCY_EWMA_ALPHA) * self.latency_ms
+        };
+        self.consecutive_rate_limits = 0;
+        self.backoff_until = None;
+    }
+
+    fn record_error(&mut self, err: &ClientError) {
+        self.sent += 1;
+        self.errors += 1;
+        if is_rate_limited(err) {
+            self.rate_limited += 1;
+            self.consecutive_rate_limits += 1;
+            let backoff = RATE_LIMIT_BACKOFF
+                .saturating_mul(2_u32.saturating_pow(self.consecutive_rate_limits - 1))
+                .min(MAX_RATE_LIMIT_BACKOFF);
+            self.backoff_until = Some(Instant::now() + backoff);
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Target {
+    BlockEngine(JitoClient),
+    Rpc(Arc<RpcClient>),
+}
+
+struct Endpoint {
+    name: String,
+    target: Target,
+    stats: Arc<Mutex<EndpointStats>>,
+}
+
+/// Sends the same signed transaction to every configured block engine (and
+/// optionally the regular RPC) at once and returns the first accepted signature.
+pub struct JitoFanout {
+    endpoints: Vec<Endpoint>,
+}
+
+impl JitoFanout {
+    pub fn new(block_engine_urls: Vec<String>, rpc_client: Option<Arc<RpcClient>>) -> Self {
+        let mut endpoints: Vec<Endpoint> = block_engine_urls
+            .iter()
+            .map(|url| Endpoint {
+                name: url.clone(),
+                target: Target::BlockEngine(JitoClient::new(url)),
+                stats: Arc::new(Mutex::new(EndpointStats::default())),
+            })
+            .collect();
+        if let Some(rpc_client) = rpc_client {
+            endpoints.push(Endpoint {
+                name: rpc_client.url(),
+                target: Target::Rpc(rpc_client),
+                stats: Arc::new(Mutex::new(EndpointStats::default())),
+            });
+        }
+        Self { endpoints }
+    }
+
+    /// Builds the fan-out from `JITO_BLOCK_ENGINE_URLS` (comma separated, falls back
+    /// to `jito_url`) and `JITO_FANOUT_RPC`, and registers it globally.
+    pub fn init_from_env(jito_url: &str, rpc_client: Arc<RpcClient>) -> Arc<JitoFanout> {
+        let block_engine_urls = env::var("JITO_BLOCK_ENGINE_URLS")
+            .ok()
+            .map(|urls| {
+                urls.split(',')
+                    .map(|url| url.trim().to_string())
+                    .filter(|url| !url.is_empty())
+                    .collect::<Vec<String>>()
+            })
+            .filter(|urls| !urls.is_empty())
+            .unwrap_or_else(|| vec![jito_url.to_string()]);
+        let include_rpc = env::var("JITO_FANOUT_RPC")
+            .ok()
+            .and_then(|v| bool::from_str(&v.to_lowercase()).ok())
+            .unwrap_or(false);
+
+        let fanout = Arc::new(JitoFanout::new(
+            block_engine_urls,
+            include_rpc.then_some(rpc_client),
+        ));
+        // Config is initialized once, so a second registration can only be a no-op
+        let _ = JITO_FANOUT.set(fanout.clone());
+        fanout
+    }
+
+    pub fn global() -> Option<Arc<JitoFanout>> {
+        JITO_FANOUT.get().cloned()
+    }
+
+    pub fn endpoint_names(&self) -> Vec<String> {
+        self.endpoints.iter().map(|e| e.name.clone()).collect()
+    }
+
+    pub fn stats(&self) -> Vec<(String, EndpointStats)> {
+        self.endpoints
+            .iter()
+            .map(|e| (e.name.clone(), e.stats.lock().unwrap().clone()))
+            .collect()
+    }
+
+    /// One line per endpoint with its send stats.
+    pub fn summary(&self) -> String {
+        self.stats()
+            .iter()
+            .map(|(name, stats)| format!("{}: {}", name, stats.summary()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Sends `transaction` to all healthy endpoints concurrently. Endpoints that are
+    /// backing off after a rate limit are skipped unless every endpoint is.
+    pub async fn send_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        logger: &Logger,
+    ) -> Result<Signature, ClientError> {
+        if self.endpoints.is_empty() {
+            return Err(ClientError::InvalidInput(
+                "no block engine endpoints configured",
+            ));
+        }
+
+        let mut selected: Vec<&Endpoint> = self
+            .endpoints
+            .iter()
+            .filter(|e| !e.stats.lock().unwrap().is_backing_off())
+            .collect();
+        if selected.is_empty() {
+            selected = self.endpoints.iter().collect();
+        }
+
+        let encoded_tx = bincode::serialize(transaction)
+            .map(|wire| bs64::encode(&wire))
+            .map_err(|e| {
+                ClientError::Parse(
+                    "Transaction serialization failed".to_string(),
+                    e.to_string(),
+                )
+            })?;
+
+        let (sender, mut receiver) = mpsc::channel(selected.len());
+        for endpoint in &selected {
+            let sender = sender.clone();
+            let name = endpoint.name.clone();
+            let target = endpoint.target.clone();
+            let stats = endpoint.stats.clone();
+            let encoded_tx = encoded_tx.clone();
+            let transaction = transaction.clone();
+            // Spawned so every endpoint receives the transaction even after the first acceptance
+            tokio::spawn(async move {
+                let start_time = Instant::now();
+                let result = match target {
+                    Target::BlockEngine(client) => client
+                        .try_send_transaction(&encoded_tx)
+                        .await
+                        .and_then(|sig| {
+                            Signature::from_str(&sig).map_err(|e| {
+                                ClientError::Parse("Invalid signature".to_string(), e.to_string())
+                            })
+                        }),
+                    Target::Rpc(client) => client
+                        .send_transaction_with_config(
+                            &transaction,
+                            RpcSendTransactionConfig {
+                                skip_preflight: true,
+                                ..RpcSendTransactionConfig::default()
+                            },
+                        )
+                        .await
+                        .map_err(ClientError::from),
+                };
+                let elapsed = start_time.elapsed();
+                let outcome = {
+                    let mut stats = stats.lock().unwrap();
+                    match result {
+                        Ok(signature) => {
+                            stats.record_success(elapsed);
+                            Ok(signature)
+                        }
+                        Err(err) => {
+                            stats.record_error(&err);
+                            Err(err.to_string())
+                        }
+                    }
+                };
+                let _ = sender.send((name, outcome, elapsed)).await;
+            });
+        }
+        drop(sender);
+
+        let mut errors = vec![];
+        while let Some((name, result, elapsed)) = receiver.recv().await {
+            match result {
+                Ok(signature) => {
+                    logger.log(format!("accepted by {} in {:?}", name, elapsed));
+                    return Ok(signature);
+                }
+                Err(err) => errors.push(format!("{}: {}", name, err)),
+            }
+        }
+
+        Err(ClientError::Jito(
+            "All block engines rejected the transaction".to_string(),
+            errors.join("; "),
+        ))
+    }
+}
+
+fn is_rate_limited(err: &ClientError) -> bool {
+    match err {
+        ClientError::RateLimitExceeded => true,
+        ClientError::Solana(_, details) | ClientError::Jito(_, details) => {
+            let details = details.to_lowercase();
+            details.contains("rate limit") || details.contains("429")
+        }
+        _ => false,
+    }
+}
@@ -1,10 +1,9 @@
 use anyhow::{anyhow, Result};
-use indicatif::{ProgressBar, ProgressStyle};
 use rand::{seq::IteratorRandom, thread_rng};
 use serde::Deserialize;
 use serde_json::Value;
 use solana_sdk::pubkey::Pubkey;
-use std::{future::Future, str::FromStr, time::Duration};
+use std::{str::FromStr, time::Duration};
 use tokio::time::{sleep, Instant};
 
 pub fn get_tip_account() -> Result<Pubkey> {
@@ -35,89 +34,25 @@ pub struct BundleStatus {
     pub transactions: Vec<String>,
     pub slot: u64,
     pub confirmation_status: String,
-    pub err: ErrorStatus,
+    pub err: Value,
 }
-#[derive(Deserialize, Debug)]
-pub struct ErrorStatus {
-    #[serde(rename = "Ok")]
-    pub ok: Option<()>,
-}
-
-pub async fn wait_for_bundle_confirmation<F, Fut>(
-    fetch_statuses: F,
-    bundle_id: String,
-    interval: Duration,
-    timeout: Duration,
-) -> Result<Vec<String>>
-where
-    F: Fn(String) -> Fut,
-    Fut: Future<Output = Result<Vec<Value>>>,
-{
-    let progress_bar = new_progress_bar();
-    let start_time = Instant::now();
-
-    loop {
-        let statuses = fetch_statuses(bundle_id.clone()).await?;
-
-        if let Some(status) = statuses.first() {
-            let bundle_status: BundleStatus =
-                serde_json::from_value(status.clone()).inspect_err(|err| {
-                    println!(
-                        "Failed to parse JSON when get_bundle_statuses, err: {}",
-                        err,
-                    );
-                })?;
-
-            println!("{:?}", bundle_status);
-            match bundle_status.confirmation_status.as_str() {
-                "finalized" | "confirmed" => {
-                    progress_bar.finish_and_clear();
-                    println!(
-                        "Finalized bundle {}: {}",
-                        bundle_id, bundle_status.confirmation_status
-                    );
-                    // // print tx
-                    // bundle_status
-                    //     .transactions
-                    //     .iter()
-                    //     .for_each(|tx| println!("https://solscan.io/tx/{}", tx));
-                    return Ok(bundle_status.transactions[0..2].to_vec());
-                }
-                _ => {
-                    progress_bar.set_message(format!(
-                        "Finalizing bundle {}: {}",
-                        bundle_id, bundle_status.confirmation_status
-                    ));
-                }
-            }
-        } else {
-            progress_bar.set_message(format!("Finalizing bundle {}: {}", bundle_id, "None"));
-        }
-
-        // check loop exceeded 1 minute,
-        if start_time.elapsed() > timeout {
-            println!("Loop exceeded {:?}, breaking out.", timeout);
-            return Err(anyhow!("Bundle status get timeout"));
-        }
 
-        // Wait for a certain duration before retrying
-        sleep(interval).await;
+impl BundleStatus {
+    pub fn is_ok(&self) -> bool {
+        self.err.get("Ok").is_some()
     }
 }
-pub fn new_progress_bar() -> ProgressBar {
-    let progress_bar = ProgressBar::new(42);
-    progress_bar.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {wide_msg}")
-            .expect("ProgressStyle::template direct input to be correct"),
-    );
-    progress_bar.enable_steady_tick(Duration::from_millis(100));
-    progress_bar
+
+#[derive(Deserialize, Debug)]
+pub struct InflightBundleStatus {
+    pub bundle_id: String,
+    pub status: String,
+    pub landed_slot: Option<u64>,
 }
 
-use crate::error::ClientError;
+use crate::{error::ClientError, utils::logger::Logger};
 use anchor_client::solana_sdk::{
-    commitment_config::CommitmentConfig, signature::Signature, transaction::Transaction,
+    commitment_config::CommitmentConfig, signature::Signature, transaction::VersionedTransaction,
 };
 use bincode;
 use bs64;
@@ -126,6 +61,9 @@ use serde_json::json;
 
 pub const MAX_RETRIES: u8 = 3;
 pub const RETRY_DELAY: Duration = Duration::from_millis(200);
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+pub const BUNDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+pub const BUNDLE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone)]
 pub struct TransactionConfig {
@@ -154,17 +92,26 @@ pub struct JitoClient {
 }
 
 impl JitoClient {
+    /// `endpoint` is the block engine base url, e.g. `https://mainnet.block-engine.jito.wtf`.
     pub fn new(endpoint: &str) -> Self {
         Self {
-            endpoint: endpoint.to_string(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
             client: reqwest::Client::new(),
             config: TransactionConfig::default(),
         }
     }
 
+    fn transactions_url(&self) -> String {
+        format!("{}/api/v1/transactions", self.endpoint)
+    }
+
+    fn bundles_url(&self) -> String {
+        format!("{}/api/v1/bundles", self.endpoint)
+    }
+
     pub async fn send_transaction(
         &self,
-        transaction: &Transaction,
+        transaction: &VersionedTransaction,
     ) -> Result<Signature, ClientError> {
         let wire_transaction = bincode::serialize(transaction).map_err(|e| {
             ClientError::Parse(
@@ -195,7 +142,10 @@ impl JitoClient {
         Err(ClientError::Other("Max retries exceeded".to_string()))
     }
 
-    async fn try_send_transaction(&self, encoded_tx: &str) -> Result<String, ClientError> {
+    pub(crate) async fn try_send_transaction(
+        &self,
+        encoded_tx: &str,
+    ) -> Result<String, ClientError> {
         let params = json!([
             encoded_tx,
             {
@@ -207,7 +157,9 @@ impl JitoClient {
             }
         ]);
 
-        let response = self.send_request("sendTransaction", params).await?;
+        let response = self
+            .send_request(&self.transactions_url(), "sendTransaction", params)
+            .await?;
 
         response["result"]
             .as_str()
@@ -220,7 +172,192 @@ impl JitoClient {
             })
     }
 
-    async fn send_request(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+    /// Submits the transactions as a single atomic bundle and returns the bundle id.
+    pub async fn send_bundle(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, ClientError> {
+        if transactions.is_empty() || transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+            return Err(ClientError::InvalidInput(
+                "bundle must contain between 1 and 5 transactions",
+            ));
+        }
+
+        let encoded_txs = transactions
+            .iter()
+            .map(|tx| {
+                bincode::serialize(tx)
+                    .map(|wire| bs64::encode(&wire))
+                    .map_err(|e| {
+                        ClientError::Parse(
+                            "Transaction serialization failed".to_string(),
+                            e.to_string(),
+                        )
+                    })
+            })
+            .collect::<Result<Vec<String>, ClientError>>()?;
+
+        let params = json!([encoded_txs, { "encoding": self.config.encoding }]);
+        let response = self
+            .send_request(&self.bundles_url(), "sendBundle", params)
+            .await
+            .map_err(into_jito_error)?;
+
+        response["result"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| {
+                ClientError::Jito(
+                    "Invalid sendBundle response".to_string(),
+                    response.to_string(),
+                )
+            })
+    }
+
+    pub async fn get_bundle_statuses(
+        &self,
+        bundle_ids: &[String],
+    ) -> Result<Vec<Option<BundleStatus>>, ClientError> {
+        let response = self
+            .send_request(
+                &self.bundles_url(),
+                "getBundleStatuses",
+                json!([bundle_ids]),
+            )
+            .await
+            .map_err(into_jito_error)?;
+
+        parse_status_values(&response)
+    }
+
+    pub async fn get_inflight_bundle_statuses(
+        &self,
+        bundle_ids: &[String],
+    ) -> Result<Vec<Option<InflightBundleStatus>>, ClientError> {
+        let response = self
+            .send_request(
+                &self.bundles_url(),
+                "getInflightBundleStatuses",
+                json!([bundle_ids]),
+            )
+            .await
+            .map_err(into_jito_error)?;
+
+        parse_status_values(&response)
+    }
+
+    /// Polls the block engine until the bundle lands, fails or `timeout` elapses.
+    /// Returns the signatures of the bundled transactions once confirmed.
+    /// Status changes are reported through `logger` at debug level.
+    pub async fn wait_for_bundle(
+        &self,
+        bundle_id: &str,
+        interval: Duration,
+        timeout: Duration,
+        logger: &Logger,
+    ) -> Result<Vec<String>, ClientError> {
+        let ids = vec![bundle_id.to_string()];
+        let start_time = Instant::now();
+        let mut last_reported = String::new();
+        let mut report = |status: &str| {
+            if status != last_reported {
+                logger.debug(format!("confirming bundle {}: {}", bundle_id, status));
+                last_reported = status.to_string();
+            }
+        };
+
+        loop {
+            let inflight = self
+                .get_inflight_bundle_statuses(&ids)
+                .await?
+                .into_iter()
+                .flatten()
+                .next();
+
+            match inflight.as_ref().map(|status| status.status.as_str()) {
+                Some("Failed") => {
+                    return Err(ClientError::Jito(
+                        "Bundle failed".to_string(),
+                        format!("{}: all regions rejected the bundle", bundle_id),
+                    ));
+                }
+                Some("Landed") | None => {
+                    // Landed (or already aged out of the inflight window), look up the final status.
+                    if let Some(status) = self
+                        .get_bundle_statuses(&ids)
+                        .await?
+                        .into_iter()
+                        .flatten()
+                        .next()
+                    {
+                        if !status.is_ok() {
+                            return Err(ClientError::Jito(
+                                "Bundle reverted".to_string(),
+                                format!("{}: {}", bundle_id, status.err),
+                            ));
+                        }
+                        match status.confirmation_status.as_str() {
+                            "finalized" | "confirmed" => {
+                                logger.debug(format!(
+                                    "confirmed bundle {} in slot {}: {}",
+                                    bundle_id, status.slot, status.confirmation_status
+                                ));
+                                return Ok(status.transactions);
+                            }
+                            _ => report(&status.confirmation_status),
+                        }
+                    } else {
+                        report("None");
+                    }
+                }
+                // Pending, or Invalid until the bundle propagates to the engine
+                Some(status) => report(status),
+            }
+
+            if start_time.elapsed() > timeout {
+                let last_status = inflight
+                    .map(|status| status.status)
+                    .unwrap_or_else(|| "None".to_string());
+                if last_status == "Invalid" {
+                    return Err(ClientError::Jito(
+                        "Bundle invalid".to_string(),
+                        format!("{}: bundle was dropped by the block engine", bundle_id),
+                    ));
+                }
+                return Err(ClientError::Timeout(
+                    "Bundle confirmation".to_string(),
+                    format!(
+                        "{} after {:?}, last status: {}",
+                        bundle_id, timeout, last_status
+                    ),
+                ));
+            }
+
+            sleep(interval).await;
+        }
+    }
+
+    pub async fn send_and_confirm_bundle(
+        &self,
+        transactions: &[VersionedTransaction],
+        logger: &Logger,
+    ) -> Result<Vec<String>, ClientError> {
+        let bundle_id = self.send_bundle(transactions).await?;
+        self.wait_for_bundle(
+            &bundle_id,
+            BUNDLE_POLL_INTERVAL,
+            BUNDLE_CONFIRM_TIMEOUT,
+            logger,
+        )
+        .await
+    }
+
+    async fn send_request(
+        &self,
+        url: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, ClientError> {
         let request_body = json!({
             "jsonrpc": "2.0",
             "id": 1,
@@ -230,13 +367,17 @@ impl JitoClient {
 
         let response = self
             .client
-            .post(&self.endpoint)
+            .post(url)
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send()
             .await
             .map_err(|e| ClientError::Solana("Request failed".to_string(), e.to_string()))?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(ClientError::RateLimitExceeded);
+        }
+
         let response_data: Value = response
             .json()
             .await
@@ -252,3 +393,37 @@ impl JitoClient {
         Ok(response_data)
     }
 }
+
+fn parse_status_values<T: serde::de::DeserializeOwned>(
+    response: &Value,
+) -> Result<Vec<Option<T>>, ClientError> {
+    let values = response["result"]["value"].as_array().ok_or_else(|| {
+        ClientError::Jito(
+            "Invalid bundle status response".to_string(),
+            response.to_string(),
+        )
+    })?;
+
+    values
+        .iter()
+        .map(|value| {
+            if value.is_null() {
+                Ok(None)
+            } else {
+                serde_json::from_value(value.clone())
+                    .map(Some)
+                    .map_err(|e| {
+                        ClientError::Parse("Invalid bundle status".to_string(), e.to_string())
+                    })
+            }
+        })
+        .collect()
+}
+
+/// Block engine rejections come back as JSON-RPC errors; surface them as `ClientError::Jito`.
+fn into_jito_error(err: ClientError) -> ClientError {
+    match err {
+        ClientError::Solana(msg, details) => ClientError::Jito(msg, details),
+        other => other,
+    }
+}
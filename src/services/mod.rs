@@ -1 +1,3 @@
-pub mod jito;
\ No newline at end of file
+pub mod fanout;
+pub mod jito;
+pub mod rpc_pool;
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    future::Future,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rand::Rng;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::time::Instant;
+
+use crate::utils::logger::Logger;
+
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+pub const MAX_SLOT_LAG: u64 = 20;
+// request failures in a row before an endpoint is skipped until the next health check
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+// weight of the newest sample in the latency moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+// a subscription endpoint that dropped or lagged is tried last for this long
+const SUBSCRIPTION_PENALTY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Clone)]
+pub struct EndpointHealth {
+    pub slot: Option<u64>,
+    pub slot_lag: u64,
+    pub latency_ms: f64,
+    pub requests: u64,
+    pub errors: u64,
+    consecutive_failures: u32,
+    lagging: bool,
+}
+
+impl EndpointHealth {
+    pub fn is_healthy(&self) -> bool {
+        !self.lagging && self.consecutive_failures < MAX_CONSECUTIVE_FAILURES
+    }
+
+    fn record_latency(&mut self, elapsed: Duration) {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        self.latency_ms = if self.latency_ms == 0.0 {
+            elapsed_ms
+        } else {
+            LATENCY_EWMA_ALPHA * elapsed_ms + (1.0 - LATENCY_EWMA_ALPHA) * self.latency_ms
+        };
+    }
+
+    fn record_success(&mut self, elapsed: Duration) {
+        self.requests += 1;
+        self.consecutive_failures = 0;
+        self.record_latency(elapsed);
+    }
+
+    fn record_error(&mut self) {
+        self.requests += 1;
+        self.errors += 1;
+        self.consecutive_failures += 1;
+    }
+}
+
+#[derive(Clone)]
+pub struct RpcEndpoint {
+    pub url: String,
+    pub weight: u32,
+    pub client: Arc<RpcClient>,
+    health: Arc<Mutex<EndpointHealth>>,
+}
+
+impl RpcEndpoint {
+    fn new(url: String, weight: u32, commitment: CommitmentConfig) -> Self {
+        Self {
+            client: Arc::new(RpcClient::new_with_commitment(url.clone(), commitment)),
+            url,
+            weight,
+            health: Arc::new(Mutex::new(EndpointHealth::default())),
+        }
+    }
+
+    pub fn health(&self) -> EndpointHealth {
+        self.health.lock().unwrap().clone()
+    }
+}
+
+/// Weighted RPC endpoints split into separate pools for reads, sends and
+/// subscriptions. Reads and sends fail over to the next endpoint on error;
+/// endpoints that lag behind the highest seen slot are skipped.
+pub struct RpcPool {
+    reads: Vec<RpcEndpoint>,
+    sends: Vec<RpcEndpoint>,
+    subscriptions: Vec<(String, u32)>,
+    max_slot_lag: u64,
+    // highest slot seen by the health checks
+    highest_slot: AtomicU64,
+    // subscription url -> when it last dropped or lagged
+    failed_subscriptions: Mutex<HashMap<String, Instant>>,
+}
+
+impl RpcPool {
+    pub fn new(
+        reads: Vec<(String, u32)>,
+        sends: Vec<(String, u32)>,
+        subscriptions: Vec<(String, u32)>,
+        commitment: CommitmentConfig,
+    ) -> Self {
+        let reads: Vec<RpcEndpoint> = reads
+            .into_iter()
+            .map(|(url, weight)| RpcEndpoint::new(url, weight, commitment))
+            .collect();
+        // an endpoint in both pools shares its client and health
+        let sends = sends
+            .into_iter()
+            .map(|(url, weight)| match reads.iter().find(|e| e.url == url) {
+                Some(endpoint) => RpcEndpoint {
+                    weight,
+                    ..endpoint.clone()
+                },
+                None => RpcEndpoint::new(url, weight, commitment),
+            })
+            .collect();
+        Self {
+            reads,
+            sends,
+            subscriptions,
+            max_slot_lag: MAX_SLOT_LAG,
+            highest_slot: AtomicU64::new(0),
+            failed_subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Builds the pool from `RPC_READ_URLS`, `RPC_SEND_URLS` and `RPC_WSS_URLS`
+    /// (comma separated, `url|weight`), falling back to `rpc_https` and `rpc_wss`,
+    /// and starts the health checks.
+    pub fn init_from_env(
+        rpc_https: &str,
+        rpc_wss: &str,
+        commitment: CommitmentConfig,
+    ) -> Arc<RpcPool> {
+        let reads =
+            endpoints_from_env("RPC_READ_URLS").unwrap_or_else(|| vec![(rpc_https.to_string(), 1)]);
+        let sends = endpoints_from_env("RPC_SEND_URLS").unwrap_or_else(|| reads.clone());
+        let subscriptions =
+            endpoints_from_env("RPC_WSS_URLS").unwrap_or_else(|| vec![(rpc_wss.to_string(), 1)]);
+        let interval = env::var("RPC_HEALTH_CHECK_INTERVAL_MS")
+            .ok()
+            .and_then(|v| u64::from_str(&v).ok())
+            .map(Duration::from_millis)
+            .unwrap_or(HEALTH_CHECK_INTERVAL);
+        let max_slot_lag = env::var("RPC_MAX_SLOT_LAG")
+            .ok()
+            .and_then(|v| u64::from_str(&v).ok())
+            .unwrap_or(MAX_SLOT_LAG);
+
+        let pool = Arc::new(RpcPool {
+            max_slot_lag,
+            ..RpcPool::new(reads, sends, subscriptions, commitment)
+        });
+        pool.clone().spawn_health_checks(interval);
+        pool
+    }
+
+    fn endpoints(&self) -> impl Iterator<Item = &RpcEndpoint> {
+        let sends = self
+            .sends
+            .iter()
+            .filter(|send| !self.reads.iter().any(|read| read.url == send.url));
+        self.reads.iter().chain(sends)
+    }
+
+    pub fn endpoint_names(&self) -> Vec<String> {
+        self.endpoints().map(|e| e.url.clone()).collect()
+    }
+
+    pub fn stats(&self) -> Vec<(String, EndpointHealth)> {
+        self.endpoints()
+            .map(|e| (e.url.clone(), e.health()))
+            .collect()
+    }
+
+    fn spawn_health_checks(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let logger = Logger::new("[RPC-POOL] => ".to_string());
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                self.check_health(&logger).await;
+            }
+        });
+    }
+
+    /// Polls every endpoint's slot and marks the ones more than `max_slot_lag`
+    /// slots behind the highest as lagging.
+    pub async fn check_health(&self, logger: &Logger) {
+        let max_slot_lag = self.max_slot_lag;
+        let endpoints: Vec<RpcEndpoint> = self.endpoints().cloned().collect();
+        let checks = endpoints.iter().map(|endpoint| async move {
+            let start_time = Instant::now();
+            let slot = endpoint
+                .client
+                .get_slot_with_commitment(CommitmentConfig::processed())
+                .await;
+            (endpoint, slot, start_time.elapsed())
+        });
+        let results = futures_util::future::join_all(checks).await;
+
+        let highest_slot = results
+            .iter()
+            .filter_map(|(_, slot, _)| slot.as_ref().ok().copied())
+            .max()
+            .unwrap_or(0);
+        self.highest_slot.fetch_max(highest_slot, Ordering::Relaxed);
+        for (endpoint, slot, elapsed) in results {
+            let mut health = endpoint.health.lock().unwrap();
+            match slot {
+                Ok(slot) => {
+                    let was_healthy = health.is_healthy();
+                    health.slot = Some(slot);
+                    health.slot_lag = highest_slot.saturating_sub(slot);
+                    health.lagging = health.slot_lag > max_slot_lag;
+                    health.consecutive_failures = 0;
+                    health.record_latency(elapsed);
+                    if was_healthy != health.is_healthy() {
+                        logger.log(format!(
+                            "{} is {} (lag {} slots)",
+                            endpoint.url,
+                            if health.is_healthy() {
+                                "healthy"
+                            } else {
+                                "lagging"
+                            },
+                            health.slot_lag
+                        ));
+                    }
+                }
+                Err(e) => {
+                    health.lagging = true;
+                    logger.debug(format!("health check failed for {}: {}", endpoint.url, e));
+                }
+            }
+        }
+    }
+
+    /// Endpoints in the order they should be tried: a weighted pick among the
+    /// healthy ones first, the other healthy ones by latency, then the rest.
+    fn ranked(endpoints: &[RpcEndpoint]) -> Vec<&RpcEndpoint> {
+        let (mut healthy, mut unhealthy): (Vec<&RpcEndpoint>, Vec<&RpcEndpoint>) =
+            endpoints.iter().partition(|e| e.health().is_healthy());
+        let by_latency = |a: &&RpcEndpoint, b: &&RpcEndpoint| {
+            a.health().latency_ms.total_cmp(&b.health().latency_ms)
+        };
+        healthy.sort_by(by_latency);
+        unhealthy.sort_by(by_latency);
+
+        let total_weight: u32 = healthy.iter().map(|e| e.weight).sum();
+        if total_weight > 0 {
+            let mut pick = rand::thread_rng().gen_range(0..total_weight);
+            if let Some(index) = healthy.iter().position(|e| {
+                if pick < e.weight {
+                    true
+                } else {
+                    pick -= e.weight;
+                    false
+                }
+            }) {
+                let first = healthy.remove(index);
+                healthy.insert(0, first);
+            }
+        }
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    async fn with_failover<T, E, F, Fut>(endpoints: &[RpcEndpoint], op: F) -> Result<T, E>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let mut last_err = None;
+        for endpoint in Self::ranked(endpoints) {
+            let start_time = Instant::now();
+            match op(endpoint.client.clone()).await {
+                Ok(value) => {
+                    endpoint
+                        .health
+                        .lock()
+                        .unwrap()
+                        .record_success(start_time.elapsed());
+                    return Ok(value);
+                }
+                Err(e) => {
+                    endpoint.health.lock().unwrap().record_error();
+                    println!("RPC {} failed, trying next endpoint: {}", endpoint.url, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.expect("rpc pool has no endpoints"))
+    }
+
+    /// Runs a read against the read pool, failing over on error.
+    pub async fn read<T, E, F, Fut>(&self, op: F) -> Result<T, E>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        Self::with_failover(&self.reads, op).await
+    }
+
+    /// Runs a send against the send pool, failing over on error.
+    pub async fn send<T, E, F, Fut>(&self, op: F) -> Result<T, E>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        Self::with_failover(&self.sends, op).await
+    }
+
+    /// The preferred read client, for callers that need a client rather than a
+    /// single request.
+    pub fn read_client(&self) -> Arc<RpcClient> {
+        Self::ranked(&self.reads)[0].client.clone()
+    }
+
+    /// Subscription URLs in the order they should be tried: by weight, with
+    /// the ones that recently dropped or lagged last.
+    pub fn subscription_urls(&self) -> Vec<String> {
+        let failed = self.failed_subscriptions.lock().unwrap();
+        let recently_failed = |url: &str| {
+            failed
+                .get(url)
+                .is_some_and(|failed_at| failed_at.elapsed() < SUBSCRIPTION_PENALTY)
+        };
+        let mut urls = self.subscriptions.clone();
+        urls.sort_by(|a, b| {
+            recently_failed(&a.0)
+                .cmp(&recently_failed(&b.0))
+                .then(b.1.cmp(&a.1))
+        });
+        urls.into_iter().map(|(url, _)| url).collect()
+    }
+
+    /// Moves a subscription endpoint that dropped or lagged to the back.
+    pub fn mark_subscription_failed(&self, url: &str) {
+        self.failed_subscriptions
+            .lock()
+            .unwrap()
+            .insert(url.to_string(), Instant::now());
+    }
+
+    /// Whether a notification for `slot` is more than `max_slot_lag` slots
+    /// behind the highest slot the health checks have seen.
+    pub fn is_slot_lagging(&self, slot: u64) -> bool {
+        self.highest_slot
+            .load(Ordering::Relaxed)
+            .saturating_sub(slot)
+            > self.max_slot_lag
+    }
+}
+
+fn endpoints_from_env(key: &str) -> Option<Vec<(String, u32)>> {
+    env::var(key)
+        .ok()
+        .map(|urls| {
+            urls.split(',')
+                .map(|entry| entry.trim())
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| match entry.rsplit_once('|') {
+                    Some((url, weight)) => (url.to_string(), u32::from_str(weight).unwrap_or(1)),
+                    None => (entry.to_string(), 1),
+                })
+                .collect::<Vec<(String, u32)>>()
+        })
+        .filter(|endpoints| !endpoints.is_empty())
+}
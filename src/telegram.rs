@@ -1,8 +1,10 @@
-use serde_json::{json, to_string, Value};
+use serde_json::{json, Value};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::{bs58, signature::Keypair, signer::Signer};
+use spl_token::{native_mint::DECIMALS, ui_amount_to_amount};
 use std::collections::HashMap;
 use std::error::Error;
+use std::str::FromStr;
 use teloxide::{
     dispatching::{
         dialogue::{self, InMemStorage},
@@ -14,14 +16,28 @@ use teloxide::{
 };
 
 use crate::{
-    engine::monitor::copytrader_pumpfun,
+    core::{
+        lookup_table::create_wallet_lookup_table,
+        nonce::create_nonce_account,
+        paper::{self, PaperAccount, DEFAULT_PAPER_SOL},
+    },
+    dex::pump_fun::get_global,
+    engine::{
+        analytics::analyze, backtest::fetch_fixture, gate::CopyGate, mirror::MirrorMode,
+        monitor::copytrader_pumpfun,
+    },
     msg::{setting_op_keyboard, start_op_keyboard, SettingOp, StartOp},
+    services::fanout::JitoFanout,
     utils::{
-        file::{read_info, write_info},
+        config::Config,
+        file::{read_info, update_info},
         logger::Logger,
     },
 };
 
+/// Transactions of a wallet's history `/analyze` looks at.
+const ANALYZE_HISTORY: usize = 500;
+
 type MyDialogue = Dialogue<ChatState, InMemStorage<ChatState>>;
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
@@ -51,6 +67,28 @@ enum Command {
     Start,
     #[command(description = "Stop the copy-trading bot")]
     Stop,
+    #[command(description = "Create an address lookup table for your wallet")]
+    LookupTable,
+    #[command(description = "Create a durable nonce account for your wallet")]
+    Nonce,
+    #[command(description = "Toggle dry-run: quote copies and record them without sending")]
+    DryRun,
+    #[command(description = "Paper trade with a virtual SOL balance: /paper <sol> or /paper off")]
+    Paper(String),
+    #[command(description = "Show the paper account balance, positions and PnL")]
+    Pnl,
+    #[command(description = "Score a wallet from its trading history: /analyze <address>")]
+    Analyze(String),
+    #[command(
+        description = "Choose which trades of a target to copy: /mirror <both|buys|sells|own_exits> [target]"
+    )]
+    Mirror(String),
+    #[command(
+        description = "Choose when trades are copied: /gate <processed|confirmed|delay <ms>>"
+    )]
+    Gate(String),
+    #[command(description = "Show send stats of each block engine")]
+    Status,
 }
 
 pub async fn run(bot: Bot) {
@@ -73,6 +111,15 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
         .branch(case![Command::Start].endpoint(start))
         .branch(case![Command::Help].endpoint(help))
         .branch(case![Command::Stop].endpoint(cancel))
+        .branch(case![Command::LookupTable].endpoint(lookup_table))
+        .branch(case![Command::Nonce].endpoint(nonce_account))
+        .branch(case![Command::DryRun].endpoint(dry_run))
+        .branch(case![Command::Paper(arg)].endpoint(paper_trading))
+        .branch(case![Command::Pnl].endpoint(paper_pnl))
+        .branch(case![Command::Analyze(address)].endpoint(analyze_wallet))
+        .branch(case![Command::Mirror(args)].endpoint(mirror_mode))
+        .branch(case![Command::Gate(arg)].endpoint(copy_gate))
+        .branch(case![Command::Status].endpoint(status))
         .branch(dptree::endpoint(invalid_command));
 
     // Text
@@ -200,7 +247,8 @@ async fn setting_cb(bot: Bot, dialogue: MyDialogue, q: CallbackQuery) -> Handler
                 dialogue.update(ChatState::AddWallet).await?;
             }
             SettingOp::Target => {
-                let text = "Target Wallet: Whale, Trader\n";
+                let text =
+                    "Target Wallet: Whale, Trader\n Score it first with /analyze <address>\n";
                 bot.send_message(chat_id, text).await?;
                 dialogue.update(ChatState::TargetSet).await?;
             }
@@ -210,8 +258,6 @@ async fn setting_cb(bot: Bot, dialogue: MyDialogue, q: CallbackQuery) -> Handler
 }
 
 async fn add_wallet(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
-    let mut info = read_info(None).await?;
-
     // Check if chat ID already exists
     let chat_id = dialogue.chat_id().to_string();
 
@@ -249,12 +295,14 @@ async fn add_wallet(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResu
     };
 
     // Update JSON with new wallet data
-    info[&chat_id] = json!({
-        "private_key": &private_key,
-        "usage": 0
-    });
-
-    write_info(to_string(&info)?, None).await?;
+    update_info(None, |info| {
+        info[&chat_id] = json!({
+            "private_key": &private_key,
+            "usage": 0
+        });
+        Ok(())
+    })
+    .await?;
 
     // Send success message with public key
     let response = format!(
@@ -267,7 +315,7 @@ async fn add_wallet(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResu
 }
 
 async fn target_set(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResult {
-    let mut info = read_info(None).await?;
+    let info = read_info(None).await?;
 
     // Check if chat ID already exists
     let chat_id = dialogue.chat_id();
@@ -324,18 +372,16 @@ async fn target_set(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResu
             }
 
             // Merge existing wallet data with new target_address
-            let mut info_data = info
-                .get(&chat_id.to_string())
-                .cloned()
-                .unwrap_or_else(|| json!({}));
-            if let Some(obj) = info_data.as_object_mut() {
-                obj.insert("target_address".to_string(), json!(&target_address));
-            } else {
-                info_data = json!({ "target_address": &target_address });
-            }
-            info[&chat_id.to_string()] = info_data;
-
-            write_info(to_string(&info)?, None).await?;
+            update_info(None, |info| {
+                let info_data = &mut info[&chat_id.to_string()];
+                if let Some(obj) = info_data.as_object_mut() {
+                    obj.insert("target_address".to_string(), json!(&target_address));
+                } else {
+                    *info_data = json!({ "target_address": &target_address });
+                }
+                Ok(())
+            })
+            .await?;
 
             // Send success message with public key
             let response = format!("🎯 Target Address\n {}\n is correctly set", target_address);
@@ -346,6 +392,394 @@ async fn target_set(bot: Bot, dialogue: MyDialogue, msg: Message) -> HandlerResu
     Ok(())
 }
 
+async fn lookup_table(bot: Bot, msg: Message) -> HandlerResult {
+    let info = read_info(None).await?;
+    let chat_id = msg.chat.id.to_string();
+
+    let Some(user_data) = info.get(&chat_id) else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+    if let Some(table) = user_data.get("lookup_table").and_then(|v| v.as_str()) {
+        bot.send_message(
+            msg.chat.id,
+            format!("Lookup table is already created\n {}", table),
+        )
+        .await?;
+        return Ok(());
+    }
+    let Some(private_key) = user_data.get("private_key").and_then(|v| v.as_str()) else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+    let wallet = Keypair::from_base58_string(private_key);
+
+    bot.send_message(msg.chat.id, "Creating lookup table...")
+        .await?;
+    let rpc_client = Config::get().await.rpc_nonblocking_client.clone();
+    let logger = Logger::new("[LOOKUP-TABLE] => ".to_string());
+    let table = match create_wallet_lookup_table(&rpc_client, &wallet, &logger).await {
+        Ok(table) => table,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("Failed to create lookup table: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    update_info(None, |info| {
+        if let Some(obj) = info[&chat_id].as_object_mut() {
+            obj.insert("lookup_table".to_string(), json!(table.to_string()));
+        }
+        Ok(())
+    })
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🗂 Lookup table\n {}\n is created and will be used for swaps",
+            table
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn nonce_account(bot: Bot, msg: Message) -> HandlerResult {
+    let info = read_info(None).await?;
+    let chat_id = msg.chat.id.to_string();
+
+    let Some(user_data) = info.get(&chat_id) else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+    if let Some(nonce_account) = user_data.get("nonce_account").and_then(|v| v.as_str()) {
+        bot.send_message(
+            msg.chat.id,
+            format!("Nonce account is already created\n {}", nonce_account),
+        )
+        .await?;
+        return Ok(());
+    }
+    let Some(private_key) = user_data.get("private_key").and_then(|v| v.as_str()) else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+    let wallet = Keypair::from_base58_string(private_key);
+
+    bot.send_message(msg.chat.id, "Creating nonce account...")
+        .await?;
+    let rpc_client = Config::get().await.rpc_nonblocking_client.clone();
+    let logger = Logger::new("[NONCE] => ".to_string());
+    let nonce_account = match create_nonce_account(&rpc_client, &wallet, &logger).await {
+        Ok(nonce_account) => nonce_account,
+        Err(e) => {
+            bot.send_message(
+                msg.chat.id,
+                format!("Failed to create nonce account: {}", e),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    update_info(None, |info| {
+        if let Some(obj) = info[&chat_id].as_object_mut() {
+            obj.insert(
+                "nonce_account".to_string(),
+                json!(nonce_account.to_string()),
+            );
+        }
+        Ok(())
+    })
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🔐 Nonce account\n {}\n is created, copy transactions will be signed with it",
+            nonce_account
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn dry_run(bot: Bot, msg: Message) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+
+    let dry_run = update_info(None, |info| {
+        let Some(obj) = info.get_mut(&chat_id).and_then(|v| v.as_object_mut()) else {
+            return Ok(None);
+        };
+        let dry_run = !obj
+            .get("dry_run")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        obj.insert("dry_run".to_string(), json!(dry_run));
+        Ok(Some(dry_run))
+    })
+    .await?;
+    let Some(dry_run) = dry_run else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+
+    let response = if dry_run {
+        "🧪 Dry-run is on\n copies are quoted and recorded to the ledger, nothing is sent"
+    } else {
+        "🧪 Dry-run is off\n copies are sent again"
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+async fn paper_trading(bot: Bot, msg: Message, arg: String) -> HandlerResult {
+    let chat_id = msg.chat.id.to_string();
+    if read_info(None).await?.get(&chat_id).is_none() {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    }
+
+    let arg = arg.trim();
+    let account = if arg.eq_ignore_ascii_case("off") {
+        let account = paper::update(&chat_id, |account| {
+            account.map(|mut account| {
+                account.enabled = false;
+                account
+            })
+        })
+        .await?;
+        let Some(account) = account else {
+            bot.send_message(msg.chat.id, "Paper trading is not set up.")
+                .await?;
+            return Ok(());
+        };
+        account
+    } else if arg.is_empty() {
+        // resume the existing account, or open one with the default balance
+        let account = paper::update(&chat_id, |account| {
+            Some(match account {
+                Some(mut account) => {
+                    account.enabled = true;
+                    account
+                }
+                None => PaperAccount::new(ui_amount_to_amount(DEFAULT_PAPER_SOL, DECIMALS)),
+            })
+        })
+        .await?;
+        account.ok_or("paper account was not saved")?
+    } else {
+        match arg.parse::<f64>() {
+            Ok(sol) if sol > 0.0 => {
+                let account = PaperAccount::new(ui_amount_to_amount(sol, DECIMALS));
+                paper::save(&chat_id, &account).await?;
+                account
+            }
+            _ => {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /paper <sol> to start over with a balance, /paper to resume, /paper off to stop",
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let response = if account.enabled {
+        format!(
+            "📄 Paper trading is on\n copies are filled against your virtual balance, nothing is sent\n{}",
+            account.summary()
+        )
+    } else {
+        format!(
+            "📄 Paper trading is off\n copies are sent again\n{}",
+            account.summary()
+        )
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+async fn paper_pnl(bot: Bot, msg: Message) -> HandlerResult {
+    let response = match paper::load(&msg.chat.id.to_string()).await? {
+        Some(account) => format!("📄 Paper account\n{}", account.summary()),
+        None => "Paper trading is not set up. Start it with /paper <sol>".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+async fn analyze_wallet(bot: Bot, msg: Message, address: String) -> HandlerResult {
+    let Ok(wallet) = Pubkey::from_str(address.trim()) else {
+        bot.send_message(msg.chat.id, "Usage: /analyze <wallet address>")
+            .await?;
+        return Ok(());
+    };
+
+    bot.send_message(msg.chat.id, format!("Analyzing {}...", wallet))
+        .await?;
+    let (rpc_client, rpc_pool) = {
+        let config = Config::get().await;
+        (
+            config.rpc_nonblocking_client.clone(),
+            config.rpc_pool.clone(),
+        )
+    };
+    let logger = Logger::new("[ANALYZE] => ".to_string());
+    let analysis = async {
+        let global = get_global(rpc_pool).await?;
+        let fixture = fetch_fixture(&rpc_client, &wallet, ANALYZE_HISTORY, &logger).await?;
+        anyhow::Ok(analyze(&fixture, global.initial_virtual_sol_reserves))
+    };
+    let response = match analysis.await {
+        Ok(stats) if stats.trades == 0 => {
+            format!(
+                "No pump.fun, PumpSwap or Meteora trades found for {}",
+                wallet
+            )
+        }
+        Ok(stats) => format!("📊 {}\n{}", wallet, stats.summary()),
+        Err(e) => format!("Failed to analyze {}: {}", wallet, e),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+async fn mirror_mode(bot: Bot, msg: Message, args: String) -> HandlerResult {
+    let info = read_info(None).await?;
+    let chat_id = msg.chat.id.to_string();
+    let Some(obj) = info.get(&chat_id).and_then(|v| v.as_object()) else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+
+    let mut args = args.split_whitespace();
+    let mode = args.next();
+    // the chat's target unless another one is named
+    let Some(target) = args
+        .next()
+        .map(ToOwned::to_owned)
+        .or_else(|| obj.get("target_address")?.as_str().map(ToOwned::to_owned))
+    else {
+        bot.send_message(
+            msg.chat.id,
+            "The target address has not been configured yet.",
+        )
+        .await?;
+        return Ok(());
+    };
+    if Pubkey::from_str(&target).is_err() {
+        bot.send_message(msg.chat.id, format!("Invalid target address {}", target))
+            .await?;
+        return Ok(());
+    }
+
+    let Some(mode) = mode else {
+        let current = MirrorMode::for_target(&Value::Object(obj.clone()), &target);
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "🪞 Mirror mode of {}: {}\nUsage: /mirror <both|buys|sells|own_exits> [target]",
+                target, current
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+    let mode = match MirrorMode::from_str(mode) {
+        Ok(mode) => mode,
+        Err(e) => {
+            bot.send_message(msg.chat.id, e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    update_info(None, |info| {
+        if let Some(obj) = info.get_mut(&chat_id).and_then(|v| v.as_object_mut()) {
+            let modes = obj.entry("mirror_modes").or_insert_with(|| json!({}));
+            modes[&target] = json!(mode);
+        }
+        Ok(())
+    })
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("🪞 Mirror mode of {}\n is set to {}", target, mode),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn copy_gate(bot: Bot, msg: Message, arg: String) -> HandlerResult {
+    let info = read_info(None).await?;
+    let chat_id = msg.chat.id.to_string();
+    let Some(obj) = info.get(&chat_id).and_then(|v| v.as_object()) else {
+        bot.send_message(msg.chat.id, "Your wallet has not been set up yet.")
+            .await?;
+        return Ok(());
+    };
+
+    if arg.trim().is_empty() {
+        let current = CopyGate::for_chat(&Value::Object(obj.clone()));
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "⏱ Copies wait for: {}\nUsage: /gate <processed|confirmed|delay <ms>>",
+                current
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+    let gate = match CopyGate::from_str(&arg) {
+        Ok(gate) => gate,
+        Err(e) => {
+            bot.send_message(msg.chat.id, e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    update_info(None, |info| {
+        if let Some(obj) = info.get_mut(&chat_id).and_then(|v| v.as_object_mut()) {
+            obj.insert("copy_gate".to_string(), json!(gate));
+        }
+        Ok(())
+    })
+    .await?;
+
+    let response = match gate {
+        CopyGate::Processed => "⏱ Copies are sent as soon as the target's trade is seen".to_string(),
+        gate => format!(
+            "⏱ Copies wait for: {}\n copies of target trades that fail or are dropped are cancelled",
+            gate
+        ),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+async fn status(bot: Bot, msg: Message) -> HandlerResult {
+    // the fan-out is registered when the config loads
+    drop(Config::get().await);
+    let response = match JitoFanout::global() {
+        Some(fanout) => format!("📡 Block engines\n{}", fanout.summary()),
+        None => "No block engines are set up yet.".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
 async fn run_trading(bot: Bot, dialogue: MyDialogue) -> HandlerResult {
     // Read info from data.json
     let info: Value = read_info(None).await?;
@@ -4,13 +4,21 @@ use lazy_static::lazy_static;
 use reqwest::Error;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair};
 use std::sync::Arc;
 use tokio::sync::{Mutex, OnceCell};
 
 use crate::{
-    dex::pump_fun::PUMP_PROGRAM,
+    dex::{
+        meteora::{DAMM_PROGRAM, DLMM_PROGRAM},
+        pump_fun::{self, PUMP_PROGRAM},
+        pump_swap::PUMP_SWAP_PROGRAM,
+        raydium::AMM_PROGRAM,
+        raydium_clmm::CLMM_PROGRAM,
+        raydium_cpmm::CPMM_PROGRAM,
+    },
     engine::swap::{SwapDirection, SwapInType},
+    services::{fanout::JitoFanout, rpc_pool::RpcPool},
     utils::{constants::INIT_MSG, env::read_env, logger::Logger},
 };
 
@@ -20,10 +28,12 @@ pub struct Config {
     pub rpc_wss: String,
     pub rpc_client: Arc<solana_client::rpc_client::RpcClient>,
     pub rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    pub rpc_pool: Arc<RpcPool>,
     pub token_percent: f64,
     pub slippage: u64,
     pub jito_url: String,
     pub jito_tip_amount: f64,
+    pub jito_bundle: bool,
 }
 
 impl Config {
@@ -47,29 +57,48 @@ impl Config {
                     yellowstone_grpc_token,
                     jito_url,
                     jito_tip_amount,
+                    jito_bundle,
                 ) = read_env();
                 let solana_price = create_coingecko_proxy().await.unwrap_or(200_f64);
                 let rpc_client = create_rpc_client(rpc_https.clone(), commitment).unwrap();
                 let rpc_nonblocking_client =
                     create_nonblocking_rpc_client(rpc_https.clone(), commitment).unwrap();
+                let rpc_pool = RpcPool::init_from_env(&rpc_https, &rpc_wss, commitment);
+                let jito_fanout =
+                    JitoFanout::init_from_env(&jito_url, rpc_nonblocking_client.clone());
+                match pump_fun::start_global_refresher(rpc_pool.clone()).await {
+                    Ok(global) => logger.log(format!(
+                        "[PUMP GLOBAL]: fee recipient {}, fee {} bps",
+                        global.fee_recipient, global.fee_basis_points
+                    )),
+                    Err(e) => logger.error(format!("failed to load pump global account: {}", e)),
+                }
 
                 logger.log(format!(
                     "[COPYTRADER ENVIRONMENT]: \n\t\t\t\t [Web Socket RPC]: {},
                 \n\t\t\t\t * [Slippage]: {}, * [Solana]: {},
-                \n\t\t\t\t * [Amount(%)]: {}",
-                    rpc_wss, slippage, solana_price, token_percent,
+                \n\t\t\t\t * [Amount(%)]: {}, * [Block Engines]: {:?},
+                \n\t\t\t\t * [RPC Endpoints]: {:?}",
+                    rpc_wss,
+                    slippage,
+                    solana_price,
+                    token_percent,
+                    jito_fanout.endpoint_names(),
+                    rpc_pool.endpoint_names(),
                 ));
 
                 Mutex::new(Config {
                     rpc_wss,
                     rpc_client,
                     rpc_nonblocking_client,
+                    rpc_pool,
                     token_percent,
                     slippage,
                     yellowstone_grpc_http,
                     yellowstone_grpc_token,
                     jito_url,
                     jito_tip_amount,
+                    jito_bundle,
                 })
             })
             .await
@@ -94,7 +123,15 @@ lazy_static! {
         "params": [
             {
                 "failed": false,
-                "accountInclude": [PUMP_PROGRAM],
+                "accountInclude": [
+                    PUMP_PROGRAM,
+                    PUMP_SWAP_PROGRAM,
+                    DLMM_PROGRAM,
+                    DAMM_PROGRAM,
+                    AMM_PROGRAM,
+                    CPMM_PROGRAM,
+                    CLMM_PROGRAM,
+                ],
                 "accountExclude": [JUP_PUBKEY],
             },
             {
@@ -130,6 +167,9 @@ pub struct SwapConfig {
     pub amount_in: f64,
     pub slippage: u64,
     pub use_jito: bool,
+    pub use_bundle: bool,
+    pub lookup_table: Option<Pubkey>,
+    pub nonce_account: Option<Pubkey>,
 }
 
 pub fn create_rpc_client(
@@ -7,7 +7,18 @@ pub fn import_env_var(key: &str) -> String {
     env::var(key).unwrap_or_else(|e| panic!("Environment variable {} is not set: {}", key, e))
 }
 
-pub fn read_env() -> (String, String, CommitmentConfig, u64, f64, String, String, String, f64) {
+pub fn read_env() -> (
+    String,
+    String,
+    CommitmentConfig,
+    u64,
+    f64,
+    String,
+    String,
+    String,
+    f64,
+    bool,
+) {
     let rpc_https = import_env_var("RPC_HTTPS");
     let rpc_wss = import_env_var("RPC_WSS");
     let commitment = match import_env_var("COMMITMENT").as_str() {
@@ -25,6 +36,10 @@ pub fn read_env() -> (String, String, CommitmentConfig, u64, f64, String, String
     let jito_tip_amount = import_env_var("JITO_TIP_AMOUNT")
         .parse::<f64>()
         .unwrap_or(0.001);
+    // Send swaps as a [swap, tip] bundle and wait for it to land
+    let jito_bundle = env::var("JITO_BUNDLE")
+        .map(|v| v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
 
     (
         rpc_https,
@@ -36,6 +51,7 @@ pub fn read_env() -> (String, String, CommitmentConfig, u64, f64, String, String
         yellowstone_grpc_token,
         jito_url,
         jito_tip_amount,
+        jito_bundle,
     )
 }
 
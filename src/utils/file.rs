@@ -1,10 +1,18 @@
+use lazy_static::lazy_static;
 use serde_json::{from_slice, json, to_string, Value};
 use std::error::Error;
 use std::fs;
+use tokio::sync::Mutex;
 
 // Define a custom HandlerResult type for returning Value
 type HandlerResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
+lazy_static! {
+    // every read-modify-write of data.json holds this, so concurrent updates
+    // can't write back stale copies over each other
+    static ref INFO_LOCK: Mutex<()> = Mutex::new(());
+}
+
 pub async fn read_info(path: Option<String>) -> HandlerResult<Value> {
     // Use provided path or default to "data.json"
     let file_path = path.unwrap_or_else(|| "data.json".to_string());
@@ -25,28 +33,35 @@ pub async fn read_info(path: Option<String>) -> HandlerResult<Value> {
     Ok(info)
 }
 
-pub async fn write_info(data: String, path: Option<String>) -> HandlerResult<Value> {
-    // Use provided path or default to "data.json"
+/// Reads data.json, applies `update` and writes the result back, with no
+/// other update in between. Nothing is written when `update` fails.
+pub async fn update_info<T>(
+    path: Option<String>,
+    update: impl FnOnce(&mut Value) -> HandlerResult<T>,
+) -> HandlerResult<T> {
     let file_path = path.unwrap_or_else(|| "data.json".to_string());
 
-    // Parse the input data as JSON
-    let info: Value = serde_json::from_str(&data).map_err(|e| {
-        println!("Failed to parse input data as JSON: {}", e);
+    let _guard = INFO_LOCK.lock().await;
+    let mut info = read_info(Some(file_path.clone())).await?;
+    let result = update(&mut info)?;
+    store_info(&info, &file_path)?;
+    Ok(result)
+}
+
+fn store_info(info: &Value, file_path: &str) -> HandlerResult<()> {
+    let data = to_string(info).map_err(|e| {
+        println!("Failed to serialize JSON to {}: {}", file_path, e);
         Box::<dyn Error + Send + Sync>::from(e)
     })?;
 
-    // Write the parsed data to the file
-    fs::write(
-        &file_path,
-        to_string(&info).map_err(|e| {
-            println!("Failed to serialize JSON to {}: {}", file_path, e);
+    // Write next to the file and rename over it, so readers never see half a file
+    let tmp_path = format!("{}.tmp", file_path);
+    fs::write(&tmp_path, data)
+        .and_then(|_| fs::rename(&tmp_path, file_path))
+        .map_err(|e| {
+            println!("Failed to write to {}: {}", file_path, e);
             Box::<dyn Error + Send + Sync>::from(e)
-        })?,
-    )
-    .map_err(|e| {
-        println!("Failed to write to {}: {}", file_path, e);
-        Box::<dyn Error + Send + Sync>::from(e)
-    })?;
+        })?;
 
-    Ok(info)
+    Ok(())
 }
@@ -1,7 +1,7 @@
+pub mod config;
+pub mod constants;
 pub mod env;
 pub mod file;
 pub mod logger;
-pub mod validation;
-pub mod config;
-pub mod constants;
 pub mod solana;
+pub mod validation;
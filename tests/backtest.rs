@@ -0,0 +1,58 @@
+use raypump_copytrading_bot::engine::backtest::{replay, BacktestConfig, Fixture};
+
+// A target that buys coin A, buys coin B twice a slot apart, sells all of A,
+// sells coin C it held from before the fixture and makes a plain transfer.
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/pump_fun_copies.json"
+);
+const COIN_B: &str = "2VDW9dFE1ZXz4zWAbaBDQFynNVdRpQ73HyfSHMzBSL6Z";
+
+fn config(latency_slots: u64) -> BacktestConfig {
+    BacktestConfig {
+        starting_sol: 10.0,
+        token_percent: 50.0,
+        slippage: 5,
+        latency_slots,
+        price_impact_bps: 0,
+        fee_basis_points: 95,
+        creator_fee_basis_points: 5,
+        tip_sol: 0.001,
+    }
+}
+
+#[test]
+fn replays_copies_against_later_curves() {
+    let fixture = Fixture::load(FIXTURE).unwrap();
+    let report = replay(&fixture, &config(1));
+
+    assert_eq!(report.transactions, 6);
+    assert_eq!(report.buys, 2);
+    assert_eq!(report.sells, 1);
+    // the first B buy lands after the target's second one moved the curve
+    assert_eq!(report.slippage_failures, 1);
+    // the C sell has no copied position, the transfer nothing to copy
+    assert_eq!(report.skipped, 2);
+
+    // A was bought and sold back at a loss of fees and tips
+    assert_eq!(report.account.realized_pnl_lamports, -57_395_880);
+    assert_eq!(report.account.sol_lamports, 7_941_604_121);
+    assert_eq!(report.account.positions.len(), 1);
+    assert_eq!(report.account.positions[COIN_B].amount, 49_271_507_956_375);
+    assert_eq!(report.open_value_lamports, 1_762_791_583);
+    assert_eq!(report.equity_lamports(), 9_704_395_704);
+}
+
+#[test]
+fn copies_landing_in_the_same_slot_fill_at_the_quote() {
+    let fixture = Fixture::load(FIXTURE).unwrap();
+    let report = replay(&fixture, &config(0));
+
+    assert_eq!(report.buys, 3);
+    assert_eq!(report.sells, 1);
+    assert_eq!(report.slippage_failures, 0);
+    assert_eq!(report.skipped, 2);
+    assert_eq!(report.account.realized_pnl_lamports, -57_395_880);
+    assert_eq!(report.account.positions[COIN_B].amount, 65_601_173_373_680);
+    assert_eq!(report.open_value_lamports, 2_308_122_394);
+}